@@ -0,0 +1,296 @@
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::store;
+use crate::utils::json_u64;
+
+const MSAT_PER_SAT: u64 = 1_000;
+const MSAT_PER_BTC: u64 = 100_000_000_000;
+
+/// An amount of bitcoin in millisatoshis. Arithmetic is checked, nothing wraps or truncates
+/// silently; convert to whole sats explicitly where lnd or the chain needs them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Amount(u64);
+
+/// Unit amounts are shown and entered in, in the order of the unit combo box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Unit {
+    #[default]
+    Sat,
+    Msat,
+    Btc,
+}
+
+impl Unit {
+    pub fn from_index(index: i32) -> Self {
+        match index {
+            1 => Unit::Msat,
+            2 => Unit::Btc,
+            _ => Unit::Sat,
+        }
+    }
+
+    pub fn index(&self) -> i32 {
+        match self {
+            Unit::Sat => 0,
+            Unit::Msat => 1,
+            Unit::Btc => 2,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Unit::Sat => "sat",
+            Unit::Msat => "msat",
+            Unit::Btc => "BTC",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [Unit::Sat, Unit::Msat, Unit::Btc].into_iter().find(|unit| unit.as_str() == name)
+    }
+
+    fn msat_per_unit(&self) -> u64 {
+        match self {
+            Unit::Sat => MSAT_PER_SAT,
+            Unit::Msat => 1,
+            Unit::Btc => MSAT_PER_BTC,
+        }
+    }
+
+    /// Decimal places of one msat in this unit.
+    fn max_decimals(&self) -> usize {
+        match self {
+            Unit::Sat => 3,
+            Unit::Msat => 0,
+            Unit::Btc => 11,
+        }
+    }
+}
+
+/// Unit amounts are displayed in, chosen in the sidebar and shared by every network.
+pub fn display_unit(db: &sled::Db) -> Unit {
+    store::get_setting(db, store::DISPLAY_UNIT_KEY).ok().flatten()
+        .and_then(|name| Unit::from_name(&name))
+        .unwrap_or_default()
+}
+
+pub fn set_display_unit(db: &sled::Db, unit: Unit) -> Result<()> {
+    store::set_setting(db, store::DISPLAY_UNIT_KEY, unit.as_str())
+}
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    pub const fn from_msat(msat: u64) -> Self {
+        Amount(msat)
+    }
+
+    pub fn from_sat(sat: u64) -> Result<Self> {
+        sat.checked_mul(MSAT_PER_SAT)
+            .map(Amount)
+            .ok_or_else(|| anyhow!("{} sat is too large", sat))
+    }
+
+    /// lncli reports sats as strings or numbers, `json_u64` accepts either.
+    pub fn from_json_sat(value: &Value) -> Self {
+        Amount(json_u64(value).saturating_mul(MSAT_PER_SAT))
+    }
+
+    pub fn from_json_msat(value: &Value) -> Self {
+        Amount(json_u64(value))
+    }
+
+    pub fn as_msat(self) -> u64 {
+        self.0
+    }
+
+    /// Whole sats, dropping any msat remainder.
+    pub fn to_sat_floor(self) -> u64 {
+        self.0 / MSAT_PER_SAT
+    }
+
+    /// Whole sats, for on-chain amounts and other places that can't carry msats.
+    pub fn to_sat_exact(self) -> Result<u64> {
+        if !self.0.is_multiple_of(MSAT_PER_SAT) {
+            return Err(anyhow!("{} is not a whole number of sats", self.format(Unit::Msat)));
+        }
+        Ok(self.0 / MSAT_PER_SAT)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    /// Parses user input such as `1000`, `1,000.5 sat`, `2500msat` or `0.001 BTC`. A unit
+    /// suffix overrides `default_unit`. Precision finer than one msat is rejected, not rounded,
+    /// and so is a comma anywhere but between groups of three digits, e.g. a decimal comma.
+    pub fn parse(input: &str, default_unit: Unit) -> Result<Self> {
+        let cleaned: String = input.chars().filter(|c| !matches!(c, ' ' | '_')).collect();
+        let lower = cleaned.to_lowercase();
+        let (number, unit) = if let Some(number) = lower.strip_suffix("msats").or_else(|| lower.strip_suffix("msat")) {
+            (number, Unit::Msat)
+        } else if let Some(number) = lower.strip_suffix("sats").or_else(|| lower.strip_suffix("sat")) {
+            (number, Unit::Sat)
+        } else if let Some(number) = lower.strip_suffix("btc") {
+            (number, Unit::Btc)
+        } else {
+            (lower.as_str(), default_unit)
+        };
+        if number.is_empty() {
+            return Err(anyhow!("Enter an amount"));
+        }
+
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        let digits_only = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+        let whole = match whole.split_once(',') {
+            None => whole.to_string(),
+            Some((first, rest)) => {
+                let grouped = !first.is_empty() && first.len() <= 3
+                    && rest.split(',').all(|group| group.len() == 3);
+                if !grouped {
+                    return Err(anyhow!("'{}' is not a valid amount, use '.' for decimals", input.trim()));
+                }
+                whole.replace(',', "")
+            }
+        };
+        let whole = whole.as_str();
+        if !digits_only(whole) || !digits_only(fraction) || (whole.is_empty() && fraction.is_empty()) {
+            return Err(anyhow!("'{}' is not a valid amount", input.trim()));
+        }
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.len() > unit.max_decimals() {
+            return Err(anyhow!("'{}' is more precise than 1 msat", input.trim()));
+        }
+
+        let too_large = || anyhow!("'{}' is too large", input.trim());
+        let whole: u64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| too_large())? };
+        let fraction_msat = if fraction.is_empty() {
+            0
+        } else {
+            let scale = 10u64.pow((unit.max_decimals() - fraction.len()) as u32);
+            fraction.parse::<u64>().map_err(|_| too_large())? * scale
+        };
+        whole.checked_mul(unit.msat_per_unit())
+            .and_then(|msat| msat.checked_add(fraction_msat))
+            .map(Amount)
+            .ok_or_else(too_large)
+    }
+
+    /// Like `parse`, but rejects zero, for amounts that are about to be sent to the node.
+    pub fn parse_positive(input: &str, default_unit: Unit) -> Result<Self> {
+        let amount = Amount::parse(input, default_unit)?;
+        if amount.is_zero() {
+            return Err(anyhow!("The amount must be greater than zero"));
+        }
+        Ok(amount)
+    }
+
+    /// The amount in `unit` with its unit name, e.g. `1,234.5 sat` or `0.00001234 BTC`.
+    pub fn format(self, unit: Unit) -> String {
+        let per_unit = unit.msat_per_unit();
+        let whole = group_thousands(self.0 / per_unit);
+        let remainder = self.0 % per_unit;
+        let number = match unit {
+            Unit::Msat => whole,
+            // Always eight decimals, more only when there are msats
+            Unit::Btc => {
+                let fraction = format!("{:011}", remainder);
+                let fraction = fraction.trim_end_matches('0');
+                format!("{}.{:0<8}", whole, fraction)
+            }
+            Unit::Sat if remainder == 0 => whole,
+            Unit::Sat => format!("{}.{}", whole, format!("{:03}", remainder).trim_end_matches('0')),
+        };
+        format!("{} {}", number, unit.as_str())
+    }
+}
+
+fn group_thousands(value: u64) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(Unit::Sat))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msat(input: &str, unit: Unit) -> u64 {
+        Amount::parse(input, unit).unwrap().as_msat()
+    }
+
+    #[test]
+    fn suffix_overrides_default_unit() {
+        assert_eq!(msat("5msats", Unit::Btc), 5);
+        assert_eq!(msat("5 msat", Unit::Btc), 5);
+        assert_eq!(msat("5sats", Unit::Msat), 5_000);
+        assert_eq!(msat("5 SAT", Unit::Msat), 5_000);
+        assert_eq!(msat("0.001btc", Unit::Msat), 100_000_000);
+        assert_eq!(msat("1,000.5", Unit::Sat), 1_000_500);
+        assert_eq!(msat("2500", Unit::Msat), 2_500);
+        assert_eq!(msat(".5", Unit::Sat), 500);
+    }
+
+    #[test]
+    fn commas_only_separate_thousands() {
+        assert_eq!(msat("1,000", Unit::Sat), 1_000_000);
+        assert_eq!(msat("12,345,678 msat", Unit::Sat), 12_345_678);
+        assert_eq!(msat("1,000.001 btc", Unit::Sat), 100_000_100_000_000);
+        for input in ["0,5", "1,5 btc", "1,00", "1,0000", ",100", "100,", "1,,000", "1.000,5", "1000,000"] {
+            assert!(Amount::parse(input, Unit::Sat).is_err(), "{:?} parsed", input);
+        }
+    }
+
+    #[test]
+    fn rejects_precision_finer_than_one_msat() {
+        assert_eq!(msat("1.001", Unit::Sat), 1_001);
+        assert_eq!(msat("1.0010000", Unit::Sat), 1_001);
+        assert!(Amount::parse("1.0001", Unit::Sat).is_err());
+        assert!(Amount::parse("1.5msat", Unit::Sat).is_err());
+        assert_eq!(msat("0.00000000001 btc", Unit::Sat), 1);
+        assert!(Amount::parse("0.000000000001 btc", Unit::Sat).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_and_overflowing_input() {
+        for input in ["", ".", "sat", "1.2.3", "-1", "1e3", "abc"] {
+            assert!(Amount::parse(input, Unit::Sat).is_err(), "{:?} parsed", input);
+        }
+        assert!(Amount::parse("184467440737095516 btc", Unit::Sat).is_err());
+        assert!(Amount::parse("99999999999999999999", Unit::Msat).is_err());
+        assert_eq!(msat("18446744073709551615", Unit::Msat), u64::MAX);
+        assert!(Amount::parse_positive("0", Unit::Sat).is_err());
+    }
+
+    #[test]
+    fn formats_each_unit() {
+        assert_eq!(Amount::from_msat(1_234_500).format(Unit::Sat), "1,234.5 sat");
+        assert_eq!(Amount::from_msat(1_234_000).format(Unit::Sat), "1,234 sat");
+        assert_eq!(Amount::from_msat(1_234_500).format(Unit::Msat), "1,234,500 msat");
+        assert_eq!(Amount::from_msat(1_234_000).format(Unit::Btc), "0.00001234 BTC");
+        assert_eq!(Amount::from_msat(1_234_500).format(Unit::Btc), "0.000012345 BTC");
+        assert_eq!(Amount::from_msat(MSAT_PER_BTC * 21).format(Unit::Btc), "21.00000000 BTC");
+        assert_eq!(Amount::ZERO.format(Unit::Btc), "0.00000000 BTC");
+    }
+}
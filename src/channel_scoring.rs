@@ -0,0 +1,267 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+use crate::channels;
+use crate::utils::{json_u64, lncli};
+
+// Relative weight of each factor in the final score (sums to 1.0)
+const CENTRALITY_WEIGHT: f64 = 0.30;
+const CAPACITY_WEIGHT: f64 = 0.25;
+const FEE_WEIGHT: f64 = 0.20;
+const AGE_WEIGHT: f64 = 0.15;
+const REACHABILITY_WEIGHT: f64 = 0.10;
+
+// Fee rates above this are treated as equally bad
+const MAX_REASONABLE_FEE_PPM: f64 = 2500.0;
+
+#[derive(Debug, Clone)]
+pub struct ChannelCandidate {
+    pub pub_key: String,
+    pub alias: String,
+    pub score: f64,
+    pub address: Option<String>,
+    pub explanation: Vec<String>,
+}
+
+#[derive(Default)]
+struct NodeStats {
+    alias: String,
+    addresses: Vec<String>,
+    channel_count: usize,
+    total_capacity: u64,
+    fee_rates: Vec<u64>,
+    oldest_block: Option<u64>,
+}
+
+/// Ranks nodes from the channel graph as candidates for a new channel, best first.
+/// Ourselves and nodes we already have (pending) channels with are excluded.
+pub fn rank_candidates(network: &str, limit: usize) -> Result<Vec<ChannelCandidate>> {
+    let graph = describe_graph(network)?;
+    let info = getinfo(network)?;
+    let block_height = info["block_height"].as_u64().ok_or_else(|| anyhow!("No block_height in getinfo output"))?;
+    let own_pubkey = info["identity_pubkey"].as_str().unwrap_or_default().to_string();
+
+    let mut excluded: HashSet<String> = HashSet::new();
+    excluded.insert(own_pubkey);
+    for channel in channels::list_active_channels(network)? {
+        excluded.insert(channel.remote_pubkey);
+    }
+    for channel in channels::list_pending_channels(network)? {
+        excluded.insert(channel.remote_node_pub);
+    }
+
+    let stats = collect_node_stats(&graph);
+    let mut candidates = score_nodes(&stats, block_height, &excluded);
+    candidates.truncate(limit);
+    Ok(candidates)
+}
+
+fn describe_graph(network: &str) -> Result<Value> {
+    let output = lncli(network).args(["describegraph"]).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("Failed to describe graph: {}", stderr));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| anyhow!("Failed to parse graph JSON: {}", e))
+}
+
+fn getinfo(network: &str) -> Result<Value> {
+    let output = lncli(network).args(["getinfo"]).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("Failed to get node info: {}", stderr));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| anyhow!("Failed to parse getinfo JSON: {}", e))
+}
+
+fn collect_node_stats(graph: &Value) -> HashMap<String, NodeStats> {
+    let mut stats: HashMap<String, NodeStats> = HashMap::new();
+
+    for node in graph["nodes"].as_array().into_iter().flatten() {
+        let pub_key = node["pub_key"].as_str().unwrap_or_default().to_string();
+        let entry = stats.entry(pub_key).or_default();
+        entry.alias = node["alias"].as_str().unwrap_or_default().to_string();
+        entry.addresses = node["addresses"].as_array().into_iter().flatten()
+            .filter_map(|a| a["addr"].as_str().map(|s| s.to_string()))
+            .collect();
+    }
+
+    for edge in graph["edges"].as_array().into_iter().flatten() {
+        let capacity = json_u64(&edge["capacity"]);
+        // The short channel id encodes the funding block height in its upper 24 bits
+        let funding_block = json_u64(&edge["channel_id"]) >> 40;
+
+        for (node_key, policy_key) in [("node1_pub", "node1_policy"), ("node2_pub", "node2_policy")] {
+            let Some(pub_key) = edge[node_key].as_str() else { continue };
+            let entry = stats.entry(pub_key.to_string()).or_default();
+            let policy = &edge[policy_key];
+            if policy.is_null() || policy["disabled"].as_bool().unwrap_or(false) {
+                continue;
+            }
+            entry.channel_count += 1;
+            entry.total_capacity += capacity;
+            entry.fee_rates.push(json_u64(&policy["fee_rate_milli_msat"]));
+            if funding_block > 0 {
+                entry.oldest_block = Some(entry.oldest_block.map_or(funding_block, |b| b.min(funding_block)));
+            }
+        }
+    }
+
+    stats
+}
+
+fn score_nodes(stats: &HashMap<String, NodeStats>, block_height: u64, excluded: &HashSet<String>) -> Vec<ChannelCandidate> {
+    // Every factor is normalised against the best node in the graph, so the ranking
+    // behaves the same on mainnet, testnet or a small signet/regtest graph
+    let max_channels = stats.values().map(|s| s.channel_count).max().unwrap_or(0);
+    let max_capacity = stats.values().map(|s| s.total_capacity).max().unwrap_or(0);
+    let max_age = stats.values()
+        .filter_map(|s| s.oldest_block.map(|b| block_height.saturating_sub(b)))
+        .max()
+        .unwrap_or(0);
+
+    let mut candidates: Vec<ChannelCandidate> = stats.iter()
+        .filter(|(pub_key, s)| s.channel_count > 0 && !excluded.contains(*pub_key))
+        .map(|(pub_key, s)| {
+            let centrality = log_ratio(s.channel_count as u64, max_channels as u64);
+            let capacity = log_ratio(s.total_capacity, max_capacity);
+            let median_fee = median(&s.fee_rates);
+            let fees = 1.0 - (median_fee as f64).min(MAX_REASONABLE_FEE_PPM) / MAX_REASONABLE_FEE_PPM;
+            let age_blocks = s.oldest_block.map(|b| block_height.saturating_sub(b)).unwrap_or(0);
+            let age = if max_age > 0 { age_blocks as f64 / max_age as f64 } else { 0.0 };
+            let (reachability, address, reachability_note) = reachability(&s.addresses);
+
+            let score = 100.0 * (CENTRALITY_WEIGHT * centrality
+                + CAPACITY_WEIGHT * capacity
+                + FEE_WEIGHT * fees
+                + AGE_WEIGHT * age
+                + REACHABILITY_WEIGHT * reachability);
+
+            let explanation = vec![
+                format!("Centrality {:.2}: {} active channels", centrality, s.channel_count),
+                format!("Capacity {:.2}: {} sats total", capacity, s.total_capacity),
+                format!("Fees {:.2}: median {} ppm", fees, median_fee),
+                format!("Age {:.2}: oldest channel ~{} days", age, age_blocks / 144),
+                format!("Reachability {:.2}: {}", reachability, reachability_note),
+            ];
+
+            ChannelCandidate {
+                pub_key: pub_key.clone(),
+                alias: s.alias.clone(),
+                score,
+                address,
+                explanation,
+            }
+        })
+        .collect();
+
+    // Equal scores fall back to the pubkey so the order doesn't change between runs
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.pub_key.cmp(&b.pub_key)));
+    candidates
+}
+
+fn reachability(addresses: &[String]) -> (f64, Option<String>, String) {
+    if let Some(clearnet) = addresses.iter().find(|a| !a.contains(".onion")) {
+        (1.0, Some(clearnet.clone()), format!("clearnet {}", clearnet))
+    } else if let Some(onion) = addresses.first() {
+        (0.5, Some(onion.clone()), format!("Tor only {}", onion))
+    } else {
+        (0.0, None, "no public address".to_string())
+    }
+}
+
+fn log_ratio(value: u64, max: u64) -> f64 {
+    if max == 0 {
+        return 0.0;
+    }
+    (1.0 + value as f64).ln() / (1.0 + max as f64).ln()
+}
+
+fn median(values: &[u64]) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    sorted[sorted.len() / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_HEIGHT: u64 = 800_000;
+
+    fn node(channel_count: usize, total_capacity: u64, fee_rate: u64, oldest_block: Option<u64>, address: &str) -> NodeStats {
+        NodeStats {
+            alias: String::new(),
+            addresses: if address.is_empty() { Vec::new() } else { vec![address.to_string()] },
+            channel_count,
+            total_capacity,
+            fee_rates: vec![fee_rate; channel_count],
+            oldest_block,
+        }
+    }
+
+    fn ranked(stats: Vec<(&str, NodeStats)>, excluded: &[&str]) -> Vec<ChannelCandidate> {
+        let stats = stats.into_iter().map(|(key, s)| (key.to_string(), s)).collect();
+        let excluded = excluded.iter().map(|key| key.to_string()).collect();
+        score_nodes(&stats, BLOCK_HEIGHT, &excluded)
+    }
+
+    fn keys(candidates: &[ChannelCandidate]) -> Vec<&str> {
+        candidates.iter().map(|c| c.pub_key.as_str()).collect()
+    }
+
+    #[test]
+    fn ranks_better_connected_cheaper_older_nodes_first() {
+        let candidates = ranked(vec![
+            ("small", node(2, 1_000_000, 1_000, Some(BLOCK_HEIGHT - 1_000), "10.0.0.3:9735")),
+            ("hub", node(200, 500_000_000, 10, Some(BLOCK_HEIGHT - 100_000), "10.0.0.1:9735")),
+            ("mid", node(40, 50_000_000, 100, Some(BLOCK_HEIGHT - 20_000), "10.0.0.2:9735")),
+        ], &[]);
+        assert_eq!(keys(&candidates), ["hub", "mid", "small"]);
+        assert!(candidates[0].score > candidates[1].score && candidates[1].score > candidates[2].score);
+        // The best node in every factor scores the maximum
+        assert!((candidates[0].score - 100.0 * (1.0 - FEE_WEIGHT * 10.0 / MAX_REASONABLE_FEE_PPM)).abs() < 1e-9);
+        assert_eq!(candidates[0].address.as_deref(), Some("10.0.0.1:9735"));
+    }
+
+    #[test]
+    fn equal_scores_are_ordered_by_pubkey() {
+        let same = || node(10, 10_000_000, 50, Some(BLOCK_HEIGHT - 5_000), "10.0.0.1:9735");
+        let candidates = ranked(vec![("03cc", same()), ("02aa", same()), ("02bb", same())], &[]);
+        assert_eq!(keys(&candidates), ["02aa", "02bb", "03cc"]);
+        assert_eq!(candidates[0].score, candidates[2].score);
+    }
+
+    #[test]
+    fn missing_metrics_score_zero_for_that_factor() {
+        let candidates = ranked(vec![
+            ("reachable", node(5, 5_000_000, 100, Some(BLOCK_HEIGHT - 5_000), "10.0.0.1:9735")),
+            ("tor", node(5, 5_000_000, 100, Some(BLOCK_HEIGHT - 5_000), "abcdefghijklmnop.onion:9735")),
+            ("no-address", node(5, 5_000_000, 100, Some(BLOCK_HEIGHT - 5_000), "")),
+            ("no-age", node(5, 5_000_000, 100, None, "10.0.0.4:9735")),
+            ("graph-only", node(0, 0, 0, None, "10.0.0.5:9735")),
+        ], &[]);
+        // Nodes without an active channel aren't candidates at all
+        assert_eq!(keys(&candidates), ["reachable", "tor", "no-address", "no-age"]);
+        let score = |key: &str| candidates.iter().find(|c| c.pub_key == key).unwrap().score;
+        assert!((score("reachable") - score("tor") - 100.0 * REACHABILITY_WEIGHT * 0.5).abs() < 1e-9);
+        assert!((score("reachable") - score("no-address") - 100.0 * REACHABILITY_WEIGHT).abs() < 1e-9);
+        assert!((score("reachable") - score("no-age") - 100.0 * AGE_WEIGHT).abs() < 1e-9);
+        assert_eq!(candidates.iter().find(|c| c.pub_key == "no-address").unwrap().address, None);
+    }
+
+    #[test]
+    fn excluded_nodes_and_an_empty_graph() {
+        let candidates = ranked(vec![
+            ("ours", node(50, 50_000_000, 10, Some(BLOCK_HEIGHT - 1), "10.0.0.1:9735")),
+            ("peer", node(5, 1_000_000, 10, Some(BLOCK_HEIGHT - 1), "10.0.0.2:9735")),
+        ], &["ours"]);
+        assert_eq!(keys(&candidates), ["peer"]);
+        assert!(candidates[0].score.is_finite());
+        assert!(ranked(Vec::new(), &[]).is_empty());
+    }
+}
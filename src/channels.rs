@@ -1,36 +1,34 @@
 use anyhow::{anyhow, Result};
-use std::process::Command;
+use base64::prelude::*;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
 use serde_json::Value;
 
+use crate::amount::Amount;
+use crate::channel_scoring::ChannelCandidate;
+use crate::utils::lncli;
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct ActiveChannelInfo {
     #[serde(rename = "chan_id")]
     pub channel_id: String,
     pub remote_pubkey: String,
-    pub capacity: String,
-    pub local_balance: String,
-    pub remote_balance: String,
+    pub capacity: Amount,
+    pub local_balance: Amount,
+    pub remote_balance: Amount,
     pub active: bool,
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
-pub struct PendingChannelData {
-    pub remote_node_pub: String,
-    #[serde(default)] // channel_point might not be in all pending types uniformly at top level
-    pub channel_point: String, 
-    pub capacity: String,
-    pub local_balance: String,
-    #[serde(default)]
-    pub remote_balance: String,
-}
-
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct PendingChannelInfo {
     pub remote_node_pub: String,
     pub channel_point: String, 
-    pub capacity: String,
-    pub local_balance: String,
-    pub remote_balance: String,
+    pub capacity: Amount,
+    pub local_balance: Amount,
+    pub remote_balance: Amount,
     pub status: String, 
 }
 
@@ -39,15 +37,9 @@ pub fn connect_to_peer(network: &str, pubkey: &str, host: &str, port: u16) -> Re
     
     println!("Attempting to connect to peer: {}", addr);
     
-    let output = if network == "mainnet" {
-        Command::new("/usr/local/bin/lncli")
-            .args(["connect", &addr])
-            .output()?
-    } else {
-        Command::new("/usr/local/bin/lncli")
-            .args(["--network", "testnet", "connect", &addr])
-            .output()?
-    };
+    let output = lncli(network)
+        .args(["connect", &addr])
+        .output()?;
     
     if output.status.success() {
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -61,15 +53,9 @@ pub fn connect_to_peer(network: &str, pubkey: &str, host: &str, port: u16) -> Re
 }
 
 pub fn list_active_channels(network: &str) -> Result<Vec<ActiveChannelInfo>> {
-    let output = if network == "mainnet" {
-        Command::new("/usr/local/bin/lncli")
-            .args(["listchannels"])
-            .output()?
-    } else {
-        Command::new("/usr/local/bin/lncli")
-            .args(["--network", "testnet", "listchannels"])
-            .output()?
-    };
+    let output = lncli(network)
+        .args(["listchannels"])
+        .output()?;
 
     if output.status.success() {
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -81,18 +67,17 @@ pub fn list_active_channels(network: &str) -> Result<Vec<ActiveChannelInfo>> {
         
         let mut channels_info = Vec::new();
         for chan_val in channels_val {
-            // Ensure all fields are strings as expected by ActiveChannelInfo
-            // or handle potential type mismatches if lncli output varies
-            let capacity_str = chan_val["capacity"].as_str().unwrap_or("0").to_string();
-            let local_balance_str = chan_val["local_balance"].as_str().unwrap_or("0").to_string();
-            let remote_balance_str = chan_val["remote_balance"].as_str().unwrap_or("0").to_string();
+            // lncli reports these in sats, as strings or numbers depending on the version
+            let capacity = Amount::from_json_sat(&chan_val["capacity"]);
+            let local_balance = Amount::from_json_sat(&chan_val["local_balance"]);
+            let remote_balance = Amount::from_json_sat(&chan_val["remote_balance"]);
 
             let channel: ActiveChannelInfo = ActiveChannelInfo {
                 channel_id: chan_val["chan_id"].as_str().unwrap_or_default().to_string(),
                 remote_pubkey: chan_val["remote_pubkey"].as_str().unwrap_or_default().to_string(),
-                capacity: capacity_str,
-                local_balance: local_balance_str,
-                remote_balance: remote_balance_str,
+                capacity,
+                local_balance,
+                remote_balance,
                 active: chan_val["active"].as_bool().unwrap_or(false),
             };
             channels_info.push(channel);
@@ -106,15 +91,9 @@ pub fn list_active_channels(network: &str) -> Result<Vec<ActiveChannelInfo>> {
 }
 
 pub fn list_pending_channels(network: &str) -> Result<Vec<PendingChannelInfo>> {
-    let output = if network == "mainnet" {
-        Command::new("/usr/local/bin/lncli")
-            .args(["pendingchannels"])
-            .output()?
-    } else {
-        Command::new("/usr/local/bin/lncli")
-            .args(["--network", "testnet", "pendingchannels"])
-            .output()?
-    };
+    let output = lncli(network)
+        .args(["pendingchannels"])
+        .output()?;
 
     if output.status.success() {
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -131,16 +110,16 @@ pub fn list_pending_channels(network: &str) -> Result<Vec<PendingChannelInfo>> {
                     // Extract fields, providing defaults if they might be missing or have varying types
                     let remote_pubkey = channel_data_val["remote_node_pub"].as_str().unwrap_or_default().to_string();
                     let channel_point = channel_data_val["channel_point"].as_str().unwrap_or_default().to_string();
-                    let capacity = channel_data_val["capacity"].as_str().unwrap_or("0").to_string();
-                    let local_balance = channel_data_val["local_balance"].as_str().unwrap_or("0").to_string();
-                    let remote_balance = channel_data_val["remote_balance"].as_str().unwrap_or("0").to_string(); // Often 0 or not present for pending
+                    let capacity = Amount::from_json_sat(&channel_data_val["capacity"]);
+                    let local_balance = Amount::from_json_sat(&channel_data_val["local_balance"]);
+                    let remote_balance = Amount::from_json_sat(&channel_data_val["remote_balance"]); // Often 0 or not present for pending
 
                     pending_infos_vec.push(PendingChannelInfo {
                         remote_node_pub: remote_pubkey,
-                        channel_point: channel_point,
-                        capacity: capacity,
-                        local_balance: local_balance,
-                        remote_balance: remote_balance,
+                        channel_point,
+                        capacity,
+                        local_balance,
+                        remote_balance,
                         status: status_str.to_string(),
                     });
                 }
@@ -164,15 +143,9 @@ pub fn list_pending_channels(network: &str) -> Result<Vec<PendingChannelInfo>> {
 pub fn list_peers(network: &str) -> Result<Vec<String>> {
     println!("Listing connected peers...");
     
-    let output = if network == "mainnet" {
-        Command::new("/usr/local/bin/lncli")
-            .args(["listpeers"])
-            .output()?
-    } else {
-        Command::new("/usr/local/bin/lncli")
-            .args(["--network", "testnet", "listpeers"])
-            .output()?
-    };
+    let output = lncli(network)
+        .args(["listpeers"])
+        .output()?;
     
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -204,47 +177,251 @@ pub fn list_peers(network: &str) -> Result<Vec<String>> {
     Ok(pub_keys)
 }
 
-pub fn auto_open_channel(network: &str, amount: u32) -> Result<String> {
-    // Get list of peers
-    let peers = list_peers(network)?;
-    
-    // Choose the first peer
-    let peer_pubkey = &peers[0];
-    println!("Selected peer with pubkey: {}", peer_pubkey);
-    
-    // Open channel with selected peer
-    open_channel(network, peer_pubkey, amount)
+/// How many of the ranked candidates auto open tries before giving up.
+const AUTO_OPEN_ATTEMPTS: usize = 3;
+
+/// Opens a channel with the best-scored candidate we can actually reach,
+/// connecting to it first if it is not already a peer.
+/// Returns the chosen candidate's pubkey and the openchannel output.
+pub fn auto_open_channel(network: &str, candidates: &[ChannelCandidate], amount: Amount) -> Result<(String, String)> {
+    let mut last_error = anyhow!("No channel candidates to choose from");
+
+    for candidate in candidates.iter().take(AUTO_OPEN_ATTEMPTS) {
+        println!("Trying candidate {} ({}) with score {:.1}", candidate.alias, candidate.pub_key, candidate.score);
+        match open_channel_with_candidate(network, candidate, amount) {
+            Ok(output) => return Ok((candidate.pub_key.clone(), output)),
+            Err(e) => {
+                println!("Candidate {} failed: {}", candidate.pub_key, e);
+                last_error = e;
+            }
+        }
+    }
+
+    if candidates.len() > AUTO_OPEN_ATTEMPTS {
+        return Err(anyhow!(
+            "Gave up after the top {} of {} candidates, open one from the list instead. Last error: {}",
+            AUTO_OPEN_ATTEMPTS, candidates.len(), last_error
+        ));
+    }
+    Err(last_error)
 }
 
-pub fn open_channel(network: &str, pub_key: &str, amount: u32) -> Result<String> {
-    println!("Opening channel with {} for {} sats", pub_key, amount);
-    
-    let output = if network == "mainnet" {
-        Command::new("/usr/local/bin/lncli")
-            .args([
-                "openchannel",
-                pub_key,
-                &amount.to_string()
-            ])
-            .output()?
-    } else {
-        Command::new("/usr/local/bin/lncli")
-            .args([
-                "--network", "testnet",
-                "openchannel",
-                pub_key,
-                &amount.to_string()
-            ])
-            .output()?
-    };
-    
+pub fn open_channel_with_candidate(network: &str, candidate: &ChannelCandidate, amount: Amount) -> Result<String> {
+    connect_candidate(network, candidate)?;
+    open_channel(network, &candidate.pub_key, amount)
+}
+
+/// Connects to the candidate unless it is already a peer.
+pub fn connect_candidate(network: &str, candidate: &ChannelCandidate) -> Result<()> {
+    let peers = list_peers(network).unwrap_or_default();
+    if !peers.contains(&candidate.pub_key) {
+        let address = candidate.address.as_ref()
+            .ok_or_else(|| anyhow!("{} has no public address to connect to", candidate.pub_key))?;
+        let (host, port) = address.rsplit_once(':')
+            .ok_or_else(|| anyhow!("Invalid node address: {}", address))?;
+        let port = port.parse::<u16>()
+            .map_err(|_| anyhow!("Invalid port in node address: {}", address))?;
+        connect_to_peer(network, &candidate.pub_key, host, port)?;
+    }
+    Ok(())
+}
+
+pub fn open_channel(network: &str, pub_key: &str, amount: Amount) -> Result<String> {
+    let local_amt = amount.to_sat_exact()?;
+    eprintln!("Opening channel with {} for {}", pub_key, amount);
+
+    let output = lncli(network)
+        .args(["openchannel", pub_key, &local_amt.to_string()])
+        .output()?;
+
     if output.status.success() {
         let stdout = String::from_utf8_lossy(&output.stdout);
-        println!("Channel open success: {}", stdout);
+        eprintln!("Channel open success: {}", stdout);
         Ok(stdout.to_string())
     } else {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        println!("Channel open failed: {}", stderr);
+        eprintln!("Channel open failed: {}", stderr);
         Err(anyhow!("Failed to open channel: {}", stderr))
     }
 }
+
+const PSBT_FUNDED_PROMPT: &str = "Base64 encoded PSBT (or path to file):";
+const PSBT_SIGNED_PROMPT: &str = "Signed base64 encoded PSBT or hex encoded raw wire TX (or path to file):";
+const PSBT_MAGIC: &[u8] = b"psbt\xff";
+
+/// A channel open waiting for an external wallet to fund it, backed by an interactive
+/// `lncli openchannel --psbt` session. Dropping it aborts the open.
+pub struct PsbtChannelOpen {
+    pub pub_key: String,
+    pub amount: Amount,
+    pub funding_address: String,
+    pub psbt_path: PathBuf,
+    child: Child,
+    stdout: ChildStdout,
+    stderr: StderrDrain,
+}
+
+impl Drop for PsbtChannelOpen {
+    fn drop(&mut self) {
+        // lnd cancels the pending funding shim once the lncli stream goes away
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Starts a channel open funded by an external wallet. The unsigned funding PSBT is written
+/// to `export_dir` so it can be signed elsewhere, then `finalize_psbt_open` completes it.
+pub fn start_psbt_open(network: &str, pub_key: &str, amount: Amount, export_dir: &Path) -> Result<PsbtChannelOpen> {
+    let local_amt = amount.to_sat_exact()?;
+    println!("Starting PSBT channel open with {} for {}", pub_key, amount);
+
+    let mut child = lncli(network)
+        .args(["openchannel", "--psbt", "--node_key", pub_key, "--local_amt", &local_amt.to_string()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdout = child.stdout.take().ok_or_else(|| anyhow!("Failed to capture lncli output"))?;
+    let mut stderr = StderrDrain::start(&mut child);
+
+    let output = match read_until_prompt(&mut child, &mut stdout, &mut stderr, PSBT_FUNDED_PROMPT) {
+        Ok(output) => output,
+        Err(e) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+    };
+
+    let funding_address = output.split("to the funding address ")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or_default()
+        .to_string();
+    let psbt_base64 = output.lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("cHNidP8"))
+        .ok_or_else(|| anyhow!("lncli did not provide a funding PSBT:\n{}", output))?;
+    let psbt = BASE64_STANDARD.decode(psbt_base64)
+        .map_err(|e| anyhow!("Funding PSBT is not valid base64: {}", e))?;
+
+    let file_name = format!("channel_funding_{}_{}.psbt", &pub_key[..pub_key.len().min(16)], chrono::Utc::now().format("%Y%m%d_%H%M%S"));
+    let psbt_path = export_dir.join(file_name);
+    std::fs::write(&psbt_path, psbt).map_err(|e| anyhow!("Failed to write {}: {}", psbt_path.display(), e))?;
+    println!("Funding PSBT for {} written to {}", funding_address, psbt_path.display());
+
+    Ok(PsbtChannelOpen {
+        pub_key: pub_key.to_string(),
+        amount,
+        funding_address,
+        psbt_path,
+        child,
+        stdout,
+        stderr,
+    })
+}
+
+/// Reads a signed PSBT file as base64. Wallets save PSBTs either as raw bytes or as
+/// base64 text, lncli only takes the latter.
+pub fn read_signed_psbt(path: &Path) -> Result<String> {
+    let signed = std::fs::read(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    if signed.starts_with(PSBT_MAGIC) {
+        return Ok(BASE64_STANDARD.encode(&signed));
+    }
+    let text = String::from_utf8_lossy(&signed).trim().to_string();
+    match BASE64_STANDARD.decode(&text) {
+        Ok(decoded) if decoded.starts_with(PSBT_MAGIC) => Ok(text),
+        _ => Err(anyhow!("{} is not a PSBT", path.display())),
+    }
+}
+
+/// Hands the externally signed PSBT to lnd, which verifies it against the pending open and
+/// then publishes the funding transaction. Returns the openchannel JSON output.
+pub fn finalize_psbt_open(mut open: PsbtChannelOpen, signed_base64: &str) -> Result<String> {
+    // Long PSBTs get truncated when pasted, so lncli is given a file path instead
+    let upload_path = std::env::temp_dir().join(format!("signed_{}", open.psbt_path.file_name().and_then(|n| n.to_str()).unwrap_or("funding.psbt")));
+    std::fs::write(&upload_path, signed_base64)?;
+    let upload = format!("{}\n", upload_path.display());
+
+    let result = (|| {
+        let stdin = open.child.stdin.as_mut().ok_or_else(|| anyhow!("lncli input is closed"))?;
+        // The funded and signed prompts both accept the signed PSBT, lnd verifies it at the first
+        stdin.write_all(upload.as_bytes())?;
+        stdin.flush()?;
+        read_until_prompt(&mut open.child, &mut open.stdout, &mut open.stderr, PSBT_SIGNED_PROMPT)?;
+
+        let stdin = open.child.stdin.as_mut().ok_or_else(|| anyhow!("lncli input is closed"))?;
+        stdin.write_all(upload.as_bytes())?;
+        stdin.flush()?;
+        drop(open.child.stdin.take());
+
+        let mut rest = String::new();
+        open.stdout.read_to_string(&mut rest)?;
+        let status = open.child.wait()?;
+        if !status.success() {
+            return Err(anyhow!("Channel funding failed: {}{}", rest, open.stderr.finish()));
+        }
+        let json_start = rest.find('{').ok_or_else(|| anyhow!("Unexpected lncli output: {}", rest))?;
+        Ok(rest[json_start..].trim().to_string())
+    })();
+
+    let _ = std::fs::remove_file(&upload_path);
+    match &result {
+        Ok(output) => println!("PSBT channel open with {} finalized: {}", open.pub_key, output),
+        Err(e) => println!("PSBT channel open with {} failed: {}", open.pub_key, e),
+    }
+    result
+}
+
+/// Reads lncli output until `prompt` is printed, prompts don't end in a newline.
+/// Verification errors make lncli quit, in that case its stderr is returned as the error.
+fn read_until_prompt(child: &mut Child, stdout: &mut ChildStdout, stderr: &mut StderrDrain, prompt: &str) -> Result<String> {
+    let mut output = String::new();
+    let mut buffer = [0u8; 4096];
+    loop {
+        let read = stdout.read(&mut buffer)?;
+        if read == 0 {
+            let _ = child.wait();
+            return Err(anyhow!("lncli stopped before asking for the PSBT: {}", stderr.finish().trim()));
+        }
+        output.push_str(&String::from_utf8_lossy(&buffer[..read]));
+        if output.contains(prompt) {
+            return Ok(output);
+        }
+    }
+}
+
+/// Collects lncli's stderr on its own thread. Left unread, a full pipe would block lncli
+/// while we wait on its stdout for the next prompt.
+struct StderrDrain {
+    text: Arc<Mutex<String>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StderrDrain {
+    fn start(child: &mut Child) -> Self {
+        let text = Arc::new(Mutex::new(String::new()));
+        let thread = child.stderr.take().map(|mut pipe| {
+            let text = text.clone();
+            thread::spawn(move || {
+                let mut buffer = [0u8; 1024];
+                while let Ok(read) = pipe.read(&mut buffer) {
+                    if read == 0 {
+                        break;
+                    }
+                    text.lock().unwrap().push_str(&String::from_utf8_lossy(&buffer[..read]));
+                }
+            })
+        });
+        StderrDrain { text, thread }
+    }
+
+    /// Everything lncli wrote to stderr, once it has exited and closed the pipe.
+    fn finish(&mut self) -> String {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.text.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::litd_config;
+use crate::types::{LndConnection, NodeMode};
+
+const DEFAULT_GRPC_PORT: u16 = 10009;
+const DEFAULT_REST_PORT: u16 = 8080;
+
+/// One line of the discovery report shown in the UI.
+pub struct DiscoveredItem {
+    pub label: String,
+    pub value: String,
+    pub found: bool,
+}
+
+/// Application data directory the way lnd and litd derive it: `~/.lnd` on Linux,
+/// `~/Library/Application Support/Lnd` on macOS and `%LOCALAPPDATA%\Lnd` on Windows.
+pub fn app_data_dir(app: &str) -> PathBuf {
+    let home = dirs::home_dir().unwrap_or_default();
+    if cfg!(target_os = "macos") {
+        home.join("Library").join("Application Support").join(capitalize(app))
+    } else if cfg!(target_os = "windows") {
+        dirs::data_local_dir().unwrap_or_else(|| home.join("AppData").join("Local")).join(capitalize(app))
+    } else {
+        home.join(format!(".{}", app))
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Reads `key=value` pairs from an lnd/litd config file, section headers are ignored
+/// since litd prefixes its lnd options (`lnd.restlisten`) instead of using sections.
+fn read_conf(path: &Path) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    let Ok(content) = std::fs::read_to_string(path) else { return values };
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') || line.starts_with('[') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    values
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~") {
+        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest.trim_start_matches(['/', '\\'])),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Splits a listen address like `0.0.0.0:8080` or `:10009` into a host we can dial and a port.
+fn parse_listen(value: Option<&String>, default_port: u16) -> (String, u16) {
+    let Some(value) = value else { return ("127.0.0.1".to_string(), default_port) };
+    let (host, port) = value.rsplit_once(':').unwrap_or((value.as_str(), ""));
+    let host = match host {
+        "" | "0.0.0.0" | "[::]" => "127.0.0.1",
+        host => host,
+    };
+    (host.to_string(), port.parse().unwrap_or(default_port))
+}
+
+fn macaroon_dir(lnd_dir: &Path, network: &str) -> PathBuf {
+    lnd_dir.join("data").join("chain").join("bitcoin").join(network)
+}
+
+/// Works out how to reach the node for `network`: plain lnd, litd running lnd in-process,
+/// or litd attached to a remote lnd. Config files override the platform defaults.
+pub fn discover(network: &str) -> LndConnection {
+    // The per-network file the app writes wins over litd's default location
+    let lit_conf_path = Some(litd_config::config_path(network))
+        .filter(|p| p.exists())
+        .unwrap_or_else(|| app_data_dir("lit").join("lit.conf"));
+    let lit_conf = read_conf(&lit_conf_path);
+    let lit_dir = lit_conf.get("lit-dir").map(|d| expand_home(d)).unwrap_or_else(|| app_data_dir("lit"));
+    let default_lnd_dir = app_data_dir("lnd");
+
+    let mode = if !lit_conf_path.exists() && !lit_dir.exists() {
+        NodeMode::Lnd
+    } else if lit_conf.get("lnd-mode").map(String::as_str) == Some("remote") {
+        NodeMode::LitdRemote
+    } else {
+        NodeMode::LitdIntegrated
+    };
+
+    match mode {
+        NodeMode::Lnd => {
+            let lnd_conf = read_conf(&default_lnd_dir.join("lnd.conf"));
+            let lnd_dir = lnd_conf.get("lnddir").map(|d| expand_home(d)).unwrap_or(default_lnd_dir);
+            let (host, port) = parse_listen(lnd_conf.get("rpclisten"), DEFAULT_GRPC_PORT);
+            let (_, rest_port) = parse_listen(lnd_conf.get("restlisten"), DEFAULT_REST_PORT);
+            LndConnection {
+                mode,
+                host,
+                port,
+                rest_port,
+                cert_path: lnd_conf.get("tlscertpath").map(|p| expand_home(p)).unwrap_or_else(|| lnd_dir.join("tls.cert")),
+                macaroon_path: lnd_conf.get("adminmacaroonpath").map(|p| expand_home(p))
+                    .unwrap_or_else(|| macaroon_dir(&lnd_dir, network).join("admin.macaroon")),
+                lit_macaroon_path: None,
+                config_path: Some(lnd_dir.join("lnd.conf")).filter(|p| p.exists()),
+                lnd_dir: Some(lnd_dir),
+            }
+        }
+        NodeMode::LitdIntegrated => {
+            let lnd_dir = lit_conf.get("lnd.lnddir").map(|d| expand_home(d)).unwrap_or(default_lnd_dir);
+            let (host, port) = parse_listen(lit_conf.get("lnd.rpclisten"), DEFAULT_GRPC_PORT);
+            let (_, rest_port) = parse_listen(lit_conf.get("lnd.restlisten"), DEFAULT_REST_PORT);
+            LndConnection {
+                mode,
+                host,
+                port,
+                rest_port,
+                cert_path: lit_conf.get("lnd.tlscertpath").map(|p| expand_home(p)).unwrap_or_else(|| lnd_dir.join("tls.cert")),
+                macaroon_path: lit_conf.get("lnd.adminmacaroonpath").map(|p| expand_home(p))
+                    .unwrap_or_else(|| macaroon_dir(&lnd_dir, network).join("admin.macaroon")),
+                lit_macaroon_path: Some(lit_conf.get("macaroonpath").map(|p| expand_home(p))
+                    .unwrap_or_else(|| lit_dir.join(network).join("lit.macaroon"))),
+                config_path: Some(lit_conf_path).filter(|p| p.exists()),
+                lnd_dir: Some(lnd_dir),
+            }
+        }
+        NodeMode::LitdRemote => {
+            let (host, port) = parse_listen(lit_conf.get("remote.lnd.rpcserver"), DEFAULT_GRPC_PORT);
+            let macaroon_path = match (lit_conf.get("remote.lnd.macaroonpath"), lit_conf.get("remote.lnd.macaroondir")) {
+                (Some(path), _) => expand_home(path),
+                (None, Some(dir)) => expand_home(dir).join("admin.macaroon"),
+                (None, None) => macaroon_dir(&default_lnd_dir, network).join("admin.macaroon"),
+            };
+            LndConnection {
+                mode,
+                host,
+                port,
+                // Remote lnd only tells litd its gRPC address, REST is assumed on the default port
+                rest_port: DEFAULT_REST_PORT,
+                cert_path: lit_conf.get("remote.lnd.tlscertpath").map(|p| expand_home(p))
+                    .unwrap_or_else(|| default_lnd_dir.join("tls.cert")),
+                macaroon_path,
+                lit_macaroon_path: Some(lit_conf.get("macaroonpath").map(|p| expand_home(p))
+                    .unwrap_or_else(|| lit_dir.join(network).join("lit.macaroon"))),
+                config_path: Some(lit_conf_path).filter(|p| p.exists()),
+                lnd_dir: None,
+            }
+        }
+    }
+}
+
+/// What discovery resolved, with a flag for whether each file is actually there.
+pub fn report(connection: &LndConnection) -> Vec<DiscoveredItem> {
+    let file = |label: &str, path: &Path| DiscoveredItem {
+        label: label.to_string(),
+        value: path.display().to_string(),
+        found: path.exists(),
+    };
+
+    let mut items = vec![
+        DiscoveredItem {
+            label: "Mode".to_string(),
+            value: connection.mode.to_string(),
+            found: true,
+        },
+        DiscoveredItem {
+            label: "gRPC".to_string(),
+            value: format!("{}:{}", connection.host, connection.port),
+            found: true,
+        },
+        DiscoveredItem {
+            label: "REST".to_string(),
+            value: connection.rest_url(),
+            found: true,
+        },
+        file("TLS certificate", &connection.cert_path),
+        file("Admin macaroon", &connection.macaroon_path),
+    ];
+    if let Some(path) = &connection.lit_macaroon_path {
+        items.push(file("Lit macaroon", path));
+    }
+    match &connection.config_path {
+        Some(path) => items.push(file("Config file", path)),
+        None => items.push(DiscoveredItem {
+            label: "Config file".to_string(),
+            value: "none, using defaults".to_string(),
+            found: false,
+        }),
+    }
+    items
+}
@@ -0,0 +1,363 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::utils::{json_u64, lncli};
+use crate::store::{self, Annotation};
+use crate::{forwarding, wallet};
+
+const PAGE_SIZE: u64 = 1000;
+
+/// Column order of the CSV export. Accounting imports depend on it, only ever append new columns.
+const COLUMNS: [&str; 17] = [
+    "record_type", "id", "created_at", "settled_at", "state", "direction",
+    "amount_sat", "amount_msat", "fee_sat", "fee_msat", "payment_hash", "memo", "reference",
+    "counterparty", "order_ref", "tags", "notes",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordType {
+    Invoice,
+    Payment,
+    Forward,
+    OnChain,
+}
+
+impl RecordType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RecordType::Invoice => "invoice",
+            RecordType::Payment => "payment",
+            RecordType::Forward => "forward",
+            RecordType::OnChain => "onchain",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    /// Index of the format combo box in the export view.
+    pub fn from_index(index: i32) -> Self {
+        if index == 1 { ExportFormat::Json } else { ExportFormat::Csv }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// One exported record. Every record type uses the same columns, empty where they don't apply.
+/// Times are RFC 3339 in UTC, amounts are always positive with `direction` giving the sign.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportRow {
+    pub record_type: &'static str,
+    pub id: String,
+    pub created_at: String,
+    pub settled_at: String,
+    pub state: String,
+    pub direction: &'static str,
+    pub amount_sat: u64,
+    pub amount_msat: u64,
+    pub fee_sat: u64,
+    pub fee_msat: u64,
+    pub payment_hash: String,
+    pub memo: String,
+    pub reference: String,
+    pub counterparty: String,
+    pub order_ref: String,
+    pub tags: String,
+    pub notes: String,
+    #[serde(skip)]
+    created_secs: i64,
+}
+
+impl ExportRow {
+    fn csv_fields(&self) -> [String; 17] {
+        [
+            self.record_type.to_string(),
+            self.id.clone(),
+            self.created_at.clone(),
+            self.settled_at.clone(),
+            self.state.clone(),
+            self.direction.to_string(),
+            self.amount_sat.to_string(),
+            self.amount_msat.to_string(),
+            self.fee_sat.to_string(),
+            self.fee_msat.to_string(),
+            self.payment_hash.clone(),
+            self.memo.clone(),
+            self.reference.clone(),
+            self.counterparty.clone(),
+            self.order_ref.clone(),
+            self.tags.clone(),
+            self.notes.clone(),
+        ]
+    }
+
+    /// Fills in our own notes on the invoice or payment.
+    fn annotate(mut self, annotations: &store::TypedTree<Annotation>) -> Self {
+        if let Ok(Some(annotation)) = annotations.get(&self.payment_hash) {
+            self.counterparty = annotation.counterparty;
+            self.order_ref = annotation.order_ref;
+            self.tags = annotation.tags.join(", ");
+            self.notes = annotation.notes;
+        }
+        self
+    }
+}
+
+fn rfc3339(secs: i64) -> String {
+    if secs <= 0 {
+        return String::new();
+    }
+    DateTime::from_timestamp(secs, 0).map(|dt| dt.to_rfc3339()).unwrap_or_default()
+}
+
+/// Pages through an lncli list command from the oldest entry, collecting `field` of every page.
+fn list_all(network: &str, command: &str, max_flag: &str, forwards_flag: &str, extra: &[&str], field: &str) -> Result<Vec<Value>> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    loop {
+        let output = lncli(network)
+            .arg(command)
+            .args(extra)
+            .args(["--index_offset", &offset.to_string(), max_flag, &PAGE_SIZE.to_string(), forwards_flag])
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow!("lncli {} failed: {}", command, String::from_utf8_lossy(&output.stderr)));
+        }
+        let json: Value = serde_json::from_slice(&output.stdout)?;
+        let page = json[field].as_array().cloned().unwrap_or_default();
+        if page.is_empty() {
+            break;
+        }
+        records.extend(page);
+        let next_offset = json_u64(&json["last_index_offset"]);
+        if next_offset <= offset {
+            break;
+        }
+        offset = next_offset;
+    }
+    Ok(records)
+}
+
+fn invoice_rows(network: &str, db: &sled::Db) -> Result<Vec<ExportRow>> {
+    let invoices = list_all(network, "listinvoices", "--max_invoices", "--paginate-forwards", &[], "invoices")?;
+    let annotations = store::annotations(db, network)?;
+    Ok(invoices.iter().map(|i| {
+        let state = i["state"].as_str().unwrap_or_default().to_string();
+        // What was actually received for settled invoices, the requested amount otherwise
+        let amount_msat = if state == "SETTLED" { json_u64(&i["amt_paid_msat"]) } else { json_u64(&i["value_msat"]) };
+        let created_secs = json_u64(&i["creation_date"]) as i64;
+        ExportRow {
+            record_type: RecordType::Invoice.as_str(),
+            id: json_u64(&i["add_index"]).to_string(),
+            created_at: rfc3339(created_secs),
+            settled_at: rfc3339(json_u64(&i["settle_date"]) as i64),
+            state,
+            direction: "in",
+            amount_sat: amount_msat / 1000,
+            amount_msat,
+            fee_sat: 0,
+            fee_msat: 0,
+            payment_hash: i["r_hash"].as_str().unwrap_or_default().to_string(),
+            memo: i["memo"].as_str().unwrap_or_default().to_string(),
+            reference: i["payment_request"].as_str().unwrap_or_default().to_string(),
+            counterparty: String::new(),
+            order_ref: String::new(),
+            tags: String::new(),
+            notes: String::new(),
+            created_secs,
+        }.annotate(&annotations)
+    }).collect())
+}
+
+fn payment_rows(network: &str, db: &sled::Db) -> Result<Vec<ExportRow>> {
+    let payments = list_all(network, "listpayments", "--max_payments", "--paginate_forwards", &["--include_incomplete"], "payments")?;
+    let annotations = store::annotations(db, network)?;
+    Ok(payments.iter().map(|p| {
+        let created_secs = (json_u64(&p["creation_time_ns"]) / 1_000_000_000) as i64;
+        // A payment completes when its last successful HTLC resolves
+        let settled_ns = p["htlcs"].as_array().map(|htlcs| {
+            htlcs.iter()
+                .filter(|h| h["status"].as_str() == Some("SUCCEEDED"))
+                .map(|h| json_u64(&h["resolve_time_ns"]))
+                .max()
+                .unwrap_or(0)
+        }).unwrap_or(0);
+        let amount_msat = json_u64(&p["value_msat"]);
+        let fee_msat = json_u64(&p["fee_msat"]);
+        ExportRow {
+            record_type: RecordType::Payment.as_str(),
+            id: json_u64(&p["payment_index"]).to_string(),
+            created_at: rfc3339(created_secs),
+            settled_at: rfc3339((settled_ns / 1_000_000_000) as i64),
+            state: p["status"].as_str().unwrap_or_default().to_string(),
+            direction: "out",
+            amount_sat: amount_msat / 1000,
+            amount_msat,
+            fee_sat: fee_msat / 1000,
+            fee_msat,
+            payment_hash: p["payment_hash"].as_str().unwrap_or_default().to_string(),
+            memo: String::new(),
+            reference: p["payment_request"].as_str().unwrap_or_default().to_string(),
+            counterparty: String::new(),
+            order_ref: String::new(),
+            tags: String::new(),
+            notes: String::new(),
+            created_secs,
+        }.annotate(&annotations)
+    }).collect())
+}
+
+fn forward_rows(network: &str, db: &sled::Db) -> Result<Vec<ExportRow>> {
+    forwarding::sync(network, db)?;
+    Ok(forwarding::list_events(network, db)?.into_iter().map(|event| {
+        let created_secs = (event.timestamp_ns / 1_000_000_000) as i64;
+        ExportRow {
+            record_type: RecordType::Forward.as_str(),
+            id: event.timestamp_ns.to_string(),
+            created_at: rfc3339(created_secs),
+            settled_at: rfc3339(created_secs),
+            state: "SETTLED".to_string(),
+            direction: "forward",
+            amount_sat: event.amt_out_msat / 1000,
+            amount_msat: event.amt_out_msat,
+            fee_sat: event.fee_msat / 1000,
+            fee_msat: event.fee_msat,
+            payment_hash: String::new(),
+            memo: String::new(),
+            reference: format!("{} -> {}", event.chan_id_in, event.chan_id_out),
+            counterparty: String::new(),
+            order_ref: String::new(),
+            tags: String::new(),
+            notes: String::new(),
+            created_secs,
+        }
+    }).collect())
+}
+
+fn onchain_rows(network: &str) -> Result<Vec<ExportRow>> {
+    Ok(wallet::list_transactions(network)?.into_iter().map(|tx| {
+        let created_secs = tx.timestamp as i64;
+        let amount_sat = tx.amount_sat.unsigned_abs();
+        ExportRow {
+            record_type: RecordType::OnChain.as_str(),
+            id: tx.txid.clone(),
+            created_at: rfc3339(created_secs),
+            settled_at: if tx.confirmations > 0 { rfc3339(created_secs) } else { String::new() },
+            state: if tx.confirmations > 0 { "CONFIRMED" } else { "UNCONFIRMED" }.to_string(),
+            direction: if tx.amount_sat < 0 { "out" } else { "in" },
+            amount_sat,
+            amount_msat: amount_sat * 1000,
+            fee_sat: tx.fee_sat,
+            fee_msat: tx.fee_sat * 1000,
+            payment_hash: String::new(),
+            memo: tx.label,
+            reference: tx.txid,
+            counterparty: String::new(),
+            order_ref: String::new(),
+            tags: String::new(),
+            notes: String::new(),
+            created_secs,
+        }
+    }).collect())
+}
+
+/// All records of the given types created between `from` and `to`, both days inclusive, oldest first.
+pub fn collect(network: &str, db: &sled::Db, types: &[RecordType], from: NaiveDate, to: NaiveDate) -> Result<Vec<ExportRow>> {
+    if from > to {
+        return Err(anyhow!("The start date {} is after the end date {}", from, to));
+    }
+    let start = from.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc().timestamp()).unwrap_or(0);
+    let end = to.succ_opt().and_then(|d| d.and_hms_opt(0, 0, 0)).map(|dt| dt.and_utc().timestamp()).unwrap_or(i64::MAX);
+
+    let mut rows = Vec::new();
+    for record_type in types {
+        rows.extend(match record_type {
+            RecordType::Invoice => invoice_rows(network, db)?,
+            RecordType::Payment => payment_rows(network, db)?,
+            RecordType::Forward => forward_rows(network, db)?,
+            RecordType::OnChain => onchain_rows(network)?,
+        });
+    }
+    rows.retain(|row| row.created_secs >= start && row.created_secs < end);
+    rows.sort_by_key(|row| row.created_secs);
+    Ok(rows)
+}
+
+/// Quotes a CSV field, and neutralises memos or notes a spreadsheet would run as a formula.
+fn csv_escape(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", field)
+    } else {
+        field.to_string()
+    };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+pub fn render(rows: &[ExportRow], format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(rows)?),
+        ExportFormat::Csv => {
+            let mut csv = COLUMNS.join(",") + "\n";
+            for row in rows {
+                let fields: Vec<String> = row.csv_fields().iter().map(|f| csv_escape(f)).collect();
+                let _ = writeln!(csv, "{}", fields.join(","));
+            }
+            Ok(csv)
+        }
+    }
+}
+
+/// Collects and writes the export to `dir`, returning the file and the number of rows.
+pub fn export(network: &str, db: &sled::Db, types: &[RecordType], from: NaiveDate, to: NaiveDate, format: ExportFormat, dir: &Path) -> Result<(PathBuf, usize)> {
+    if types.is_empty() {
+        return Err(anyhow!("Select at least one kind of record to export"));
+    }
+    let rows = collect(network, db, types, from, to)?;
+    let file_name = format!(
+        "lightning_export_{}_{}_{}_{}.{}",
+        network, from, to, Utc::now().format("%Y%m%d_%H%M%S"), format.extension()
+    );
+    let path = dir.join(file_name);
+    std::fs::write(&path, render(&rows, format)?).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    Ok((path, rows.len()))
+}
+
+pub fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").map_err(|_| anyhow!("Dates must be YYYY-MM-DD, got '{}'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_escape_quotes_and_defuses_formulas() {
+        assert_eq!(csv_escape("coffee"), "coffee");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+        assert_eq!(csv_escape("+1"), "'+1");
+        assert_eq!(csv_escape("-1"), "'-1");
+        assert_eq!(csv_escape("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_escape("a=b"), "a=b");
+    }
+}
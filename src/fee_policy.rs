@@ -0,0 +1,183 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{channels, store};
+use crate::utils::{json_u64, lncli};
+
+const FEE_PROFILES_TREE: &str = "fee_profiles";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeePolicy {
+    pub base_fee_msat: u64,
+    pub fee_rate_ppm: u64,
+    pub time_lock_delta: u64,
+    pub min_htlc_msat: u64,
+    pub max_htlc_msat: u64,
+}
+
+/// Raises fees on a channel (or all channels) while its local balance is below a threshold,
+/// and puts them back once the channel has recovered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeProfile {
+    pub name: String,
+    pub chan_id: String, // empty for all channels
+    pub threshold_pct: u64,
+    pub low_base_fee_msat: u64,
+    pub low_fee_rate_ppm: u64,
+    pub normal_base_fee_msat: u64,
+    pub normal_fee_rate_ppm: u64,
+}
+
+/// Our side of a channel's policy as currently announced in the graph, with its channel point.
+pub fn current_policy(network: &str, chan_id: &str, own_pubkey: &str) -> Result<(String, FeePolicy)> {
+    let output = lncli(network).args(["getchaninfo", chan_id]).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("Failed to get channel info for {}: {}", chan_id, stderr));
+    }
+    let json: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow!("Failed to parse getchaninfo JSON: {}", e))?;
+
+    let policy_key = if json["node1_pub"].as_str() == Some(own_pubkey) {
+        "node1_policy"
+    } else if json["node2_pub"].as_str() == Some(own_pubkey) {
+        "node2_policy"
+    } else {
+        return Err(anyhow!("Channel {} does not belong to node {}", chan_id, own_pubkey));
+    };
+    let policy = &json[policy_key];
+    if policy.is_null() {
+        return Err(anyhow!("No policy announced yet for channel {}", chan_id));
+    }
+
+    let chan_point = json["chan_point"].as_str().unwrap_or_default().to_string();
+    Ok((chan_point, FeePolicy {
+        base_fee_msat: json_u64(&policy["fee_base_msat"]),
+        fee_rate_ppm: json_u64(&policy["fee_rate_milli_msat"]),
+        time_lock_delta: json_u64(&policy["time_lock_delta"]),
+        min_htlc_msat: json_u64(&policy["min_htlc"]),
+        max_htlc_msat: json_u64(&policy["max_htlc_msat"]),
+    }))
+}
+
+/// Applies `policy` to a single channel, or to every channel when `chan_point` is None.
+pub fn update_policy(network: &str, chan_point: Option<&str>, policy: &FeePolicy) -> Result<()> {
+    if policy.max_htlc_msat > 0 && policy.min_htlc_msat > policy.max_htlc_msat {
+        return Err(anyhow!("Min HTLC must not be greater than max HTLC"));
+    }
+    if policy.time_lock_delta < 18 {
+        return Err(anyhow!("Time lock delta must be at least 18 blocks"));
+    }
+
+    let base_fee = policy.base_fee_msat.to_string();
+    let fee_rate = policy.fee_rate_ppm.to_string();
+    let time_lock_delta = policy.time_lock_delta.to_string();
+    let min_htlc = policy.min_htlc_msat.to_string();
+    let max_htlc = policy.max_htlc_msat.to_string();
+
+    let mut command = lncli(network);
+    command.args([
+        "updatechanpolicy",
+        "--base_fee_msat", &base_fee,
+        "--fee_rate_ppm", &fee_rate,
+        "--time_lock_delta", &time_lock_delta,
+        "--min_htlc_msat", &min_htlc,
+    ]);
+    if policy.max_htlc_msat > 0 {
+        command.args(["--max_htlc_msat", &max_htlc]);
+    }
+    if let Some(chan_point) = chan_point {
+        command.args(["--chan_point", chan_point]);
+    }
+
+    let output = command.output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("Failed to update channel policy: {}", stderr));
+    }
+    println!("Updated channel policy ({}): {}", chan_point.unwrap_or("all channels"), String::from_utf8_lossy(&output.stdout));
+    Ok(())
+}
+
+pub fn list_profiles(network: &str, db: &sled::Db) -> Result<Vec<FeeProfile>> {
+    let tree = store::scoped_tree(db, network, FEE_PROFILES_TREE)?;
+    let mut profiles = Vec::new();
+    for entry in tree.iter() {
+        let (_, value) = entry?;
+        match bincode::deserialize::<FeeProfile>(&value) {
+            Ok(profile) => profiles.push(profile),
+            Err(e) => println!("Skipping unreadable fee profile: {}", e),
+        }
+    }
+    Ok(profiles)
+}
+
+pub fn save_profile(network: &str, db: &sled::Db, profile: &FeeProfile) -> Result<()> {
+    if profile.name.trim().is_empty() {
+        return Err(anyhow!("Profile name must not be empty"));
+    }
+    if profile.threshold_pct == 0 || profile.threshold_pct >= 100 {
+        return Err(anyhow!("Threshold must be between 1 and 99 percent"));
+    }
+    let tree = store::scoped_tree(db, network, FEE_PROFILES_TREE)?;
+    tree.insert(profile.name.as_bytes(), bincode::serialize(profile)?)?;
+    Ok(())
+}
+
+pub fn delete_profile(network: &str, db: &sled::Db, name: &str) -> Result<()> {
+    let tree = store::scoped_tree(db, network, FEE_PROFILES_TREE)?;
+    tree.remove(name.as_bytes())?;
+    Ok(())
+}
+
+/// Evaluates every saved profile against current channel balances and updates
+/// the policy of each channel whose fees don't match its liquidity state.
+/// Returns a description of each change made.
+pub fn apply_profiles(network: &str, db: &sled::Db, own_pubkey: &str) -> Result<Vec<String>> {
+    let profiles = list_profiles(network, db)?;
+    if profiles.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut changes = Vec::new();
+    for channel in channels::list_active_channels(network)? {
+        // A profile for this specific channel wins over a profile for all channels
+        let Some(profile) = profiles.iter().find(|p| p.chan_id == channel.channel_id)
+            .or_else(|| profiles.iter().find(|p| p.chan_id.is_empty())) else { continue };
+
+        let capacity = channel.capacity.to_sat_floor();
+        let local_balance = channel.local_balance.to_sat_floor();
+        if capacity == 0 {
+            continue;
+        }
+        let local_pct = local_balance * 100 / capacity;
+        let low_liquidity = local_pct < profile.threshold_pct;
+
+        let (chan_point, current) = match current_policy(network, &channel.channel_id, own_pubkey) {
+            Ok(policy) => policy,
+            Err(e) => {
+                println!("Skipping fee profile for {}: {}", channel.channel_id, e);
+                continue;
+            }
+        };
+        let target = FeePolicy {
+            base_fee_msat: if low_liquidity { profile.low_base_fee_msat } else { profile.normal_base_fee_msat },
+            fee_rate_ppm: if low_liquidity { profile.low_fee_rate_ppm } else { profile.normal_fee_rate_ppm },
+            ..current.clone()
+        };
+        if target == current {
+            continue;
+        }
+
+        match update_policy(network, Some(&chan_point), &target) {
+            Ok(_) => changes.push(format!(
+                "{}: channel {} at {}% local, set {} msat + {} ppm",
+                profile.name, channel.channel_id, local_pct, target.base_fee_msat, target.fee_rate_ppm
+            )),
+            Err(e) => println!("Fee profile {} failed on {}: {}", profile.name, channel.channel_id, e),
+        }
+    }
+
+    Ok(changes)
+}
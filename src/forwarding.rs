@@ -0,0 +1,166 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::store;
+use crate::utils::{json_u64, lncli};
+
+const FORWARDS_TREE: &str = "forwarding_events";
+const FORWARDS_META_TREE: &str = "forwarding_meta";
+const SYNC_OFFSET_KEY: &[u8] = b"last_offset_index";
+const PAGE_SIZE: u64 = 10000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardingEvent {
+    pub timestamp_ns: u64,
+    pub chan_id_in: String,
+    pub chan_id_out: String,
+    pub amt_in_msat: u64,
+    pub amt_out_msat: u64,
+    pub fee_msat: u64,
+}
+
+pub struct ChannelRevenue {
+    pub chan_id: String,
+    pub forwards: u64,
+    pub fee_msat: u64,
+    pub volume_msat: u64,
+}
+
+pub struct DailyRevenue {
+    pub day: String,
+    pub forwards: u64,
+    pub fee_msat: u64,
+}
+
+/// Pages through fwdinghistory from where the last sync stopped and stores new events.
+/// Returns the number of events added.
+pub fn sync(network: &str, db: &sled::Db) -> Result<usize> {
+    let events_tree = store::scoped_tree(db, network, FORWARDS_TREE)?;
+    let meta_tree = store::scoped_tree(db, network, FORWARDS_META_TREE)?;
+    let mut offset = meta_tree.get(SYNC_OFFSET_KEY)?
+        .and_then(|v| v.as_ref().try_into().ok().map(u64::from_be_bytes))
+        .unwrap_or(0);
+
+    // A fixed time range keeps index offsets stable between syncs, the log is append-only
+    let end_time = Utc::now().timestamp().to_string();
+    let mut added = 0;
+
+    loop {
+        let output = lncli(network)
+            .args([
+                "fwdinghistory",
+                "--start_time", "1",
+                "--end_time", &end_time,
+                "--index_offset", &offset.to_string(),
+                "--max_events", &PAGE_SIZE.to_string(),
+            ])
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!("Failed to fetch forwarding history: {}", stderr));
+        }
+        let json: Value = serde_json::from_slice(&output.stdout)
+            .map_err(|e| anyhow!("Failed to parse fwdinghistory JSON: {}", e))?;
+
+        let events = json["forwarding_events"].as_array().cloned().unwrap_or_default();
+        for (i, event) in events.iter().enumerate() {
+            let forward = ForwardingEvent {
+                timestamp_ns: json_u64(&event["timestamp_ns"]),
+                chan_id_in: event["chan_id_in"].as_str().unwrap_or_default().to_string(),
+                chan_id_out: event["chan_id_out"].as_str().unwrap_or_default().to_string(),
+                amt_in_msat: json_u64(&event["amt_in_msat"]),
+                amt_out_msat: json_u64(&event["amt_out_msat"]),
+                fee_msat: json_u64(&event["fee_msat"]),
+            };
+            // Keyed by time first so iteration is chronological, the offset keeps keys unique
+            let mut key = forward.timestamp_ns.to_be_bytes().to_vec();
+            key.extend_from_slice(&(offset + i as u64).to_be_bytes());
+            events_tree.insert(key, bincode::serialize(&forward)?)?;
+        }
+        added += events.len();
+
+        offset = json_u64(&json["last_offset_index"]).max(offset);
+        meta_tree.insert(SYNC_OFFSET_KEY, &offset.to_be_bytes())?;
+
+        if (events.len() as u64) < PAGE_SIZE {
+            break;
+        }
+    }
+
+    println!("Synced {} new forwarding events", added);
+    Ok(added)
+}
+
+pub fn list_events(network: &str, db: &sled::Db) -> Result<Vec<ForwardingEvent>> {
+    let tree = store::scoped_tree(db, network, FORWARDS_TREE)?;
+    let mut events = Vec::new();
+    for entry in tree.iter() {
+        let (_, value) = entry?;
+        events.push(bincode::deserialize::<ForwardingEvent>(&value)?);
+    }
+    Ok(events)
+}
+
+/// Earned fees per outgoing channel, highest earning first.
+pub fn revenue_per_channel(events: &[ForwardingEvent]) -> Vec<ChannelRevenue> {
+    let mut per_channel: BTreeMap<&str, ChannelRevenue> = BTreeMap::new();
+    for event in events {
+        let entry = per_channel.entry(&event.chan_id_out).or_insert_with(|| ChannelRevenue {
+            chan_id: event.chan_id_out.clone(),
+            forwards: 0,
+            fee_msat: 0,
+            volume_msat: 0,
+        });
+        entry.forwards += 1;
+        entry.fee_msat += event.fee_msat;
+        entry.volume_msat += event.amt_out_msat;
+    }
+    let mut revenue: Vec<ChannelRevenue> = per_channel.into_values().collect();
+    revenue.sort_by_key(|r| std::cmp::Reverse(r.fee_msat));
+    revenue
+}
+
+/// Earned fees for each of the last `days` days (UTC), oldest first, including days without forwards.
+pub fn revenue_per_day(events: &[ForwardingEvent], days: i64) -> Vec<DailyRevenue> {
+    let today = Utc::now().date_naive();
+    let mut per_day: BTreeMap<String, DailyRevenue> = BTreeMap::new();
+    for offset in (0..days).rev() {
+        let day = (today - Duration::days(offset)).format("%Y-%m-%d").to_string();
+        per_day.insert(day.clone(), DailyRevenue { day, forwards: 0, fee_msat: 0 });
+    }
+    for event in events {
+        let day = format_day(event.timestamp_ns);
+        if let Some(entry) = per_day.get_mut(&day) {
+            entry.forwards += 1;
+            entry.fee_msat += event.fee_msat;
+        }
+    }
+    per_day.into_values().collect()
+}
+
+/// Writes all stored events as CSV into `dir` and returns the file path.
+pub fn export_csv(events: &[ForwardingEvent], dir: &Path, network: &str) -> Result<PathBuf> {
+    let mut csv = String::from("timestamp,chan_id_in,chan_id_out,amt_in_msat,amt_out_msat,fee_msat\n");
+    for event in events {
+        let timestamp = DateTime::from_timestamp_nanos(event.timestamp_ns as i64).to_rfc3339();
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{}",
+            timestamp, event.chan_id_in, event.chan_id_out, event.amt_in_msat, event.amt_out_msat, event.fee_msat
+        );
+    }
+
+    let file_name = format!("forwarding_history_{}_{}.csv", network, Utc::now().format("%Y%m%d_%H%M%S"));
+    let path = dir.join(file_name);
+    std::fs::write(&path, csv).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+fn format_day(timestamp_ns: u64) -> String {
+    DateTime::from_timestamp_nanos(timestamp_ns as i64).format("%Y-%m-%d").to_string()
+}
@@ -0,0 +1,276 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::amount::{Amount, Unit};
+use crate::utils::{extract_funding_txid_from_string, generate_preimage, lncli};
+use crate::node::{self, GetInfo};
+use crate::{channels, invoice, litd_service, wallet};
+
+const USAGE: &str = "Usage: lnd-htlc-ui --headless [--json] [--network NETWORK] <command> [options]
+
+Commands:
+  preimage                                          Generate a preimage and its payment hash
+  create-hold-invoice --amt AMOUNT [--memo TEXT] [--preimage HEX]
+                                                    Create a hold invoice, generating a preimage if none is given
+  list-invoices                                     List invoices
+  settle (--preimage HEX | --hash PAYMENT_HASH)     Settle a hold invoice, --hash uses the stored preimage
+  cancel --hash PAYMENT_HASH                        Cancel a hold invoice
+  annotate --hash PAYMENT_HASH [--counterparty NAME] [--order-ref REF] [--tags A,B] [--notes TEXT]
+                                                    Set local notes on an invoice or payment, omitted fields are kept
+  pay --invoice BOLT11 [--fee-limit AMOUNT]         Pay an invoice
+  open-channel --pubkey PUBKEY --amt AMOUNT         Open a channel
+  status                                            Show node status
+  help                                              Show this message
+
+Amounts are in sats unless suffixed with msat or btc, e.g. 2500msat or 0.001btc.
+The network defaults to the one selected in the app. Results are printed to stdout,
+with --json as a single JSON document; diagnostics go to stderr.";
+
+/// Runs one command against the node without opening the window, for scripts and cron jobs.
+/// `args` are the arguments after `--headless`.
+pub fn run(db: &sled::Db, args: &[String]) -> Result<()> {
+    let json_output = args.iter().any(|a| a == "--json");
+    match execute(db, args) {
+        Ok((value, text)) => {
+            if json_output {
+                println!("{}", serde_json::to_string_pretty(&value)?);
+            } else {
+                println!("{}", text);
+            }
+            Ok(())
+        }
+        Err(e) if json_output => {
+            println!("{}", json!({ "error": e.to_string() }));
+            std::process::exit(1);
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Value of `--name VALUE`, if given.
+fn flag(args: &[String], name: &str) -> Option<String> {
+    args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).cloned()
+}
+
+fn required(args: &[String], name: &str) -> Result<String> {
+    flag(args, name).ok_or_else(|| anyhow!("Missing {}, see --headless help", name))
+}
+
+fn parse_amount(value: &str) -> Result<Amount> {
+    Amount::parse_positive(value, Unit::Sat)
+}
+
+/// The first argument that is neither an option nor an option's value.
+fn command_name(args: &[String]) -> Option<&str> {
+    let mut skip_next = false;
+    for arg in args {
+        if skip_next {
+            skip_next = false;
+        } else if arg == "--json" {
+            continue;
+        } else if arg.starts_with("--") {
+            skip_next = true;
+        } else {
+            return Some(arg);
+        }
+    }
+    None
+}
+
+fn execute(db: &sled::Db, args: &[String]) -> Result<(Value, String)> {
+    let network = flag(args, "--network").unwrap_or_else(|| litd_service::current_network(db));
+    let command = command_name(args).unwrap_or("help");
+    // These open the node's namespace in the database, which needs its identity
+    if ["create-hold-invoice", "list-invoices", "settle", "annotate"].contains(&command) {
+        if let Err(e) = node::resolve_identity(db, &network) {
+            eprintln!("Could not ask the {} node for its identity, using the stored one: {}", network, e);
+        }
+    }
+    match command {
+        "help" => Ok((json!({ "usage": USAGE }), USAGE.to_string())),
+        "preimage" => {
+            let (preimage, hash) = generate_preimage();
+            let text = format!("preimage:     {}\npayment hash: {}", preimage, hash);
+            Ok((json!({ "preimage": preimage, "payment_hash": hash }), text))
+        }
+        "create-hold-invoice" => {
+            let amount = parse_amount(&required(args, "--amt")?)?;
+            let memo = flag(args, "--memo").unwrap_or_default();
+            let (preimage, hash) = match flag(args, "--preimage") {
+                Some(preimage) => {
+                    let bytes = hex::decode(&preimage).map_err(|e| anyhow!("Preimage is not valid hex: {}", e))?;
+                    if bytes.len() != 32 {
+                        return Err(anyhow!("Preimage must be 32 bytes, got {}", bytes.len()));
+                    }
+                    let hash = hex::encode(Sha256::digest(&bytes));
+                    (preimage, hash)
+                }
+                None => generate_preimage(),
+            };
+            let output = invoice::create_invoice(&network, preimage.clone(), hash.clone(), amount, memo, db)?;
+            let text = format!(
+                "payment hash:    {}\npreimage:        {}\npayment request: {}",
+                hash, preimage, output.payment_request
+            );
+            Ok((json!({
+                "payment_hash": hash,
+                "preimage": preimage,
+                "payment_request": output.payment_request,
+                "payment_addr": output.payment_addr,
+                "amount_sat": amount.to_sat_floor(),
+                "amount_msat": amount.as_msat(),
+            }), text))
+        }
+        "list-invoices" => {
+            let invoices = invoice::list_invoices(&network, db)?;
+            let text = invoices.iter()
+                .map(|r| format!("{}  {:>13}  {:<9}  {}  {}", r.creation_date(), r.invoice.value, r.invoice.state, r.invoice.r_hash, r.invoice.memo))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let value: Vec<Value> = invoices.iter().map(|r| json!({
+                "payment_hash": r.invoice.r_hash,
+                "memo": r.invoice.memo,
+                "value_sat": r.invoice.value.to_sat_floor(),
+                "value_msat": r.invoice.value.as_msat(),
+                "state": r.invoice.state,
+                "creation_date": r.creation_date(),
+                "payment_request": r.invoice.payment_request,
+                "is_own_invoice": r.is_own_invoice,
+                "counterparty": r.annotation.counterparty,
+                "order_ref": r.annotation.order_ref,
+                "tags": r.annotation.tags,
+                "notes": r.annotation.notes,
+            })).collect();
+            Ok((Value::Array(value), text))
+        }
+        "settle" => {
+            let preimage = match (flag(args, "--preimage"), flag(args, "--hash")) {
+                (Some(preimage), _) => preimage,
+                (None, Some(hash)) => invoice::stored_preimage(&network, db, &hash)?,
+                (None, None) => return Err(anyhow!("settle needs --preimage or --hash")),
+            };
+            invoice::settle_invoice(&network, preimage.clone())?;
+            Ok((json!({ "settled": true, "preimage": preimage }), "Invoice settled".to_string()))
+        }
+        "cancel" => {
+            let hash = required(args, "--hash")?;
+            invoice::cancel_invoice(&network, &hash)?;
+            Ok((json!({ "cancelled": true, "payment_hash": hash }), format!("Invoice {} cancelled", hash)))
+        }
+        "annotate" => {
+            let hash = required(args, "--hash")?;
+            let mut annotation = invoice::annotation(&network, db, &hash)?;
+            if let Some(counterparty) = flag(args, "--counterparty") {
+                annotation.counterparty = counterparty.trim().to_string();
+            }
+            if let Some(order_ref) = flag(args, "--order-ref") {
+                annotation.order_ref = order_ref.trim().to_string();
+            }
+            if let Some(tags) = flag(args, "--tags") {
+                annotation.tags = invoice::parse_tags(&tags);
+            }
+            if let Some(notes) = flag(args, "--notes") {
+                annotation.notes = notes.trim().to_string();
+            }
+            invoice::save_annotation(&network, db, &hash, &annotation)?;
+            Ok((json!({
+                "payment_hash": hash,
+                "counterparty": annotation.counterparty,
+                "order_ref": annotation.order_ref,
+                "tags": annotation.tags,
+                "notes": annotation.notes,
+            }), format!("Notes saved for {}", hash)))
+        }
+        "pay" => {
+            let payment_request = required(args, "--invoice")?;
+            let fee_limit = flag(args, "--fee-limit").map(|f| Amount::parse(&f, Unit::Sat)).transpose()?;
+            let outcome = invoice::pay_invoice(&network, &payment_request, fee_limit)?;
+            let value = json!({
+                "payment_hash": outcome.payment_hash,
+                "status": outcome.status,
+                "preimage": outcome.preimage,
+                "amount_sat": outcome.amount.to_sat_floor(),
+                "amount_msat": outcome.amount.as_msat(),
+                "fee_sat": outcome.fee.to_sat_floor(),
+                "fee_msat": outcome.fee.as_msat(),
+                "failure_reason": outcome.failure_reason,
+            });
+            if outcome.status != "SUCCEEDED" {
+                return Err(anyhow!("Payment {} {}: {}", outcome.payment_hash, outcome.status, outcome.failure_reason));
+            }
+            let text = format!(
+                "Paid {} ({} fee, {} total)\npayment hash: {}\npreimage:     {}",
+                outcome.amount, outcome.fee, outcome.total()?, outcome.payment_hash, outcome.preimage
+            );
+            Ok((value, text))
+        }
+        "open-channel" => {
+            let pubkey = required(args, "--pubkey")?;
+            let amount = parse_amount(&required(args, "--amt")?)?;
+            let output = channels::open_channel(&network, &pubkey, amount)?;
+            let funding_txid = extract_funding_txid_from_string(&output).unwrap_or_default();
+            let text = format!("Opening a {} channel to {}, funding txid {}", amount, pubkey, funding_txid);
+            Ok((json!({ "pubkey": pubkey, "amount_sat": amount.to_sat_floor(), "funding_txid": funding_txid }), text))
+        }
+        "status" => node_status(&network),
+        other => Err(anyhow!("Unknown command: {}, see --headless help", other)),
+    }
+}
+
+fn node_status(network: &str) -> Result<(Value, String)> {
+    let service = litd_service::litd_service_status(network)
+        .map(|(status, _)| status)
+        .unwrap_or_else(|e| format!("unknown ({})", e));
+    let output = lncli(network).arg("getinfo").output()?;
+    if !output.status.success() {
+        let text = format!("network: {}\nlitd:    {}\nnode:    not reachable", network, service);
+        return Ok((json!({ "network": network, "litd_service": service, "running": false }), text));
+    }
+    let info = GetInfo::parse(&output.stdout)?;
+    // The rest of the status is still worth printing if the wallet can't be read
+    let balance = wallet::balance(network)
+        .map_err(|e| eprintln!("Wallet balance unavailable: {}", e))
+        .ok();
+    let balance_text = match &balance {
+        Some(balance) => format!("{} confirmed, {} unconfirmed", balance.confirmed, balance.unconfirmed),
+        None => "unavailable".to_string(),
+    };
+
+    let value = json!({
+        "network": network,
+        "litd_service": service,
+        "running": true,
+        "identity_pubkey": info.identity_pubkey,
+        "alias": info.alias,
+        "color": info.color,
+        "version": info.version,
+        "block_height": info.block_height,
+        "synced_to_chain": info.synced_to_chain,
+        "synced_to_graph": info.synced_to_graph,
+        "num_active_channels": info.num_active_channels,
+        "num_pending_channels": info.num_pending_channels,
+        "num_inactive_channels": info.num_inactive_channels,
+        "num_peers": info.num_peers,
+        "uris": info.uris,
+        "confirmed_balance_sat": balance.as_ref().map(|b| b.confirmed.to_sat_floor()),
+        "unconfirmed_balance_sat": balance.as_ref().map(|b| b.unconfirmed.to_sat_floor()),
+    });
+    let text = format!(
+        "network:  {}\nlitd:     {}\npubkey:   {}\nalias:    {}\nversion:  {}\nheight:   {} (synced: {}, graph: {})\nchannels: {} active, {} pending, {} inactive, {} peers\nbalance:  {}",
+        network,
+        service,
+        info.identity_pubkey,
+        info.alias,
+        info.version,
+        info.block_height,
+        info.synced_to_chain,
+        info.synced_to_graph,
+        info.num_active_channels,
+        info.num_pending_channels,
+        info.num_inactive_channels,
+        info.num_peers,
+        balance_text,
+    );
+    Ok((value, text))
+}
@@ -0,0 +1,291 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use hyper::body::HttpBody;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use rand::RngCore;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::amount::Amount;
+use crate::utils::generate_preimage;
+use crate::{get_app_data_dir, invoice, litd_service, store};
+
+pub const DEFAULT_PORT: u16 = 8089;
+const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+#[derive(Deserialize)]
+struct CreateHoldInvoice {
+    #[serde(default)]
+    amount_sat: Option<u64>,
+    #[serde(default)]
+    amount_msat: Option<u64>,
+    #[serde(default)]
+    memo: String,
+}
+
+impl CreateHoldInvoice {
+    fn amount(&self) -> Result<Amount, String> {
+        let amount = match (self.amount_sat, self.amount_msat) {
+            (Some(sat), None) => Amount::from_sat(sat).map_err(|e| e.to_string())?,
+            (None, Some(msat)) => Amount::from_msat(msat),
+            _ => return Err("Give exactly one of amount_sat and amount_msat".to_string()),
+        };
+        if amount.is_zero() {
+            return Err("The amount must be positive".to_string());
+        }
+        Ok(amount)
+    }
+}
+
+/// The bearer token clients must send, created on first use and kept in sled.
+/// It is also written to `api_token` in the app data directory for other services to read.
+pub fn api_token(db: &sled::Db) -> Result<String> {
+    let token = match store::get_setting(db, store::API_TOKEN_KEY)? {
+        Some(token) => token,
+        None => {
+            let mut bytes = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            let token = hex::encode(bytes);
+            store::set_setting(db, store::API_TOKEN_KEY, &token)?;
+            token
+        }
+    };
+    if let Some(dir) = get_app_data_dir() {
+        write_token_file(&dir.join("api_token"), &token)?;
+    }
+    Ok(token)
+}
+
+#[cfg(unix)]
+fn write_token_file(path: &std::path::Path, token: &str) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    file.write_all(token.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_token_file(path: &std::path::Path, token: &str) -> Result<()> {
+    std::fs::write(path, token).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Serves the hold invoice API on 127.0.0.1 until the app exits.
+///
+/// Every request needs `Authorization: Bearer <token>`. Routes:
+/// - `POST /v1/hold-invoices` with `{"amount_sat": 1000, "memo": "..."}` or `amount_msat` instead,
+///   the preimage stays in the app
+/// - `GET /v1/hold-invoices`
+/// - `GET /v1/hold-invoices/{payment_hash}`
+/// - `POST /v1/hold-invoices/{payment_hash}/settle`
+/// - `POST /v1/hold-invoices/{payment_hash}/cancel`
+/// - `GET /v1/payments/{payment_hash}`
+pub async fn serve(db: sled::Db, port: u16) -> Result<()> {
+    let token = api_token(&db)?;
+    let address = SocketAddr::from(([127, 0, 0, 1], port));
+    let make_service = make_service_fn(move |_| {
+        let db = db.clone();
+        let token = token.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let db = db.clone();
+                let token = token.clone();
+                async move { Ok::<_, Infallible>(handle(db, token, request).await) }
+            }))
+        }
+    });
+    println!("HTTP API listening on http://{}", address);
+    Server::try_bind(&address)
+        .with_context(|| format!("Failed to bind the HTTP API to {}", address))?
+        .serve(make_service)
+        .await
+        .context("HTTP API server failed")
+}
+
+fn respond(status: StatusCode, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap_or_default()
+}
+
+fn error(status: StatusCode, message: impl ToString) -> Response<Body> {
+    respond(status, json!({ "error": message.to_string() }))
+}
+
+/// Compares in constant time so the token can't be guessed byte by byte.
+fn token_matches(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected.bytes().zip(given.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn handle(db: sled::Db, token: String, request: Request<Body>) -> Response<Body> {
+    let authorized = request.headers().get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| token_matches(&token, given.trim()));
+    if !authorized {
+        return error(StatusCode::UNAUTHORIZED, "Missing or invalid bearer token");
+    }
+
+    let method = request.method().clone();
+    let path = request.uri().path().trim_end_matches('/').to_string();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    println!("HTTP API {} {}", method, path);
+
+    let body = if method == Method::POST {
+        let too_large = request.headers().get("Content-Length")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .is_some_and(|length| length > MAX_BODY_BYTES);
+        if too_large {
+            return error(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large");
+        }
+        // Content-Length can be missing or wrong with chunked bodies, so count as we read
+        let mut incoming = request.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = incoming.data().await {
+            match chunk {
+                Ok(chunk) if (bytes.len() + chunk.len()) as u64 > MAX_BODY_BYTES => {
+                    return error(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large");
+                }
+                Ok(chunk) => bytes.extend_from_slice(&chunk),
+                Err(e) => return error(StatusCode::BAD_REQUEST, e),
+            }
+        }
+        bytes
+    } else {
+        Default::default()
+    };
+
+    let route = match (&method, segments.as_slice()) {
+        (&Method::POST, ["v1", "hold-invoices"]) => match serde_json::from_slice::<CreateHoldInvoice>(&body) {
+            Ok(create) => Route::Create(create),
+            Err(e) => return error(StatusCode::BAD_REQUEST, format!("Invalid request body: {}", e)),
+        },
+        (&Method::GET, ["v1", "hold-invoices"]) => Route::List,
+        (&Method::GET, ["v1", "hold-invoices", hash]) => Route::Lookup(hash.to_string()),
+        (&Method::POST, ["v1", "hold-invoices", hash, "settle"]) => Route::Settle(hash.to_string()),
+        (&Method::POST, ["v1", "hold-invoices", hash, "cancel"]) => Route::Cancel(hash.to_string()),
+        (&Method::GET, ["v1", "payments", hash]) => Route::Payment(hash.to_string()),
+        _ => return error(StatusCode::NOT_FOUND, format!("No route for {} {}", method, path)),
+    };
+
+    // lncli calls block, keep them off the server's worker threads
+    let task_db = db.clone();
+    let result = tokio::task::spawn_blocking(move || route.run(&task_db)).await;
+    match result {
+        Ok(Ok((status, body))) => respond(status, body),
+        Ok(Err(e)) => error(StatusCode::BAD_GATEWAY, e),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+enum Route {
+    Create(CreateHoldInvoice),
+    List,
+    Lookup(String),
+    Settle(String),
+    Cancel(String),
+    Payment(String),
+}
+
+impl Route {
+    fn run(self, db: &sled::Db) -> Result<(StatusCode, Value)> {
+        let network = litd_service::current_network(db);
+        match self {
+            Route::Create(create) => {
+                let amount = match create.amount() {
+                    Ok(amount) => amount,
+                    Err(e) => return Ok((StatusCode::BAD_REQUEST, json!({ "error": e }))),
+                };
+                let (preimage, hash) = generate_preimage();
+                let output = invoice::create_invoice(&network, preimage, hash.clone(), amount, create.memo, db)?;
+                Ok((StatusCode::CREATED, json!({
+                    "payment_hash": hash,
+                    "payment_request": output.payment_request,
+                    "payment_addr": output.payment_addr,
+                    "amount_sat": amount.to_sat_floor(),
+                    "amount_msat": amount.as_msat(),
+                })))
+            }
+            Route::List => {
+                let invoices: Vec<Value> = invoice::list_invoices(&network, db)?.iter()
+                    // Only invoices whose preimage this app holds can be settled through the API
+                    .filter(|r| invoice::stored_preimage(&network, db, &r.invoice.r_hash).is_ok())
+                    .map(|r| json!({
+                        "payment_hash": r.invoice.r_hash,
+                        "memo": r.invoice.memo,
+                        "value_sat": r.invoice.value.to_sat_floor(),
+                        "value_msat": r.invoice.value.as_msat(),
+                        "state": r.invoice.state,
+                        "creation_date": r.creation_date(),
+                        "payment_request": r.invoice.payment_request,
+                    }))
+                    .collect();
+                Ok((StatusCode::OK, json!({ "invoices": invoices })))
+            }
+            Route::Lookup(hash) => {
+                if let Some(not_found) = unknown_invoice(&network, db, &hash) {
+                    return Ok(not_found);
+                }
+                let status = invoice::lookup_invoice(&network, &hash)?;
+                Ok((StatusCode::OK, json!({
+                    "payment_hash": status.payment_hash,
+                    "state": status.state,
+                    "value_sat": status.value.to_sat_floor(),
+                    "value_msat": status.value.as_msat(),
+                    "amount_paid_sat": status.amount_paid.to_sat_floor(),
+                    "amount_paid_msat": status.amount_paid.as_msat(),
+                    "memo": status.memo,
+                    "payment_request": status.payment_request,
+                })))
+            }
+            Route::Settle(hash) => {
+                let preimage = match invoice::stored_preimage(&network, db, &hash) {
+                    Ok(preimage) => preimage,
+                    Err(e) => return Ok((StatusCode::NOT_FOUND, json!({ "error": e.to_string() }))),
+                };
+                invoice::settle_invoice(&network, preimage)?;
+                Ok((StatusCode::OK, json!({ "payment_hash": hash, "settled": true })))
+            }
+            Route::Cancel(hash) => {
+                if let Some(not_found) = unknown_invoice(&network, db, &hash) {
+                    return Ok(not_found);
+                }
+                invoice::cancel_invoice(&network, &hash)?;
+                Ok((StatusCode::OK, json!({ "payment_hash": hash, "cancelled": true })))
+            }
+            Route::Payment(hash) => match invoice::payment_status(&network, &hash)? {
+                Some(payment) => Ok((StatusCode::OK, json!({
+                    "payment_hash": payment.payment_hash,
+                    "status": payment.status,
+                    "amount_sat": payment.amount.to_sat_floor(),
+                    "amount_msat": payment.amount.as_msat(),
+                    "fee_sat": payment.fee.to_sat_floor(),
+                    "fee_msat": payment.fee.as_msat(),
+                    "failure_reason": payment.failure_reason,
+                }))),
+                None => Ok((StatusCode::NOT_FOUND, json!({ "error": format!("No payment with hash {}", hash) }))),
+            },
+        }
+    }
+}
+
+/// The API only reaches hold invoices created in this app, anything else on the node is
+/// reported as not found.
+fn unknown_invoice(network: &str, db: &sled::Db, hash: &str) -> Option<(StatusCode, Value)> {
+    invoice::stored_preimage(network, db, hash)
+        .err()
+        .map(|e| (StatusCode::NOT_FOUND, json!({ "error": e.to_string() })))
+}
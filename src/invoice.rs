@@ -1,70 +1,254 @@
 use anyhow::{anyhow, Result};
-use chrono::{DateTime, NaiveDateTime, Utc};
-use sha2::{Digest, Sha256};
-use std::process::Command;
-use bincode;
+use chrono::{DateTime, NaiveDate};
 use copypasta::{ClipboardContext, ClipboardProvider};
 
-use crate::{InvoiceData, InvoiceDetails, ListInvoicesResponse};
+use crate::amount::{Amount, Unit};
+use crate::utils::{json_u64, lncli};
+use crate::store::{self, Annotation, InvoiceData};
+use crate::invoice_index::{self, IndexedInvoice};
+use crate::InvoiceDetails;
 
-pub fn list_invoices(db: &sled::Db) -> Result<Vec<InvoiceDetails>> {
-    let output = Command::new("/usr/local/bin/lncli")
-        .args(["--network", "testnet", "listinvoices"])
-        .output()?;
-    // println!("{}", String::from_utf8_lossy(&output.stdout));
-
-    match serde_json::from_str::<ListInvoicesResponse>(&String::from_utf8_lossy(&output.stdout)) {
-        Ok(parsed_response) => {
-            let slint_invoices_vec: Vec<InvoiceDetails> = parsed_response.invoices.into_iter().map(|i| {
-                let formatted_date = match i.creation_date.parse::<i64>() {
-                    Ok(timestamp_seconds) => {
-                        // Ensure NaiveDateTime::from_timestamp_opt is used for safety
-                        NaiveDateTime::from_timestamp_opt(timestamp_seconds, 0)
-                            .map(|naive_dt| DateTime::<Utc>::from_naive_utc_and_offset(naive_dt, Utc).format("%Y-%m-%d %H:%M:%S UTC").to_string())
-                            .unwrap_or_else(|| {
-                                println!("Warning: Failed to format timestamp {} for r_hash {}", timestamp_seconds, i.r_hash);
-                                i.creation_date.clone() // Fallback to original string
-                            })
-                    }
-                    Err(e) => {
-                        println!("Warning: Failed to parse creation_date '{}' as i64 for r_hash {}: {}", i.creation_date, i.r_hash, e);
-                        i.creation_date.clone() // Fallback to original string if timestamp is not a valid i64
-                    }
-                };
-
-                let is_own_invoice = match db.get(i.r_hash.as_bytes()) {
-                    Ok(Some(invoice_data)) => {
-                        bincode::deserialize::<InvoiceData>(&invoice_data)
-                            .map_or(false, |deserialized_struct| {
-                                println!("Preimage X: {}", deserialized_struct.preimage_x);
-                                println!("Preimage H: {}", deserialized_struct.preimage_h);
-                                deserialized_struct.is_own_invoice
-                            })
-                    }
-                    _ => false,
-                };
-
-                InvoiceDetails {
-                    memo: i.memo.into(),
-                    r_hash: i.r_hash.into(),
-                    value: i.value.into(),
-                    state: i.state.into(),
-                    creation_date: formatted_date.into(),
-                    is_own_invoice,
-                    payment_request: i.payment_request.into(),
-                }
-            }).collect();
+/// An indexed invoice together with what we keep about it locally.
+pub struct InvoiceRecord {
+    pub invoice: IndexedInvoice,
+    pub is_own_invoice: bool,
+    pub annotation: Annotation,
+}
 
-            Ok(slint_invoices_vec)
+impl InvoiceRecord {
+    pub fn creation_date(&self) -> String {
+        DateTime::from_timestamp(self.invoice.creation_date, 0)
+            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_else(|| {
+                eprintln!("Warning: Failed to format timestamp {} for r_hash {}", self.invoice.creation_date, self.invoice.r_hash);
+                self.invoice.creation_date.to_string()
+            })
+    }
+
+    /// Row for the invoices view with the value shown in `unit`.
+    pub fn details(self, unit: Unit) -> InvoiceDetails {
+        let creation_date = self.creation_date();
+        let (invoice, annotation) = (self.invoice, self.annotation);
+        InvoiceDetails {
+            notes: annotation.notes.into(),
+            tags: annotation.tags.join(", ").into(),
+            counterparty: annotation.counterparty.into(),
+            order_ref: annotation.order_ref.into(),
+            memo: invoice.memo.into(),
+            r_hash: invoice.r_hash.into(),
+            value: invoice.value.format(unit).into(),
+            state: invoice.state.into(),
+            creation_date: creation_date.into(),
+            is_own_invoice: self.is_own_invoice,
+            payment_request: invoice.payment_request.into(),
         }
-        Err(e) => {
-            let error_msg = format!("Error parsing invoices JSON: {}", e);
-            println!("{}", error_msg);
-            Err(anyhow!("Error parsing invoices JSON: {}", e))
+    }
+}
+
+/// Syncs the local invoice index and returns every invoice, oldest first.
+pub fn list_invoices(network: &str, db: &sled::Db) -> Result<Vec<InvoiceRecord>> {
+    invoice_index::sync(network, db)?;
+    invoice_records(network, db, invoice_index::all(network, db)?)
+}
+
+/// Syncs the local invoice index, returning how many invoices were added or changed.
+pub fn sync_invoices(network: &str, db: &sled::Db) -> Result<usize> {
+    let delta = invoice_index::sync(network, db)?;
+    Ok(delta.added.len() + delta.updated.len())
+}
+
+// State filters in the order of the invoices view's combo box, empty for any state
+const STATE_FILTERS: [&str; 5] = ["", "OPEN", "ACCEPTED", "SETTLED", "CANCELED"];
+
+pub fn state_filter(index: i32) -> &'static str {
+    usize::try_from(index).ok().and_then(|i| STATE_FILTERS.get(i)).copied().unwrap_or("")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ownership {
+    All,
+    Own,
+    External,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InvoiceSort {
+    Newest,
+    Oldest,
+    LargestFirst,
+    SmallestFirst,
+}
+
+impl Ownership {
+    /// Index of the ownership combo box in the invoices view.
+    pub fn from_index(index: i32) -> Self {
+        match index {
+            1 => Ownership::Own,
+            2 => Ownership::External,
+            _ => Ownership::All,
         }
     }
-} 
+}
 
+impl InvoiceSort {
+    /// Index of the sort combo box in the invoices view.
+    pub fn from_index(index: i32) -> Self {
+        match index {
+            1 => InvoiceSort::Oldest,
+            2 => InvoiceSort::LargestFirst,
+            3 => InvoiceSort::SmallestFirst,
+            _ => InvoiceSort::Newest,
+        }
+    }
+}
+
+/// Filter, order and page of the invoice list. Empty or `None` fields don't filter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvoiceQuery {
+    pub state: String,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub min_amount: Option<Amount>,
+    pub max_amount: Option<Amount>,
+    pub ownership: Ownership,
+    /// Words that must all appear in the memo, payment hash or our annotations
+    pub text: String,
+    pub sort: InvoiceSort,
+    /// Zero based
+    pub page: usize,
+    pub page_size: usize,
+}
+
+impl InvoiceQuery {
+    pub fn new(page_size: usize) -> Self {
+        InvoiceQuery {
+            state: String::new(),
+            from: None,
+            to: None,
+            min_amount: None,
+            max_amount: None,
+            ownership: Ownership::All,
+            text: String::new(),
+            sort: InvoiceSort::Newest,
+            page: 0,
+            page_size: page_size.max(1),
+        }
+    }
+
+    fn matches(&self, invoice: &IndexedInvoice, is_own: bool, annotation: &Annotation) -> bool {
+        let day = DateTime::from_timestamp(invoice.creation_date, 0).map(|dt| dt.date_naive());
+        (self.state.is_empty() || invoice.state == self.state)
+            && self.from.is_none_or(|from| day.is_some_and(|d| d >= from))
+            && self.to.is_none_or(|to| day.is_some_and(|d| d <= to))
+            && self.min_amount.is_none_or(|min| invoice.value >= min)
+            && self.max_amount.is_none_or(|max| invoice.value <= max)
+            && match self.ownership {
+                Ownership::All => true,
+                Ownership::Own => is_own,
+                Ownership::External => !is_own,
+            }
+            && matches_text(invoice, annotation, &self.text)
+    }
+}
+
+/// Whether every word of `text` appears, ignoring case, in the memo, payment hash or our
+/// own annotations of the invoice.
+fn matches_text(invoice: &IndexedInvoice, annotation: &Annotation, text: &str) -> bool {
+    if text.trim().is_empty() {
+        return true;
+    }
+    let haystack = [
+        invoice.memo.as_str(), invoice.r_hash.as_str(), annotation.notes.as_str(), &annotation.tags.join(","),
+        annotation.counterparty.as_str(), annotation.order_ref.as_str(),
+    ].map(str::to_lowercase).join("\n");
+    text.to_lowercase().split_whitespace().all(|word| haystack.contains(word))
+}
+
+/// One page of query results and where it sits in the whole result.
+pub struct InvoicePage {
+    pub invoices: Vec<InvoiceDetails>,
+    /// Zero based, clamped to the last page
+    pub page: usize,
+    pub pages: usize,
+    pub total: usize,
+}
+
+/// Runs `query` against the local index without calling lnd. Only the requested page is
+/// turned into rows for the view, so the cost of a large index stays in plain Rust data.
+pub fn query_invoices(network: &str, db: &sled::Db, query: &InvoiceQuery, unit: Unit) -> Result<InvoicePage> {
+    let stored_invoices = store::invoices(db, network)?;
+    let annotations = store::annotations(db, network)?;
+    let mut matching: Vec<IndexedInvoice> = invoice_index::all(network, db)?.into_iter()
+        .filter(|i| {
+            let is_own = stored_invoices.get(&i.r_hash).ok().flatten().is_some_and(|data| data.is_own_invoice);
+            let annotation = annotations.get(&i.r_hash).ok().flatten().unwrap_or_default();
+            query.matches(i, is_own, &annotation)
+        })
+        .collect();
+
+    match query.sort {
+        InvoiceSort::Newest => matching.sort_by_key(|i| std::cmp::Reverse(i.add_index)),
+        InvoiceSort::Oldest => matching.sort_by_key(|i| i.add_index),
+        InvoiceSort::LargestFirst => matching.sort_by(|a, b| b.value.cmp(&a.value).then(b.add_index.cmp(&a.add_index))),
+        InvoiceSort::SmallestFirst => matching.sort_by(|a, b| a.value.cmp(&b.value).then(b.add_index.cmp(&a.add_index))),
+    }
+
+    let total = matching.len();
+    let pages = total.div_ceil(query.page_size).max(1);
+    let page = query.page.min(pages - 1);
+    let rows: Vec<IndexedInvoice> = matching.into_iter().skip(page * query.page_size).take(query.page_size).collect();
+    let invoices = invoice_records(network, db, rows)?.into_iter().map(|r| r.details(unit)).collect();
+    Ok(InvoicePage { invoices, page, pages, total })
+}
+
+fn invoice_records(network: &str, db: &sled::Db, invoices: Vec<IndexedInvoice>) -> Result<Vec<InvoiceRecord>> {
+    let stored_invoices = store::invoices(db, network)?;
+    let annotations = store::annotations(db, network)?;
+
+    Ok(invoices.into_iter().map(|i| {
+        let is_own_invoice = match stored_invoices.get(&i.r_hash) {
+            Ok(invoice_data) => invoice_data.is_some_and(|data| data.is_own_invoice),
+            Err(e) => {
+                eprintln!("Warning: {}", e);
+                false
+            }
+        };
+
+        let annotation = annotations.get(&i.r_hash).unwrap_or_else(|e| {
+            eprintln!("Warning: {}", e);
+            None
+        }).unwrap_or_default();
+
+        InvoiceRecord { invoice: i, is_own_invoice, annotation }
+    }).collect())
+}
+
+/// Splits a comma separated tag list, dropping blanks and repeats.
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    let mut parsed: Vec<String> = Vec::new();
+    for tag in tags.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        if !parsed.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            parsed.push(tag.to_string());
+        }
+    }
+    parsed
+}
+
+/// Notes, tags, counterparty and order reference kept for an invoice or payment, empty if none were saved.
+pub fn annotation(network: &str, db: &sled::Db, payment_hash: &str) -> Result<Annotation> {
+    Ok(store::annotations(db, network)?.get(payment_hash)?.unwrap_or_default())
+}
+
+pub fn save_annotation(network: &str, db: &sled::Db, payment_hash: &str, annotation: &Annotation) -> Result<()> {
+    let annotations = store::annotations(db, network)?;
+    if *annotation == Annotation::default() {
+        annotations.remove(payment_hash)
+    } else {
+        annotations.insert(payment_hash, annotation)
+    }
+}
+
+#[allow(dead_code)]
 pub struct InvoiceOutput {
     pub payment_addr: String,
     pub payment_request: String,
@@ -72,31 +256,41 @@ pub struct InvoiceOutput {
     pub identity_pubkey: String,
 }
 
-pub fn create_invoice(preimage_x: String, preimage_h: String, amount: String, memo: String, db: &sled::Db) -> Result<InvoiceOutput> {
-    let output = Command::new("/usr/local/bin/lncli")
-        .args(["--network", "testnet", "addholdinvoice", &preimage_h, "--amt", &amount, "--memo", &memo])
+/// Rejects a zero amount so lnd never creates an open-amount invoice by accident.
+fn invoice_amount(amount: Amount) -> Result<String> {
+    if amount.is_zero() {
+        return Err(anyhow!("The invoice amount must be greater than zero"));
+    }
+    Ok(amount.as_msat().to_string())
+}
+
+pub fn create_invoice(network: &str, preimage_x: String, preimage_h: String, amount: Amount, memo: String, db: &sled::Db) -> Result<InvoiceOutput> {
+    let amount_msat = invoice_amount(amount)?;
+    // Before lnd holds an invoice we could never settle without the stored preimage
+    let identity_pubkey_str = store::node_identity(db, network)?;
+    let invoices = store::invoices(db, network)?;
+    let output = lncli(network)
+        .args(["addholdinvoice", &preimage_h, "--amt_msat", &amount_msat, "--memo", &memo])
         .output()?;
-    println!("{}", String::from_utf8_lossy(&output.stdout));
+    eprintln!("{}", String::from_utf8_lossy(&output.stdout));
     let stdout = String::from_utf8_lossy(&output.stdout).to_string();
     match serde_json::from_str::<serde_json::Value>(&stdout) {
         Ok(json) => {
             if let Some(payment_addr) = json.get("payment_addr").and_then(|v| v.as_str()) {
                 if let Some(payment_request) = json.get("payment_request").and_then(|v| v.as_str()) {
-                    let invoice_output = Command::new("/usr/local/bin/lncli")
-                        .args(["--network", "testnet", "decodepayreq", payment_request])
+                    let invoice_output = lncli(network)
+                        .args(["decodepayreq", payment_request])
                         .output()?;
 
                     let invoice_stdout = String::from_utf8_lossy(&invoice_output.stdout).to_string();
                     let invoice_stderr = String::from_utf8_lossy(&invoice_output.stderr).to_string();
-                    println!("{}", invoice_stderr);
+                    eprintln!("{}", invoice_stderr);
                     match serde_json::from_str::<serde_json::Value>(&invoice_stdout) {
                         Ok(json) => {
                             let destination_pubkey = json.get("destination").and_then(|v| v.as_str()).unwrap_or("");
-                            let identity_pubkey = db.get(b"identity_pubkey")?.unwrap_or(sled::IVec::from(b""));
-                            let identity_pubkey_str = String::from_utf8_lossy(&identity_pubkey).to_string();
 
-                            println!("destination_pubkey: {}", destination_pubkey);
-                            println!("identity_pubkey: {}", identity_pubkey_str);
+                            eprintln!("destination_pubkey: {}", destination_pubkey);
+                            eprintln!("identity_pubkey: {}", identity_pubkey_str);
 
                             let is_own_invoice = destination_pubkey == identity_pubkey_str;
 
@@ -107,18 +301,18 @@ pub fn create_invoice(preimage_x: String, preimage_h: String, amount: String, me
                                 r_hash: preimage_h.to_string(),
                                 is_own_invoice,
                             };
-                            let serialized_invoice_data = bincode::serialize(&invoice_data_to_save)?;
-                            db.insert(preimage_h.as_bytes(), serialized_invoice_data)?;
+                            invoices.insert(&preimage_h, &invoice_data_to_save)?;
+                            db.flush()?;
                             
                             Ok(InvoiceOutput {
                                 payment_addr: payment_addr.to_string(),
                                 payment_request: payment_request.to_string(),
                                 destination_pubkey: destination_pubkey.to_string(),
-                                identity_pubkey: identity_pubkey_str.to_string(),
+                                identity_pubkey: identity_pubkey_str,
                             })
                         }
                         Err(e) => {
-                            println!("Failed to parse JSON response: {}", e);
+                            eprintln!("Failed to parse JSON response: {}", e);
                             Err(anyhow!("Failed to parse JSON response: {}", e))
                         }
                     }
@@ -133,15 +327,15 @@ pub fn create_invoice(preimage_x: String, preimage_h: String, amount: String, me
     }
 }
 
-pub fn settle_invoice(preimage_x: String) -> Result<()> {
-    println!("Attempting to settle invoice using preimage_x: {}", preimage_x);
+pub fn settle_invoice(network: &str, preimage_x: String) -> Result<()> {
+    eprintln!("Attempting to settle invoice using preimage_x: {}", preimage_x);
 
-    let output = Command::new("/usr/local/bin/lncli")
-        .args(["--network", "testnet", "settleinvoice", &preimage_x])
+    let output = lncli(network)
+        .args(["settleinvoice", &preimage_x])
         .output()?;
 
     let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-    println!("{}", stderr);
+    eprintln!("{}", stderr);
 
     if !output.status.success() {
         return Err(anyhow!("Failed to settle invoice: {}", stderr));
@@ -155,27 +349,30 @@ pub fn copy_payment_request(payment_request: String) -> Result<()> {
         Ok(ctx) => ctx,
         Err(e) => {
             let err_msg = format!("Failed to initialize clipboard: {}", e);
-            println!("{}", err_msg);
+            eprintln!("{}", err_msg);
             return Err(anyhow!(err_msg));
         }
     };
 
     match ctx.set_contents(payment_request.clone()) {
         Ok(_) => {
-            println!("Copied to clipboard: {}", payment_request);
+            eprintln!("Copied to clipboard: {}", payment_request);
             Ok(())
         },
         Err(e) => {
             let err_msg = format!("Failed to copy to clipboard: {}", e);
-            println!("{}", err_msg); 
+            eprintln!("{}", err_msg); 
             Err(anyhow!(err_msg))
         }
     }
 }
 
-pub fn create_standard_invoice(amount: String, memo: String, db: &sled::Db) -> Result<String> {
-    let output = Command::new("/usr/local/bin/lncli")
-        .args(["--network", "testnet", "addinvoice", "--amt", &amount, "--memo", &memo])
+pub fn create_standard_invoice(network: &str, amount: Amount, memo: String, db: &sled::Db) -> Result<InvoiceOutput> {
+    let amount_msat = invoice_amount(amount)?;
+    let identity_pubkey_str = store::node_identity(db, network)?;
+    let invoices = store::invoices(db, network)?;
+    let output = lncli(network)
+        .args(["addinvoice", "--amt_msat", &amount_msat, "--memo", &memo])
         .output()?;
 
     let stdout = String::from_utf8_lossy(&output.stdout).to_string();
@@ -184,22 +381,20 @@ pub fn create_standard_invoice(amount: String, memo: String, db: &sled::Db) -> R
             if let Some(payment_addr) = json.get("payment_addr").and_then(|v| v.as_str()) {
                 if let Some(r_hash) = json.get("r_hash").and_then(|v| v.as_str()) {
                     if let Some(payment_request) = json.get("payment_request").and_then(|v| v.as_str()) {
-                        let invoice_output = Command::new("/usr/local/bin/lncli")
-                            .args(["--network", "testnet", "decodepayreq", payment_request])
+                        let invoice_output = lncli(network)
+                            .args(["decodepayreq", payment_request])
                             .output()?;
     
                         let invoice_stdout = String::from_utf8_lossy(&invoice_output.stdout).to_string();
                         let invoice_stderr = String::from_utf8_lossy(&invoice_output.stderr).to_string();
-                        println!("{}", invoice_stderr);
+                        eprintln!("{}", invoice_stderr);
                         
                         match serde_json::from_str::<serde_json::Value>(&invoice_stdout) {
                             Ok(json) => {
                                 let destination_pubkey = json.get("destination").and_then(|v| v.as_str()).unwrap_or("");
-                                let identity_pubkey = db.get(b"identity_pubkey")?.unwrap_or(sled::IVec::from(b""));
-                                let identity_pubkey_str = String::from_utf8_lossy(&identity_pubkey).to_string();
     
-                                println!("destination_pubkey: {}", destination_pubkey);
-                                println!("identity_pubkey: {}", identity_pubkey_str);
+                                eprintln!("destination_pubkey: {}", destination_pubkey);
+                                eprintln!("identity_pubkey: {}", identity_pubkey_str);
     
                                 let is_own_invoice = destination_pubkey == identity_pubkey_str;
     
@@ -210,12 +405,16 @@ pub fn create_standard_invoice(amount: String, memo: String, db: &sled::Db) -> R
                                     r_hash: r_hash.to_string(),
                                     is_own_invoice,
                                 };
-                                let serialized_invoice_data = bincode::serialize(&invoice_data_to_save)?;
-                                db.insert(r_hash.as_bytes(), serialized_invoice_data)?;
-                                Ok(payment_addr.to_string())
+                                invoices.insert(r_hash, &invoice_data_to_save)?;
+                                Ok(InvoiceOutput {
+                                    payment_addr: payment_addr.to_string(),
+                                    payment_request: payment_request.to_string(),
+                                    destination_pubkey: destination_pubkey.to_string(),
+                                    identity_pubkey: identity_pubkey_str,
+                                })
                             }
                             Err(e) => {
-                                println!("Failed to parse JSON response: {}", e);
+                                eprintln!("Failed to parse JSON response: {}", e);
                                 Err(anyhow!("Failed to parse JSON response: {}", e))
                             }
                         }
@@ -231,4 +430,154 @@ pub fn create_standard_invoice(amount: String, memo: String, db: &sled::Db) -> R
         }
         Err(e) => Err(anyhow!("Failed to parse JSON response: {}", e))
     }
-}
\ No newline at end of file
+}
+/// Cancels an open or accepted hold invoice, failing any HTLCs held for it back to the payer.
+pub fn cancel_invoice(network: &str, payment_hash: &str) -> Result<()> {
+    eprintln!("Cancelling invoice {}", payment_hash);
+    let output = lncli(network)
+        .args(["cancelinvoice", payment_hash])
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!("Failed to cancel invoice: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(())
+}
+
+/// The preimage we generated for a hold invoice, looked up by its payment hash.
+pub fn stored_preimage(network: &str, db: &sled::Db, payment_hash: &str) -> Result<String> {
+    let invoice_data = store::invoices(db, network)?.get(payment_hash)?
+        .ok_or_else(|| anyhow!("No stored invoice for payment hash {}", payment_hash))?;
+    if invoice_data.preimage_x.is_empty() {
+        return Err(anyhow!("Invoice {} was not created with a custom preimage", payment_hash));
+    }
+    Ok(invoice_data.preimage_x)
+}
+
+/// An invoice as lnd reads it, checked to be payable before anything is sent.
+pub struct DecodedInvoice {
+    pub destination: String,
+    pub payment_hash: String,
+    pub amount: Amount,
+    pub description: String,
+    pub expires_at: i64,
+}
+
+/// Decodes `payment_request` with `lncli decodepayreq`, which also rejects invoices for
+/// another network. Expired and amountless invoices are errors, `pay_invoice` can't pay them.
+pub fn decode_invoice(network: &str, payment_request: &str) -> Result<DecodedInvoice> {
+    let output = lncli(network).args(["decodepayreq", payment_request]).output()?;
+    if !output.status.success() {
+        return Err(anyhow!("lnd could not decode the invoice: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let decoded = DecodedInvoice {
+        destination: json["destination"].as_str().unwrap_or_default().to_string(),
+        payment_hash: json["payment_hash"].as_str().unwrap_or_default().to_string(),
+        amount: Amount::from_json_msat(&json["num_msat"]),
+        description: json["description"].as_str().unwrap_or_default().to_string(),
+        expires_at: (json_u64(&json["timestamp"]) + json_u64(&json["expiry"])) as i64,
+    };
+    if decoded.expires_at < chrono::Utc::now().timestamp() {
+        let expired = DateTime::from_timestamp(decoded.expires_at, 0).map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string());
+        return Err(anyhow!("The invoice expired on {}", expired.unwrap_or_default()));
+    }
+    if decoded.amount.is_zero() {
+        return Err(anyhow!("The invoice has no amount, ask the payee for one that does"));
+    }
+    Ok(decoded)
+}
+
+pub struct PaymentOutcome {
+    pub payment_hash: String,
+    pub status: String,
+    pub preimage: String,
+    pub amount: Amount,
+    pub fee: Amount,
+    pub failure_reason: String,
+}
+
+impl PaymentOutcome {
+    fn from_json(payment: &serde_json::Value) -> Self {
+        PaymentOutcome {
+            payment_hash: payment["payment_hash"].as_str().unwrap_or_default().to_string(),
+            status: payment["status"].as_str().unwrap_or("UNKNOWN").to_string(),
+            preimage: payment["payment_preimage"].as_str().unwrap_or_default().to_string(),
+            amount: Amount::from_json_msat(&payment["value_msat"]),
+            fee: Amount::from_json_msat(&payment["fee_msat"]),
+            failure_reason: payment["failure_reason"].as_str().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// What left the wallet, amount plus routing fee.
+    pub fn total(&self) -> Result<Amount> {
+        self.amount.checked_add(self.fee).ok_or_else(|| anyhow!("Payment total overflows"))
+    }
+}
+
+/// Pays a BOLT11 invoice and waits for the payment to succeed or fail. lncli takes the fee
+/// limit in whole sats, so a limit with msats is rounded down rather than loosened.
+pub fn pay_invoice(network: &str, payment_request: &str, fee_limit: Option<Amount>) -> Result<PaymentOutcome> {
+    eprintln!("Paying invoice {}", payment_request);
+    let mut command = lncli(network);
+    command.args(["payinvoice", "--pay_req", payment_request, "--force", "--json"]);
+    if let Some(fee_limit) = fee_limit {
+        command.args(["--fee_limit", &fee_limit.to_sat_floor().to_string()]);
+    }
+    let output = command.output()?;
+
+    // With --json lncli prints one JSON object per payment update, the last one is final
+    let final_update = serde_json::Deserializer::from_slice(&output.stdout)
+        .into_iter::<serde_json::Value>()
+        .filter_map(|update| update.ok())
+        .last()
+        .ok_or_else(|| anyhow!("Failed to pay invoice: {}", String::from_utf8_lossy(&output.stderr).trim()))?;
+
+    Ok(PaymentOutcome::from_json(&final_update))
+}
+
+/// Recent outgoing payments including in-flight and failed ones.
+pub fn list_payments(network: &str) -> Result<Vec<PaymentOutcome>> {
+    let output = lncli(network)
+        .args(["listpayments", "--include_incomplete", "--max_payments", "1000"])
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!("Failed to list payments: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    Ok(json["payments"].as_array()
+        .map(|payments| payments.iter().map(PaymentOutcome::from_json).collect())
+        .unwrap_or_default())
+}
+
+/// The latest state of an outgoing payment, None if the node has no payment with that hash.
+pub fn payment_status(network: &str, payment_hash: &str) -> Result<Option<PaymentOutcome>> {
+    Ok(list_payments(network)?.into_iter().find(|p| p.payment_hash == payment_hash))
+}
+
+pub struct InvoiceStatus {
+    pub payment_hash: String,
+    pub state: String,
+    pub value: Amount,
+    pub amount_paid: Amount,
+    pub memo: String,
+    pub payment_request: String,
+}
+
+/// Looks up one invoice by payment hash. The preimage is left out on purpose.
+pub fn lookup_invoice(network: &str, payment_hash: &str) -> Result<InvoiceStatus> {
+    let output = lncli(network)
+        .args(["lookupinvoice", payment_hash])
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!("Failed to look up invoice: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    Ok(InvoiceStatus {
+        payment_hash: payment_hash.to_string(),
+        state: json["state"].as_str().unwrap_or("UNKNOWN").to_string(),
+        value: Amount::from_json_msat(&json["value_msat"]),
+        amount_paid: Amount::from_json_msat(&json["amt_paid_msat"]),
+        memo: json["memo"].as_str().unwrap_or_default().to_string(),
+        payment_request: json["payment_request"].as_str().unwrap_or_default().to_string(),
+    })
+}
@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::amount::Amount;
+use crate::store;
+use crate::utils::{json_u64, lncli};
+
+// add_index (big endian) -> IndexedInvoice, so iteration runs oldest first
+pub const INDEX_TREE: &str = "invoice_index";
+pub const SYNC_META_TREE: &str = "invoice_sync";
+// add_index (big endian) of every OPEN or ACCEPTED invoice, the only ones that can still change
+pub const PENDING_TREE: &str = "invoice_pending";
+const LAST_ADD_INDEX_KEY: &[u8] = b"last_add_index";
+const PENDING_TRACKED_KEY: &[u8] = b"pending_tracked";
+const PAGE_SIZE: u64 = 1000;
+const PENDING_STATES: [&str; 2] = ["OPEN", "ACCEPTED"];
+
+/// Our copy of an lnd invoice, kept current by `sync`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IndexedInvoice {
+    pub add_index: u64,
+    pub settle_index: u64,
+    pub r_hash: String,
+    pub memo: String,
+    pub value: Amount,
+    pub amt_paid: Amount,
+    pub state: String,
+    pub creation_date: i64,
+    pub settle_date: i64,
+    pub payment_request: String,
+}
+
+impl IndexedInvoice {
+    fn from_json(invoice: &Value) -> Self {
+        IndexedInvoice {
+            add_index: json_u64(&invoice["add_index"]),
+            settle_index: json_u64(&invoice["settle_index"]),
+            r_hash: invoice["r_hash"].as_str().unwrap_or_default().to_string(),
+            memo: invoice["memo"].as_str().unwrap_or_default().to_string(),
+            value: Amount::from_json_msat(&invoice["value_msat"]),
+            amt_paid: Amount::from_json_msat(&invoice["amt_paid_msat"]),
+            state: invoice["state"].as_str().unwrap_or_default().to_string(),
+            creation_date: json_u64(&invoice["creation_date"]) as i64,
+            settle_date: json_u64(&invoice["settle_date"]) as i64,
+            payment_request: invoice["payment_request"].as_str().unwrap_or_default().to_string(),
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        PENDING_STATES.contains(&self.state.as_str())
+    }
+}
+
+/// What changed in the index during one `sync`.
+#[derive(Default)]
+pub struct SyncDelta {
+    pub added: Vec<IndexedInvoice>,
+    pub updated: Vec<IndexedInvoice>,
+}
+
+/// One page of `lncli listinvoices` from `offset`, and the offset of the next page.
+fn list_page(network: &str, offset: u64, pending_only: bool) -> Result<(Vec<IndexedInvoice>, u64)> {
+    let mut command = lncli(network);
+    command.args([
+        "listinvoices",
+        "--index_offset", &offset.to_string(),
+        "--max_invoices", &PAGE_SIZE.to_string(),
+        "--paginate-forwards",
+    ]);
+    if pending_only {
+        command.arg("--pending_only");
+    }
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(anyhow!("lncli listinvoices failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    let json: Value = serde_json::from_slice(&output.stdout)?;
+    let invoices = json["invoices"].as_array()
+        .map(|page| page.iter().map(IndexedInvoice::from_json).collect())
+        .unwrap_or_default();
+    Ok((invoices, json_u64(&json["last_index_offset"])))
+}
+
+fn lookup(network: &str, r_hash: &str) -> Result<IndexedInvoice> {
+    let output = lncli(network).args(["lookupinvoice", r_hash]).output()?;
+    if !output.status.success() {
+        return Err(anyhow!("lncli lookupinvoice failed: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(IndexedInvoice::from_json(&serde_json::from_slice(&output.stdout)?))
+}
+
+/// Brings the local index up to date with lnd and returns what changed.
+///
+/// New invoices are paged in from the highest add_index we have. Only OPEN and ACCEPTED
+/// invoices can still change, so those are refreshed from `--pending_only` and any that
+/// dropped out of it are looked up once to pick up their settle_index or cancellation.
+pub fn sync(network: &str, db: &sled::Db) -> Result<SyncDelta> {
+    let index = store::scoped_tree(db, network, INDEX_TREE)?;
+    let pending = store::scoped_tree(db, network, PENDING_TREE)?;
+    let meta = store::scoped_tree(db, network, SYNC_META_TREE)?;
+    let mut delta = SyncDelta::default();
+
+    if meta.get(PENDING_TRACKED_KEY)?.is_none() {
+        // Indexes written before the pending set was kept, read once to fill it
+        for invoice in all_from(&index)?.into_iter().filter(IndexedInvoice::is_pending) {
+            pending.insert(invoice.add_index.to_be_bytes(), &[])?;
+        }
+        meta.insert(PENDING_TRACKED_KEY, &[])?;
+    }
+
+    let mut offset = meta.get(LAST_ADD_INDEX_KEY)?
+        .and_then(|v| v.as_ref().try_into().ok().map(u64::from_be_bytes))
+        .unwrap_or(0);
+    loop {
+        let (page, next_offset) = list_page(network, offset, false)?;
+        if page.is_empty() {
+            break;
+        }
+        for invoice in page {
+            store_invoice(&index, &pending, &invoice)?;
+            delta.added.push(invoice);
+        }
+        if next_offset <= offset {
+            break;
+        }
+        offset = next_offset;
+        meta.insert(LAST_ADD_INDEX_KEY, &offset.to_be_bytes())?;
+    }
+
+    let mut still_pending = HashSet::new();
+    let mut pending_offset = 0;
+    loop {
+        let (page, next_offset) = list_page(network, pending_offset, true)?;
+        if page.is_empty() {
+            break;
+        }
+        for invoice in page {
+            still_pending.insert(invoice.add_index);
+            update(&index, &pending, invoice, &mut delta)?;
+        }
+        if next_offset <= pending_offset {
+            break;
+        }
+        pending_offset = next_offset;
+    }
+
+    let stale_keys = pending.iter().keys().collect::<Result<Vec<_>, _>>()?;
+    for key in stale_keys {
+        let add_index = key.as_ref().try_into().map(u64::from_be_bytes)
+            .map_err(|_| anyhow!("Invalid key in the pending invoice set"))?;
+        if still_pending.contains(&add_index) {
+            continue;
+        }
+        let Some(stale) = index.get(&key)?.map(|v| bincode::deserialize::<IndexedInvoice>(&v)).transpose()? else {
+            pending.remove(&key)?;
+            continue;
+        };
+        match lookup(network, &stale.r_hash) {
+            Ok(invoice) => update(&index, &pending, invoice, &mut delta)?,
+            Err(e) => eprintln!("Could not refresh invoice {}: {}", stale.r_hash, e),
+        }
+    }
+
+    if !delta.added.is_empty() || !delta.updated.is_empty() {
+        eprintln!("Invoice sync: {} new, {} changed", delta.added.len(), delta.updated.len());
+    }
+    Ok(delta)
+}
+
+/// Writes `invoice` to the index and keeps it in the pending set only while it can still change.
+fn store_invoice(index: &sled::Tree, pending: &sled::Tree, invoice: &IndexedInvoice) -> Result<()> {
+    let key = invoice.add_index.to_be_bytes();
+    index.insert(key, bincode::serialize(invoice)?)?;
+    if invoice.is_pending() {
+        pending.insert(key, &[])?;
+    } else {
+        pending.remove(key)?;
+    }
+    Ok(())
+}
+
+/// Stores `invoice` if it differs from our copy, recording it as updated unless it was added in this sync.
+fn update(index: &sled::Tree, pending: &sled::Tree, invoice: IndexedInvoice, delta: &mut SyncDelta) -> Result<()> {
+    let key = invoice.add_index.to_be_bytes();
+    let previous = index.get(key)?.and_then(|v| bincode::deserialize::<IndexedInvoice>(&v).ok());
+    if previous.as_ref() == Some(&invoice) {
+        return Ok(());
+    }
+    store_invoice(index, pending, &invoice)?;
+    if let Some(added) = delta.added.iter_mut().find(|i| i.add_index == invoice.add_index) {
+        *added = invoice;
+    } else {
+        delta.updated.retain(|i| i.add_index != invoice.add_index);
+        delta.updated.push(invoice);
+    }
+    Ok(())
+}
+
+fn all_from(index: &sled::Tree) -> Result<Vec<IndexedInvoice>> {
+    let mut invoices = Vec::new();
+    for entry in index.iter() {
+        let (_, value) = entry?;
+        invoices.push(bincode::deserialize::<IndexedInvoice>(&value)?);
+    }
+    Ok(invoices)
+}
+
+/// Every indexed invoice, oldest first. Call `sync` first for current data.
+pub fn all(network: &str, db: &sled::Db) -> Result<Vec<IndexedInvoice>> {
+    all_from(&store::scoped_tree(db, network, INDEX_TREE)?)
+}
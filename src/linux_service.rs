@@ -0,0 +1,178 @@
+use std::{path::PathBuf, process::Command};
+
+use anyhow::{Context, Result};
+
+use crate::litd_config;
+
+// One unit per network like the launchd agents, only the selected network's unit runs
+const UNIT_PREFIX: &str = "com.btc-";
+const UNIT_SUFFIX: &str = ".litd.service";
+// The single unit earlier versions rewrote for every network
+const LEGACY_UNIT_NAME: &str = "com.btc.litd.service";
+
+fn unit_name(network: &str) -> String {
+    format!("{}{}{}", UNIT_PREFIX, network, UNIT_SUFFIX)
+}
+
+fn systemctl(args: &[&str]) -> Result<std::process::Output> {
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .context(format!("Failed to execute systemctl --user {}", args.join(" ")))?;
+    if !output.status.success() {
+        eprintln!("systemctl --user {} failed", args.join(" "));
+        eprintln!("{}", String::from_utf8_lossy(&output.stdout));
+        eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(output)
+}
+
+/// Starts litd on `network`, leaving it alone if it already runs with the current unit.
+pub fn start_linux_service(network: &str) -> Result<()> {
+    let changed = install_unit(network)?;
+    // Only a changed unit needs a restart to apply
+    run_unit(if changed { "restart" } else { "start" }, network)
+}
+
+/// Restarts litd on `network`, e.g. to pick up a changed lit.conf.
+pub fn restart_linux_service(network: &str) -> Result<()> {
+    install_unit(network)?;
+    run_unit("restart", network)
+}
+
+fn run_unit(action: &str, network: &str) -> Result<()> {
+    let unit = unit_name(network);
+    let output = systemctl(&[action, &unit])?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("Failed to {} {}: {}", action, unit, String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(())
+}
+
+/// Writes and enables the unit for `network` and stops the other networks' units.
+/// Returns whether the unit changed.
+fn install_unit(network: &str) -> Result<bool> {
+    let unit = unit_name(network);
+    let status = linux_service_status(network)?;
+    println!("Service '{}' is {} before setup.", unit, status);
+
+    // Two litds would fight over the same ports
+    for other in installed_units()?.into_iter().filter(|u| *u != unit) {
+        println!("Stopping {} for the switch to {}", other, network);
+        let disable_output = systemctl(&["disable", "--now", &other])?;
+        if !disable_output.status.success() {
+            eprintln!("Warning: could not stop {}", other);
+        }
+        if other == LEGACY_UNIT_NAME {
+            std::fs::remove_file(unit_dir()?.join(&other)).context(format!("Failed to remove {}", other))?;
+        }
+    }
+
+    let changed = write_service(network)?;
+    if changed {
+        println!("Wrote systemd unit {}", unit);
+        let reload_output = systemctl(&["daemon-reload"])?;
+        if !reload_output.status.success() {
+            return Err(anyhow::anyhow!("Failed to reload systemd user units: {}", String::from_utf8_lossy(&reload_output.stderr)));
+        }
+    }
+
+    let enable_output = systemctl(&["enable", &unit])?;
+    if !enable_output.status.success() {
+        eprintln!("Warning: 'systemctl --user enable' exited with non-zero status. Stderr: {}", String::from_utf8_lossy(&enable_output.stderr));
+    }
+    Ok(changed)
+}
+
+/// Stops every litd unit the app installed, whichever network it is for.
+pub fn stop_linux_service() -> Result<()> {
+    for unit in installed_units()? {
+        let stop_output = systemctl(&["stop", &unit])?;
+        if !stop_output.status.success() {
+            // It's possible it was never started.
+            eprintln!("Warning: 'systemctl --user stop {}' exited with non-zero status", unit);
+        }
+    }
+    Ok(())
+}
+
+/// The unit's active state as systemd reports it, e.g. "active", "inactive" or "failed".
+pub fn linux_service_status(network: &str) -> Result<String> {
+    // is-active exits non-zero for anything but active, the state is still printed
+    let output = Command::new("systemctl")
+        .args(["--user", "is-active", &unit_name(network)])
+        .output()
+        .context("Failed to execute systemctl --user is-active")?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn unit_dir() -> Result<PathBuf> {
+    Ok(dirs::config_dir()
+        .ok_or_else(|| anyhow::anyhow!("Could not determine the user config directory"))?
+        .join("systemd")
+        .join("user"))
+}
+
+/// Names of the litd units the app wrote, for any network.
+fn installed_units() -> Result<Vec<String>> {
+    let entries = match std::fs::read_dir(unit_dir()?) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("Failed to list systemd user units"),
+    };
+    let mut units = Vec::new();
+    for entry in entries {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if name == LEGACY_UNIT_NAME || (name.starts_with(UNIT_PREFIX) && name.ends_with(UNIT_SUFFIX)) {
+            units.push(name);
+        }
+    }
+    Ok(units)
+}
+
+/// Quotes an ExecStart argument the way systemd splits them: double quotes around anything
+/// with whitespace or quotes, backslash escapes inside, and `%`/`$` doubled so they are not
+/// taken as specifiers or variables.
+fn systemd_quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%").replace('$', "$$");
+    if !escaped.is_empty() && !escaped.chars().any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';')) {
+        return escaped;
+    }
+    format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Writes the unit for `network`, returning whether its content changed.
+fn write_service(network: &str) -> Result<bool> {
+    let unit_dir = unit_dir()?;
+    std::fs::create_dir_all(&unit_dir).context(format!("Failed to create {:?}", unit_dir))?;
+    let unit_path = unit_dir.join(unit_name(network));
+
+    let mut exec_start = vec!["/usr/local/bin/litd".to_string()];
+    exec_start.extend(litd_config::litd_args(network));
+    let exec_start = exec_start.iter().map(|arg| systemd_quote(arg)).collect::<Vec<_>>().join(" ");
+    let content = format!(
+        r#"[Unit]
+Description=Lightning Terminal ({network})
+After=network-online.target
+
+[Service]
+ExecStart={exec_start}
+Restart=always
+RestartSec=5
+StandardOutput=append:/tmp/com.btc.litd.stdout.log
+StandardError=append:/tmp/com.btc.litd.stderr.log
+
+[Install]
+WantedBy=default.target
+"#
+    );
+
+    if std::fs::read_to_string(&unit_path).is_ok_and(|existing| existing == content) {
+        return Ok(false);
+    }
+    match std::fs::write(&unit_path, content) {
+        Ok(_) => Ok(true),
+        Err(e) => Err(anyhow::anyhow!("Failed to write service file: {}", e)),
+    }
+}
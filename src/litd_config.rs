@@ -0,0 +1,231 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::discovery;
+
+const DEFAULT_FEE_URL: &str = "https://nodes.lightning.computer/fees/v1/btc-fee-estimates.json";
+const MIN_UI_PASSWORD_LENGTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LndMode {
+    Integrated,
+    Remote,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainBackend {
+    Neutrino,
+    Bitcoind,
+    Btcd,
+}
+
+/// UI order of the combo boxes in the settings view.
+pub const LND_MODES: [&str; 2] = ["integrated", "remote"];
+pub const CHAIN_BACKENDS: [&str; 3] = ["neutrino", "bitcoind", "btcd"];
+
+/// The lit.conf settings the app manages. Any other option found in the file is kept as is.
+#[derive(Debug, Clone)]
+pub struct LitConfig {
+    pub https_listen: String,
+    pub ui_password: String,
+    pub lnd_mode: LndMode,
+    pub backend: ChainBackend,
+    pub fee_url: String,
+    pub scid_alias: bool,
+    pub zero_conf: bool,
+    pub remote_rpcserver: String,
+    pub other: Vec<(String, String)>,
+}
+
+impl Default for LitConfig {
+    fn default() -> Self {
+        Self {
+            https_listen: "0.0.0.0:8443".to_string(),
+            ui_password: String::new(),
+            lnd_mode: LndMode::Integrated,
+            backend: ChainBackend::Neutrino,
+            fee_url: DEFAULT_FEE_URL.to_string(),
+            scid_alias: true,
+            zero_conf: true,
+            remote_rpcserver: String::new(),
+            other: Vec::new(),
+        }
+    }
+}
+
+impl LndMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LndMode::Integrated => LND_MODES[0],
+            LndMode::Remote => LND_MODES[1],
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "integrated" => Ok(LndMode::Integrated),
+            "remote" => Ok(LndMode::Remote),
+            other => Err(anyhow!("Unknown lnd-mode: {}", other)),
+        }
+    }
+}
+
+impl ChainBackend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChainBackend::Neutrino => CHAIN_BACKENDS[0],
+            ChainBackend::Bitcoind => CHAIN_BACKENDS[1],
+            ChainBackend::Btcd => CHAIN_BACKENDS[2],
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "neutrino" => Ok(ChainBackend::Neutrino),
+            "bitcoind" => Ok(ChainBackend::Bitcoind),
+            "btcd" => Ok(ChainBackend::Btcd),
+            other => Err(anyhow!("Unknown chain backend: {}", other)),
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> bool {
+    matches!(value, "1" | "true")
+}
+
+impl LitConfig {
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut config = LitConfig {
+            ui_password: String::new(),
+            fee_url: String::new(),
+            scid_alias: false,
+            zero_conf: false,
+            ..LitConfig::default()
+        };
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else { continue };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "httpslisten" => config.https_listen = value.to_string(),
+                "uipassword" => config.ui_password = value.to_string(),
+                "lnd-mode" => config.lnd_mode = LndMode::parse(value)?,
+                "lnd.bitcoin.node" => config.backend = ChainBackend::parse(value)?,
+                "lnd.feeurl" => config.fee_url = value.to_string(),
+                "lnd.protocol.option-scid-alias" => config.scid_alias = parse_bool(value),
+                "lnd.protocol.zero-conf" => config.zero_conf = parse_bool(value),
+                "remote.lnd.rpcserver" => config.remote_rpcserver = value.to_string(),
+                // The network comes from the file's location and the service arguments
+                "network" | "lnd.bitcoin.active" | "lnd.bitcoin.mainnet" | "lnd.bitcoin.testnet"
+                | "lnd.bitcoin.regtest" | "lnd.bitcoin.signet" | "lnd.bitcoin.simnet" => {}
+                _ => config.other.push((key.to_string(), value.to_string())),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Rejects settings litd would refuse to start with or that contradict each other.
+    pub fn validate(&self, network: &str) -> Result<()> {
+        let (_, port) = self.https_listen.rsplit_once(':')
+            .ok_or_else(|| anyhow!("httpslisten must be host:port, got '{}'", self.https_listen))?;
+        port.parse::<u16>().map_err(|_| anyhow!("httpslisten has an invalid port: '{}'", port))?;
+
+        if self.ui_password.len() < MIN_UI_PASSWORD_LENGTH {
+            return Err(anyhow!("uipassword must be at least {} characters", MIN_UI_PASSWORD_LENGTH));
+        }
+        if self.zero_conf && !self.scid_alias {
+            return Err(anyhow!("zero-conf channels require option-scid-alias"));
+        }
+        match self.lnd_mode {
+            LndMode::Remote => {
+                if self.remote_rpcserver.trim().is_empty() {
+                    return Err(anyhow!("Remote mode needs the remote lnd RPC server (host:port)"));
+                }
+                if self.zero_conf || self.scid_alias {
+                    return Err(anyhow!("scid-alias and zero-conf are lnd options, in remote mode set them on the remote lnd"));
+                }
+            }
+            LndMode::Integrated => {
+                if !self.remote_rpcserver.trim().is_empty() {
+                    return Err(anyhow!("A remote lnd RPC server is only used in remote mode"));
+                }
+                // Neutrino can't estimate fees on mainnet by itself
+                if self.backend == ChainBackend::Neutrino && network == "mainnet" && self.fee_url.trim().is_empty() {
+                    return Err(anyhow!("A fee URL is required for neutrino on mainnet"));
+                }
+            }
+        }
+        if self.other.iter().any(|(key, _)| key.starts_with("lnd.")) && self.lnd_mode == LndMode::Remote {
+            return Err(anyhow!("lnd.* options in the file are ignored in remote mode, remove them first"));
+        }
+        Ok(())
+    }
+
+    pub fn render(&self, network: &str) -> String {
+        let mut lines = vec![
+            format!("network={}", network),
+            format!("httpslisten={}", self.https_listen),
+            format!("uipassword={}", self.ui_password),
+            format!("lnd-mode={}", self.lnd_mode.as_str()),
+        ];
+        match self.lnd_mode {
+            LndMode::Integrated => {
+                lines.push(format!("lnd.bitcoin.node={}", self.backend.as_str()));
+                if !self.fee_url.trim().is_empty() {
+                    lines.push(format!("lnd.feeurl={}", self.fee_url.trim()));
+                }
+                lines.push(format!("lnd.protocol.option-scid-alias={}", self.scid_alias));
+                lines.push(format!("lnd.protocol.zero-conf={}", self.zero_conf));
+            }
+            LndMode::Remote => lines.push(format!("remote.lnd.rpcserver={}", self.remote_rpcserver.trim())),
+        }
+        for (key, value) in &self.other {
+            lines.push(format!("{}={}", key, value));
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+/// Where the config for `network` lives, next to litd's per-network data.
+pub fn config_path(network: &str) -> PathBuf {
+    discovery::app_data_dir("lit").join(network).join("lit.conf")
+}
+
+/// The saved config for `network`, or the defaults when nothing was written yet.
+pub fn load(network: &str) -> Result<LitConfig> {
+    let path = config_path(network);
+    if !path.exists() {
+        return Ok(LitConfig::default());
+    }
+    let content = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    LitConfig::parse(&content)
+}
+
+pub fn save(network: &str, config: &LitConfig) -> Result<PathBuf> {
+    config.validate(network)?;
+    let path = config_path(network);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    std::fs::write(&path, config.render(network)).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("Wrote lit.conf for {} to {}", network, path.display());
+    Ok(path)
+}
+
+/// Arguments every way of running litd passes: the network and, once written, its config file.
+pub fn litd_args(network: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    if network != "mainnet" {
+        args.push("--network".to_string());
+        args.push(network.to_string());
+    }
+    let path = config_path(network);
+    if path.exists() {
+        args.push(format!("--configfile={}", path.display()));
+    }
+    args
+}
@@ -1,29 +1,42 @@
-use std::{path::PathBuf, process::Command};
+use std::process::Command;
 use anyhow::{Result, Context};
 
+use crate::linux_service::{linux_service_status, restart_linux_service, start_linux_service, stop_linux_service};
 use crate::{mac_service::start_mac_service, windows_service::start_windows_service};
+use crate::store;
 
-pub fn start_litd_service(network: &str) -> Result<()> {
-    // Determine the os type
+fn os_type() -> Result<String> {
     let os_type = Command::new("uname")
         .arg("-s")
         .output()
         .context("Failed to execute uname command")?;
     let os_type = String::from_utf8_lossy(&os_type.stdout).trim().to_string();
-    println!("OS type: {}", os_type);
+    eprintln!("OS type: {}", os_type);
+    Ok(os_type)
+}
+
+pub fn start_litd_service(network: &str) -> Result<()> {
+    // Determine the os type
+    let os_type = os_type()?;
 
     if os_type == "Darwin" {
         start_mac_service(network)?;
     } else if os_type == "Windows" {
         start_windows_service(network)?;
+    } else if os_type == "Linux" {
+        start_linux_service(network)?;
     } else {
-        return Err(anyhow::anyhow!("This script is only supported on macOS and Windows"));
+        return Err(anyhow::anyhow!("This script is only supported on macOS, Linux and Windows"));
     }
 
     Ok(())
 }
 
 pub fn stop_litd_service() -> Result<()> {
+    if os_type()? == "Linux" {
+        return stop_linux_service();
+    }
+
     let unload_output = Command::new("launchctl")
         .arg("remove")
         .arg("com.btc.litd")
@@ -39,10 +52,43 @@ pub fn stop_litd_service() -> Result<()> {
     Ok(())
 }
 
+/// Restarts litd on `network`. systemd restarts in place, launchd needs a remove and a fresh load.
+pub fn restart_litd_service(network: &str) -> Result<()> {
+    if os_type()? == "Linux" {
+        // The unit is rewritten first in case the network changed
+        return restart_linux_service(network);
+    }
+    stop_litd_service()?;
+    std::thread::sleep(std::time::Duration::from_secs(3));
+    start_litd_service(network)
+}
+
+/// A short description of the service state for the UI, and whether litd is up.
+pub fn litd_service_status(network: &str) -> Result<(String, bool)> {
+    let os_type = os_type()?;
+    if os_type == "Linux" {
+        let state = linux_service_status(network)?;
+        let running = state == "active";
+        return Ok((format!("systemd user unit {}", state), running));
+    }
+    if os_type == "Darwin" {
+        let list_output = Command::new("launchctl")
+            .args(["list", "com.btc.litd"])
+            .output()
+            .context("Failed to execute launchctl list")?;
+        let loaded = list_output.status.success();
+        return Ok((if loaded { "launchd agent loaded" } else { "launchd agent not loaded" }.to_string(), loaded));
+    }
+    Ok(("not managed by the app".to_string(), false))
+}
+
 pub async fn get_network(db: &sled::Db) -> Result<String> {
-    let network = db.get(b"network")?.unwrap_or(sled::IVec::from(b"testnet"));
-    let network_str = String::from_utf8(network.to_vec()).unwrap_or_else(|_| "testnet".to_string());
-    Ok(network_str)
+    Ok(current_network(db))
+}
+
+/// The network selected in the app, for callers outside the async runtime.
+pub fn current_network(db: &sled::Db) -> String {
+    store::get_setting(db, store::NETWORK_KEY).ok().flatten().unwrap_or_else(|| "testnet".to_string())
 }
 
 pub async fn send_custom_message(message: String, identity_pubkey: String) -> Result<()> {
@@ -1,9 +1,9 @@
-use std::{env, path::PathBuf, process::Command};
+use std::{path::PathBuf, process::Command};
 
 use anyhow::{Context, Result};
-use directories::{ProjectDirs, UserDirs};
+use directories::UserDirs;
 
-use crate::get_app_data_dir;
+use crate::litd_config;
 
 pub fn start_mac_service(network: &str) -> Result<()> {
     let service_name = format!("com.btc-{}.litd", network);
@@ -11,7 +11,7 @@ pub fn start_mac_service(network: &str) -> Result<()> {
     // Using sh -c to correctly interpret pipes
     let check_output = Command::new("sh")
         .arg("-c")
-        .arg(&format!("launchctl list | grep {}", service_name))
+        .arg(format!("launchctl list | grep {}", service_name))
         .output()
         .context("Failed to execute launchctl list command")?;
 
@@ -64,34 +64,20 @@ pub fn start_mac_service(network: &str) -> Result<()> {
     Ok(())
 }
 
+/// Escapes text for a plist `<string>`, a path with `&` or `<` would otherwise break the file.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
 fn write_service(network: &str) -> Result<PathBuf>{
     let launch_agents_dir = UserDirs::new().unwrap().home_dir().join("Library").join("LaunchAgents");
     let service_file_name = format!("com.btc-{}.litd.plist", network);
     let plist_path = launch_agents_dir.join(service_file_name);
-    let content = if network == "mainnet" { 
-        r#"
-        <?xml version="1.0" encoding="UTF-8"?>
-        <!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
-        <plist version="1.0">
-        <dict>
-            <key>Label</key>
-            <string>com.btc.litd</string>
-            <key>ProgramArguments</key>
-            <array>
-                <string>/usr/local/bin/litd</string>
-            </array>
-            <key>RunAtLoad</key>
-            <true/>
-            <key>KeepAlive</key>
-            <true/>
-            <key>StandardOutPath</key>
-            <string>/tmp/com.btc.litd.stdout.log</string>
-            <key>StandardErrorPath</key>
-            <string>/tmp/com.btc.litd.stderr.log</string>
-        </dict>
-        </plist>
-        "#
-    } else {
+    let program_arguments: String = std::iter::once("/usr/local/bin/litd".to_string())
+        .chain(litd_config::litd_args(network))
+        .map(|arg| format!("                <string>{}</string>\n", xml_escape(&arg)))
+        .collect();
+    let content = format!(
         r#"
         <?xml version="1.0" encoding="UTF-8"?>
         <!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
@@ -101,10 +87,7 @@ fn write_service(network: &str) -> Result<PathBuf>{
             <string>com.btc.litd</string>
             <key>ProgramArguments</key>
             <array>
-                <string>/usr/local/bin/litd</string>
-                <string>--network</string>
-                <string>testnet</string>
-            </array>
+{program_arguments}            </array>
             <key>RunAtLoad</key>
             <true/>
             <key>KeepAlive</key>
@@ -116,7 +99,7 @@ fn write_service(network: &str) -> Result<PathBuf>{
         </dict>
         </plist>
         "#
-    };
+    );
 
     match std::fs::write(&plist_path, content) {
         Ok(_) => Ok(plist_path),
@@ -3,84 +3,133 @@ mod types;
 mod utils;
 mod node;
 mod channels;
+mod channel_scoring;
+mod rebalance;
 mod litd_service;
 mod unlock_wallet;
 mod mac_service;
 mod windows_service;
+mod fee_policy;
+mod forwarding;
+mod wallet;
+mod discovery;
+mod linux_service;
+mod supervisor;
+mod litd_config;
+mod node_log;
+mod headless;
+mod http_api;
+mod webhooks;
+mod export;
+mod store;
+mod invoice_index;
+mod amount;
+mod qr;
+mod payment_uri;
 
 use anyhow::Result;
 use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use slint::{Model, ModelRc, SharedString, VecModel};
 use unlock_wallet::unlock_wallet_rpc;
 use std::path::PathBuf;
-use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
 use tokio::time::{interval, Duration};
-use chrono::{format, DateTime, NaiveDateTime, Utc};
 
 use sha2::Digest;
 
-use types::AppState;
+use amount::Amount;
 use utils::generate_preimage;
 use node::{node_status, NodeInfo};
-use channels::{ActiveChannelInfo, PendingChannelInfo};
 
 slint::include_modules!();
 
-// Structs for deserializing lncli listinvoices output
-#[derive(Deserialize, Debug, Clone)]
-struct LnCliInvoice {
-    memo: String,
-    r_hash: String,
-    value: String, // Value is often a string in lncli output
-    state: String, // e.g., "OPEN", "SETTLED"
-    creation_date: String, // Unix timestamp string
-    payment_request: String,
-    // Add other fields if needed, like amt_paid_sat, is_keysend etc.
-}
-
-#[derive(Deserialize, Debug)]
-struct ListInvoicesResponse {
-    invoices: Vec<LnCliInvoice>,
-    // last_index_offset, first_index_offset if you need pagination
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct InvoiceData {
-    preimage_x: String,
-    preimage_h: String,
-    payment_address: String,
-    r_hash: String,
-    is_own_invoice: bool,
-}
+const DEFAULT_CHANNEL_AMOUNT: Amount = Amount::from_msat(20_000_000);
+const CHANNEL_CANDIDATE_LIMIT: usize = 20;
+const FEE_PROFILE_INTERVAL_SECS: u64 = 600;
+const FORWARDING_CHART_DAYS: i64 = 30;
+const SEED_VERIFY_WORDS: usize = 3;
+const LOG_VIEW_LIMIT: usize = 500;
+const LOG_HASH_CONTEXT: usize = 5;
+const WEBHOOK_POLL_INTERVAL_SECS: u64 = 15;
+const WEBHOOK_LOG_ROWS: usize = 50;
+const EXPORT_DEFAULT_DAYS: i64 = 30;
+const INVOICE_REFRESH_INTERVAL_SECS: u64 = 10;
+const INVOICE_PAGE_SIZE: usize = 50;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let app_data_dir = get_app_data_dir().unwrap();
+    let args: Vec<String> = std::env::args().collect();
+    let headless_position = args.iter().position(|a| a == "--headless");
     // Initialize sled database
     let db = match sled::open(app_data_dir.join("invoice_data_db")) {
         Ok(db) => db,
+        // sled holds an exclusive lock, so this is what a running app looks like
+        Err(e) if headless_position.is_some() => {
+            return Err(anyhow::anyhow!(
+                "Could not open the app database ({}). If the app is running, close it first or use its HTTP API (--http-api) instead.",
+                e
+            ));
+        }
         Err(e) => {
             eprintln!("CRITICAL: Failed to open sled database 'invoice_data_db': {}. Please check permissions and disk space.", e);
             panic!("Failed to open database: {}", e);
         }
     };
+    store::migrate(&db)?;
 
-    let initial_network_db = db.clone();
-    let initial_network = initial_network_db.get(b"network")?.unwrap_or(sled::IVec::from(b"testnet"));
-    let initial_network_str = String::from_utf8(initial_network.to_vec()).unwrap_or_else(|_| "testnet".to_string());
+    let initial_network_str = litd_service::current_network(&db);
+
+    // --headless runs a single command against an already running node and exits
+    if let Some(position) = headless_position {
+        return headless::run(&db, &args[position + 1..]);
+    }
+
+    // A litd left running by an earlier session answers right away, otherwise the node
+    // status poll stores the identity once the node is up
+    if let Err(e) = node::resolve_identity(&db, &initial_network_str) {
+        eprintln!("Node identity on {} not known yet: {}", initial_network_str, e);
+    }
+
+    // --http-api serves hold invoices to local services, see http_api::serve for the routes
+    if args.iter().any(|a| a == "--http-api") {
+        let port = args.iter().position(|a| a == "--http-api-port")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(http_api::DEFAULT_PORT);
+        let api_db = db.clone();
+        tokio::spawn(async move {
+            if let Err(e) = http_api::serve(api_db, port).await {
+                println!("HTTP API stopped: {:#}", e);
+            }
+        });
+    }
+
+    // --supervise-litd runs litd as our own child process instead of a launchd/systemd service,
+    // anything after `--` is passed on to litd
+    let supervisor = args.iter().any(|a| a == "--supervise-litd").then(|| {
+        let litd_args = args.iter().skip_while(|a| *a != "--").skip(1).cloned().collect();
+        supervisor::LitdSupervisor::new(litd_args)
+    });
 
     let initial_network_str_clone = initial_network_str.clone();
     // Start litd service
-    match litd_service::start_litd_service(&initial_network_str_clone) {
+    let start_result = match &supervisor {
+        Some(supervisor) => supervisor.start(&initial_network_str_clone),
+        None => litd_service::start_litd_service(&initial_network_str_clone),
+    };
+    match start_result {
         Ok(_) => {
             // Create channel for node status updates
             let (tx_node_status, mut rx_node_status) = mpsc::channel(5);
             let window = MainWindow::new().map_err(|e| anyhow::anyhow!("Failed to create main window: {}", e))?;
             let window_weak = Arc::new(window.as_weak());
+            window.set_litd_started_by_app(supervisor.is_some());
+            let display_unit = amount::display_unit(&db);
+            window.set_display_unit(display_unit.index());
+            window.set_amount_unit(display_unit.as_str().into());
 
             let node_db = db.clone();
             let node_update_window_clone = window_weak.clone();
@@ -91,7 +140,7 @@ async fn main() -> Result<()> {
                     interval.tick().await;
                     let node_network = litd_service::get_network(&node_db).await.unwrap_or_else(|_| "testnet".to_string());
                     let info = node_status(&node_network, &node_update_window_clone).await;
-                    if let Err(_) = tx_node_status.send(info).await {
+                    if tx_node_status.send(info).await.is_err() {
                         break; 
                     }
                 }
@@ -102,6 +151,7 @@ async fn main() -> Result<()> {
             let initial_node_info = node_status(&initial_network_str_clone, &initial_node_window_clone).await;
 
             let node_db_clone = db.clone();
+            show_connection_report(&window_weak, &initial_network_str);
             update_ui_with_node_info(&window_weak, initial_node_info.clone(), &node_db_clone);
             if !initial_node_info.running {
                 window.set_wallet_needs_unlock(true);
@@ -115,51 +165,241 @@ async fn main() -> Result<()> {
                 }
             });
 
+            // Follow the litd logs in the background, the viewer is redrawn while it's open
+            let log_update_window_weak = window_weak.clone();
+            let log_tail = match node_log::LogTail::start(move || {
+                let window_weak = log_update_window_weak.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(window) = window_weak.upgrade() {
+                        if window.get_active_page() == 12 {
+                            window.invoke_refresh_node_logs();
+                        }
+                    }
+                });
+            }) {
+                Ok(tail) => Some(Arc::new(tail)),
+                Err(e) => {
+                    println!("Failed to start following the litd logs: {}", e);
+                    None
+                }
+            };
+            let log_filter = Arc::new(Mutex::new(node_log::LogFilter::default()));
+            // Set while the viewer shows the lines around a payment hash instead of the filter
+            let log_payment_hash = Arc::new(Mutex::new(String::new()));
+
+            let logs_window_weak = window_weak.clone();
+            let logs_tail = log_tail.clone();
+            let logs_filter = log_filter.clone();
+            let logs_payment_hash = log_payment_hash.clone();
+            window.on_refresh_node_logs(move || {
+                let Some(window) = logs_window_weak.upgrade() else { return };
+                let Some(tail) = &logs_tail else {
+                    window.set_log_status("The litd log files could not be watched".into());
+                    return;
+                };
+                let payment_hash = logs_payment_hash.lock().unwrap().clone();
+                let (lines, status) = if payment_hash.is_empty() {
+                    let lines = tail.filtered(&logs_filter.lock().unwrap(), LOG_VIEW_LIMIT);
+                    let status = format!("Showing the last {} matching lines", lines.len());
+                    (lines, status)
+                } else {
+                    let lines = tail.around_payment_hash(&payment_hash, LOG_HASH_CONTEXT);
+                    let status = if lines.is_empty() {
+                        format!("No log lines mention {}", payment_hash)
+                    } else {
+                        format!("Lines mentioning {} with {} lines of context", payment_hash, LOG_HASH_CONTEXT)
+                    };
+                    (lines, status)
+                };
+                let entries: Vec<LogEntry> = lines.into_iter().map(|line| LogEntry {
+                    timestamp: line.timestamp.into(),
+                    level: line.level.into(),
+                    subsystem: line.subsystem.into(),
+                    message: line.message.into(),
+                    source: line.source.into(),
+                }).collect();
+                window.set_log_entries(ModelRc::new(VecModel::from(entries)));
+                window.set_log_status(status.into());
+            });
+
+            let filter_window_weak = window_weak.clone();
+            let filter_log_filter = log_filter.clone();
+            let filter_payment_hash = log_payment_hash.clone();
+            window.on_apply_log_filter(move |level_index, subsystem, text| {
+                *filter_log_filter.lock().unwrap() = node_log::LogFilter {
+                    // Index 0 of the combo box is "All levels"
+                    min_level: (level_index > 0).then(|| level_index as usize - 1),
+                    subsystem: subsystem.trim().to_string(),
+                    text: text.trim().to_string(),
+                };
+                filter_payment_hash.lock().unwrap().clear();
+                if let Some(window) = filter_window_weak.upgrade() {
+                    window.invoke_refresh_node_logs();
+                }
+            });
+
+            let hash_window_weak = window_weak.clone();
+            let hash_payment_hash = log_payment_hash.clone();
+            window.on_find_log_payment_hash(move |payment_hash| {
+                *hash_payment_hash.lock().unwrap() = payment_hash.trim().to_string();
+                if let Some(window) = hash_window_weak.upgrade() {
+                    window.invoke_refresh_node_logs();
+                }
+            });
+
             let network_window_weak = window_weak.clone();
             let network_db = db.clone();
+            let network_supervisor = supervisor.clone();
             window.on_toggle_network(move |network: SharedString| {
                 println!("Toggling network: {}", network);
                 let network_str = network.to_string();
                 let task_arc_weak_clone = network_window_weak.clone();
                 let network_db_clone = network_db.clone();
+                let network_supervisor_clone = network_supervisor.clone();
 
                 tokio::spawn(async move {
-                    match litd_service::stop_litd_service() {
+                    let restart_result = match &network_supervisor_clone {
+                        Some(supervisor) => supervisor.restart(&network_str),
+                        None => litd_service::restart_litd_service(&network_str),
+                    };
+                    match restart_result {
                         Ok(_) => {
-                            println!("Litd service stopped successfully");
-                            tokio::time::sleep(Duration::from_secs(3)).await;
-                            
-                            match litd_service::start_litd_service(&network_str) {
-                                Ok(_) => {
-                                    println!("Litd service started successfully");
-                                    
-                                    network_db_clone.insert(b"network", network_str.as_bytes());
-                                    let _ = slint::invoke_from_event_loop(move || {
-                                        if let Some(window) = task_arc_weak_clone.upgrade() {
-                                            window.set_is_mainnet(network_str == "mainnet");
-                                        }
-                                    });
-                                }
-                                Err(e) => {
-                                    println!("Failed to start litd service: {}", e);
-                                }
+                            println!("Litd service restarted successfully");
+
+                            let _ = store::set_setting(&network_db_clone, store::NETWORK_KEY, &network_str);
+                            // litd may still be starting, the node status poll stores it once it answers
+                            if let Err(e) = node::resolve_identity(&network_db_clone, &network_str) {
+                                eprintln!("Node identity on {} not known yet: {}", network_str, e);
                             }
+                            show_connection_report(&task_arc_weak_clone, &network_str);
+                            let _ = slint::invoke_from_event_loop(move || {
+                                if let Some(window) = task_arc_weak_clone.upgrade() {
+                                    window.set_is_mainnet(network_str == "mainnet");
+                                }
+                            });
                         }
                         Err(e) => {
-                            println!("Failed to stop litd service: {}", e);
+                            println!("Failed to restart litd service: {}", e);
                         }
                     }
                 });
             });
 
+            let settings_window_weak = window_weak.clone();
+            let settings_db = db.clone();
+            window.on_show_litd_settings(move || {
+                let window_weak = settings_window_weak.clone();
+                let db = settings_db.clone();
+                tokio::spawn(async move {
+                    let network = litd_service::get_network(&db).await.unwrap_or_else(|_| "testnet".to_string());
+                    let config_path = litd_config::config_path(&network);
+                    let (config, status) = match litd_config::load(&network) {
+                        Ok(config) => (config, String::new()),
+                        Err(e) => (litd_config::LitConfig::default(), format!("Could not read the existing lit.conf, showing defaults: {}", e)),
+                    };
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = window_weak.upgrade() {
+                            let modes: Vec<SharedString> = litd_config::LND_MODES.iter().map(|m| (*m).into()).collect();
+                            let backends: Vec<SharedString> = litd_config::CHAIN_BACKENDS.iter().map(|b| (*b).into()).collect();
+                            window.set_litd_lnd_modes(ModelRc::new(VecModel::from(modes)));
+                            window.set_litd_backends(ModelRc::new(VecModel::from(backends)));
+                            window.set_litd_config_path(format!("{} ({})", config_path.display(), network).into());
+                            window.set_litd_https_listen(config.https_listen.into());
+                            window.set_litd_ui_password(config.ui_password.into());
+                            window.set_litd_lnd_mode_index(litd_config::LND_MODES.iter().position(|m| *m == config.lnd_mode.as_str()).unwrap_or(0) as i32);
+                            window.set_litd_backend_index(litd_config::CHAIN_BACKENDS.iter().position(|b| *b == config.backend.as_str()).unwrap_or(0) as i32);
+                            window.set_litd_fee_url(config.fee_url.into());
+                            window.set_litd_scid_alias(config.scid_alias);
+                            window.set_litd_zero_conf(config.zero_conf);
+                            window.set_litd_remote_rpcserver(config.remote_rpcserver.into());
+                            window.set_litd_settings_status(status.into());
+                            window.set_litd_restart_offered(false);
+                            window.set_litd_settings_in_progress(false);
+                        }
+                    });
+                });
+            });
+
+            let save_settings_window_weak = window_weak.clone();
+            let save_settings_db = db.clone();
+            window.on_save_litd_settings(move |https_listen, ui_password, mode_index, backend_index, fee_url, scid_alias, zero_conf, remote_rpcserver| {
+                let window_weak = save_settings_window_weak.clone();
+                let db = save_settings_db.clone();
+                tokio::spawn(async move {
+                    let network = litd_service::get_network(&db).await.unwrap_or_else(|_| "testnet".to_string());
+                    let result = (|| {
+                        let config = litd_config::LitConfig {
+                            https_listen: https_listen.trim().to_string(),
+                            ui_password: ui_password.to_string(),
+                            lnd_mode: litd_config::LndMode::parse(litd_config::LND_MODES.get(mode_index as usize).unwrap_or(&""))?,
+                            backend: litd_config::ChainBackend::parse(litd_config::CHAIN_BACKENDS.get(backend_index as usize).unwrap_or(&""))?,
+                            fee_url: fee_url.to_string(),
+                            scid_alias,
+                            zero_conf,
+                            remote_rpcserver: remote_rpcserver.to_string(),
+                            // Options the editor doesn't cover are written back untouched
+                            other: litd_config::load(&network).map(|c| c.other).unwrap_or_default(),
+                        };
+                        litd_config::save(&network, &config)
+                    })();
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = window_weak.upgrade() {
+                            match result {
+                                Ok(path) => {
+                                    window.set_litd_settings_status(format!("Saved {}. Restart litd to apply the changes.", path.display()).into());
+                                    window.set_litd_restart_offered(true);
+                                }
+                                Err(e) => window.set_litd_settings_status(format!("Not saved: {}", e).into()),
+                            }
+                            window.set_litd_settings_in_progress(false);
+                        }
+                    });
+                });
+            });
+
+            let restart_window_weak = window_weak.clone();
+            let restart_db = db.clone();
+            let restart_supervisor = supervisor.clone();
+            window.on_restart_litd(move || {
+                let window_weak = restart_window_weak.clone();
+                let db = restart_db.clone();
+                let supervisor = restart_supervisor.clone();
+                tokio::spawn(async move {
+                    let network = litd_service::get_network(&db).await.unwrap_or_else(|_| "testnet".to_string());
+                    // Rewrites the service definition too, so a config file saved for the first time is picked up
+                    let restart_result = match &supervisor {
+                        Some(supervisor) => supervisor.restart(&network),
+                        None => litd_service::restart_litd_service(&network),
+                    };
+                    let status = match restart_result {
+                        Ok(_) => {
+                            show_connection_report(&window_weak, &network);
+                            "litd restarted with the new settings".to_string()
+                        }
+                        Err(e) => format!("Failed to restart litd: {}", e),
+                    };
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = window_weak.upgrade() {
+                            window.set_litd_settings_status(status.into());
+                            window.set_litd_restart_offered(false);
+                            window.set_litd_settings_in_progress(false);
+                        }
+                    });
+                });
+            });
+
             let wallet_window_weak = window_weak.clone();
+            let wallet_db = db.clone();
             window.on_unlock_wallet(move |password: SharedString| {
                 println!("Unlocking wallet with password: {}", password);
                 let password_str = password.to_string();
                 let task_arc_weak_clone = wallet_window_weak.clone(); // Clone Arc for the tokio task
+                let wallet_db_clone = wallet_db.clone();
 
                 tokio::spawn(async move { // task_arc_weak_clone is moved into this async block
-                    match unlock_wallet_rpc(&password_str).await {
+                    let wallet_network = litd_service::get_network(&wallet_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let connection = discovery::discover(&wallet_network);
+                    match unlock_wallet_rpc(&connection, &password_str).await {
                         Ok(_) => {
                             println!("Wallet unlocked successfully");
                             // Clone the Arc again for the invoke_from_event_loop closure
@@ -190,6 +430,102 @@ async fn main() -> Result<()> {
                 });
             });
 
+            let pending_seed: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+
+            let seed_window_weak = window_weak.clone();
+            let seed_db = db.clone();
+            let seed_store = pending_seed.clone();
+            window.on_generate_wallet_seed(move |passphrase| {
+                let task_arc_weak_clone = seed_window_weak.clone();
+                let seed_db_clone = seed_db.clone();
+                let seed_store_clone = seed_store.clone();
+
+                tokio::spawn(async move {
+                    let seed_network = litd_service::get_network(&seed_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let result = unlock_wallet::generate_seed(&discovery::discover(&seed_network), &passphrase).await;
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = task_arc_weak_clone.upgrade() {
+                            match result {
+                                Ok(words) => {
+                                    let mut indices = rand::seq::index::sample(&mut rand::thread_rng(), words.len(), SEED_VERIFY_WORDS).into_vec();
+                                    indices.sort();
+                                    let seed_words: Vec<SharedString> = words.iter().map(SharedString::from).collect();
+                                    *seed_store_clone.lock().unwrap() = words;
+                                    window.set_wallet_seed_words(ModelRc::new(VecModel::from(seed_words)));
+                                    window.set_wallet_seed_verify_indices(ModelRc::new(VecModel::from(
+                                        indices.into_iter().map(|i| i as i32).collect::<Vec<i32>>(),
+                                    )));
+                                    window.set_wallet_setup_status("".into());
+                                    window.set_wallet_setup_step(2);
+                                }
+                                Err(e) => window.set_wallet_setup_status(format!("Failed to generate seed: {}", e).into()),
+                            }
+                            window.set_wallet_setup_in_progress(false);
+                        }
+                    });
+                });
+            });
+
+            let create_wallet_window_weak = window_weak.clone();
+            let create_wallet_db = db.clone();
+            let create_wallet_seed = pending_seed.clone();
+            window.on_create_wallet(move |password, passphrase, answers| {
+                let task_arc_weak_clone = create_wallet_window_weak.clone();
+                let seed = create_wallet_seed.lock().unwrap().clone();
+                let indices: Vec<usize> = task_arc_weak_clone.upgrade()
+                    .map(|window| window.get_wallet_seed_verify_indices().iter().map(|i| i as usize).collect())
+                    .unwrap_or_default();
+
+                // Every requested word has to match before the wallet is created from this seed
+                if indices.is_empty() || answers.row_count() != indices.len() {
+                    if let Some(window) = task_arc_weak_clone.upgrade() {
+                        window.set_wallet_setup_status("Enter every requested word of your seed.".into());
+                        window.set_wallet_setup_in_progress(false);
+                    }
+                    return;
+                }
+                let mismatch = indices.iter().zip(answers.iter())
+                    .find(|(i, answer)| seed.get(**i).map(|word| word.as_str()) != Some(answer.trim().to_lowercase().as_str()));
+                if let Some((i, _)) = mismatch {
+                    if let Some(window) = task_arc_weak_clone.upgrade() {
+                        window.set_wallet_setup_status(format!("Word {} does not match your seed, check your backup.", i + 1).into());
+                        window.set_wallet_setup_in_progress(false);
+                    }
+                    return;
+                }
+
+                let seed_store_clone = create_wallet_seed.clone();
+                let create_wallet_db_clone = create_wallet_db.clone();
+                tokio::spawn(async move {
+                    let wallet_network = litd_service::get_network(&create_wallet_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let connection = discovery::discover(&wallet_network);
+                    let result = unlock_wallet::init_wallet(&connection, &password, &seed, &passphrase, unlock_wallet::WalletInit::Create).await;
+                    if result.is_ok() {
+                        seed_store_clone.lock().unwrap().clear();
+                    }
+                    report_wallet_init(&task_arc_weak_clone, result, "Wallet created");
+                });
+            });
+
+            let restore_wallet_window_weak = window_weak.clone();
+            let restore_wallet_db = db.clone();
+            window.on_restore_wallet(move |mnemonic, passphrase, password, backup_path| {
+                let task_arc_weak_clone = restore_wallet_window_weak.clone();
+                let restore_wallet_db_clone = restore_wallet_db.clone();
+                let words: Vec<String> = mnemonic.split_whitespace().map(|w| w.to_lowercase()).collect();
+
+                tokio::spawn(async move {
+                    let wallet_network = litd_service::get_network(&restore_wallet_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let connection = discovery::discover(&wallet_network);
+                    let backup_path = backup_path.trim();
+                    let init = unlock_wallet::WalletInit::Restore {
+                        channel_backup_path: (!backup_path.is_empty()).then(|| std::path::Path::new(backup_path)),
+                    };
+                    let result = unlock_wallet::init_wallet(&connection, &password, &words, &passphrase, init).await;
+                    report_wallet_init(&task_arc_weak_clone, result, "Wallet restored, the node is rescanning the chain for funds");
+                });
+            });
+
             // Handle Manage Channels click
             let window_weak_clone = window_weak.clone();
             let channels_db = db.clone();
@@ -203,6 +539,7 @@ async fn main() -> Result<()> {
 
                     let active_channels_result = channels::list_active_channels(&channels_network);
                     let pending_channels_result = channels::list_pending_channels(&channels_network);
+                    let unit = amount::display_unit(&channels_db_clone);
 
                     // Now, schedule the UI update on the Slint event loop
                     let _ = slint::invoke_from_event_loop(move || {
@@ -211,14 +548,7 @@ async fn main() -> Result<()> {
                         if let Some(window_on_event_loop) = ui_handle_weak.upgrade() {
                             match active_channels_result {
                                 Ok(active_list) => {
-                                    let slint_active_channels: Vec<Channel> = active_list.into_iter().map(|ac| Channel {
-                                        channel_id: ac.channel_id.into(),
-                                        remote_pubkey: ac.remote_pubkey.into(),
-                                        capacity: ac.capacity.into(),
-                                        local_balance: ac.local_balance.into(),
-                                        remote_balance: ac.remote_balance.into(),
-                                        active: ac.active,
-                                    }).collect();
+                                    let slint_active_channels: Vec<Channel> = active_list.into_iter().map(|ac| channel_row(ac, unit)).collect();
                                     window_on_event_loop.set_channels(ModelRc::new(VecModel::from(slint_active_channels)));
                                     window_on_event_loop.set_status_message("Active channels loaded.".into());
                                 }
@@ -233,9 +563,9 @@ async fn main() -> Result<()> {
                                     let slint_pending_channels: Vec<PendingChannel> = pending_list.into_iter().map(|pc| PendingChannel {
                                         remote_pubkey: pc.remote_node_pub.into(),
                                         channel_point: pc.channel_point.into(),
-                                        capacity: pc.capacity.into(),
-                                        local_balance: pc.local_balance.into(),
-                                        remote_balance: pc.remote_balance.into(),
+                                        capacity: pc.capacity.format(unit).into(),
+                                        local_balance: pc.local_balance.format(unit).into(),
+                                        remote_balance: pc.remote_balance.format(unit).into(),
                                         status: pc.status.into(),
                                     }).collect();
                                     window_on_event_loop.set_pending_channels(ModelRc::new(VecModel::from(slint_pending_channels)));
@@ -256,97 +586,730 @@ async fn main() -> Result<()> {
                 });
             });
 
+            let channel_candidates: Arc<Mutex<Vec<channel_scoring::ChannelCandidate>>> = Arc::new(Mutex::new(Vec::new()));
+
             let open_channel_weak_ref = window_weak.clone(); // Clone Arc<Weak> for the callback
             let channel_db = db.clone();
+            let open_channel_candidates = channel_candidates.clone();
             window.on_open_lightning_channel(move || { // This callback runs on the Slint thread
                 println!("Auto Open Channel button clicked");
-                
+
                 // We don't upgrade `ui` here to pass into tokio::spawn.
                 // Instead, clone the weak reference again for the tokio task.
-                let task_weak_ref = open_channel_weak_ref.clone(); 
+                let task_weak_ref = open_channel_weak_ref.clone();
                 let channel_db_clone = channel_db.clone();
-                
+                let candidates_store = open_channel_candidates.clone();
+
                 tokio::spawn(async move { // task_weak_ref (Arc<Weak<MainWindow>>) is moved here. This is Send + Sync.
-                    const DEFAULT_CHANNEL_AMOUNT: u32 = 20000;
+                    let channel_network = litd_service::get_network(&channel_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+
+                    // --- Stage 1: Score candidates from the graph (Async/Blocking work) ---
+                    match channel_scoring::rank_candidates(&channel_network, CHANNEL_CANDIDATE_LIMIT) {
+                        Ok(candidates) => {
+                            *candidates_store.lock().unwrap() = candidates.clone();
+                            show_channel_candidates(&task_weak_ref, &candidates);
+
+                            // --- Stage 2: Update UI - Best candidate (on Slint thread) ---
+                            if let Some(best) = candidates.first() {
+                                let status_msg_best = format!("Best candidate: {} (score {:.1}). Attempting to open channel for {}...", best.pub_key, best.score, DEFAULT_CHANNEL_AMOUNT.format(amount::display_unit(&channel_db_clone)));
+                                let weak_for_status_update = task_weak_ref.clone();
+                                slint::invoke_from_event_loop(move || {
+                                    if let Some(ui) = weak_for_status_update.upgrade() {
+                                        ui.set_create_channel_status_message(status_msg_best.into());
+                                    }
+                                }).ok(); // .ok() to ignore error if UI is already closed
+                            }
+
+                            // --- Stage 3: Open Channel with the best reachable candidate ---
+                            let open_channel_result = channels::auto_open_channel(&channel_network, &candidates, DEFAULT_CHANNEL_AMOUNT);
+                            report_channel_open(&task_weak_ref, open_channel_result);
+                        }
+                        Err(e) => {
+                            report_channel_open(&task_weak_ref, Err(anyhow::anyhow!("Failed to score channel candidates: {}", e)));
+                        }
+                    }
+                });
+            });
+
+            let score_weak_ref = window_weak.clone();
+            let score_db = db.clone();
+            let score_candidates = channel_candidates.clone();
+            window.on_score_channel_candidates(move || {
+                let task_weak_ref = score_weak_ref.clone();
+                let score_db_clone = score_db.clone();
+                let candidates_store = score_candidates.clone();
+
+                tokio::spawn(async move {
+                    let score_network = litd_service::get_network(&score_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let message = match channel_scoring::rank_candidates(&score_network, CHANNEL_CANDIDATE_LIMIT) {
+                        Ok(candidates) => {
+                            *candidates_store.lock().unwrap() = candidates.clone();
+                            show_channel_candidates(&task_weak_ref, &candidates);
+                            format!("Scored {} channel candidates.", candidates.len())
+                        }
+                        Err(e) => format!("Failed to score channel candidates: {}", e),
+                    };
+                    slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = task_weak_ref.upgrade() {
+                            ui.set_create_channel_status_message(message.into());
+                            ui.set_create_channel_in_progress(false);
+                        }
+                    }).ok();
+                });
+            });
+
+            let open_candidate_weak_ref = window_weak.clone();
+            let open_candidate_db = db.clone();
+            let open_candidate_candidates = channel_candidates.clone();
+            window.on_open_candidate_channel(move |pub_key| {
+                let task_weak_ref = open_candidate_weak_ref.clone();
+                let open_candidate_db_clone = open_candidate_db.clone();
+                let candidate = open_candidate_candidates.lock().unwrap().iter().find(|c| c.pub_key == pub_key.as_str()).cloned();
+
+                tokio::spawn(async move {
+                    let candidate_network = litd_service::get_network(&open_candidate_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let result = match candidate {
+                        Some(candidate) => channels::open_channel_with_candidate(&candidate_network, &candidate, DEFAULT_CHANNEL_AMOUNT)
+                            .map(|output| (candidate.pub_key.clone(), output)),
+                        None => Err(anyhow::anyhow!("Candidate {} is no longer in the list, score candidates again", pub_key)),
+                    };
+                    report_channel_open(&task_weak_ref, result);
+                });
+            });
+
+            let psbt_open: Arc<Mutex<Option<channels::PsbtChannelOpen>>> = Arc::new(Mutex::new(None));
+
+            let start_psbt_weak_ref = window_weak.clone();
+            let start_psbt_db = db.clone();
+            let start_psbt_open = psbt_open.clone();
+            window.on_start_psbt_channel(move |peer, amount| {
+                let task_weak_ref = start_psbt_weak_ref.clone();
+                let start_psbt_db_clone = start_psbt_db.clone();
+                let psbt_store = start_psbt_open.clone();
+
+                tokio::spawn(async move {
+                    let psbt_network = litd_service::get_network(&start_psbt_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let result = (|| {
+                        let amount = Amount::parse_positive(&amount, amount::display_unit(&start_psbt_db_clone))?;
+                        let (pub_key, address) = match peer.trim().split_once('@') {
+                            Some((pub_key, address)) => (pub_key.to_string(), Some(address.to_string())),
+                            None => (peer.trim().to_string(), None),
+                        };
+                        let candidate = channel_scoring::ChannelCandidate {
+                            pub_key,
+                            alias: String::new(),
+                            score: 0.0,
+                            address,
+                            explanation: Vec::new(),
+                        };
+                        channels::connect_candidate(&psbt_network, &candidate)?;
+                        channels::start_psbt_open(&psbt_network, &candidate.pub_key, amount, &utils::export_dir())
+                    })();
+
+                    let (details, message) = match result {
+                        Ok(open) => {
+                            let details = format!(
+                                "Send exactly {} to {}. The funding PSBT was saved to {}",
+                                open.amount.format(amount::display_unit(&start_psbt_db_clone)), open.funding_address, open.psbt_path.display()
+                            );
+                            *psbt_store.lock().unwrap() = Some(open);
+                            (details, "Waiting for the signed PSBT.".to_string())
+                        }
+                        Err(e) => (String::new(), format!("Failed to start PSBT channel open: {}", e)),
+                    };
+                    slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = task_weak_ref.upgrade() {
+                            ui.set_psbt_channel_details(details.into());
+                            ui.set_create_channel_status_message(message.into());
+                            ui.set_create_channel_in_progress(false);
+                        }
+                    }).ok();
+                });
+            });
+
+            let finalize_psbt_weak_ref = window_weak.clone();
+            let finalize_psbt_open = psbt_open.clone();
+            window.on_finalize_psbt_channel(move |signed_path| {
+                let task_weak_ref = finalize_psbt_weak_ref.clone();
+                let psbt_store = finalize_psbt_open.clone();
+
+                tokio::spawn(async move {
+                    // A bad file keeps the open alive so another file can be tried
+                    let signed_psbt = match channels::read_signed_psbt(std::path::Path::new(signed_path.trim())) {
+                        Ok(signed_psbt) => signed_psbt,
+                        Err(e) => {
+                            slint::invoke_from_event_loop(move || {
+                                if let Some(ui) = task_weak_ref.upgrade() {
+                                    ui.set_create_channel_status_message(e.to_string().into());
+                                    ui.set_create_channel_in_progress(false);
+                                }
+                            }).ok();
+                            return;
+                        }
+                    };
+
+                    let open = psbt_store.lock().unwrap().take();
+                    let result = match open {
+                        Some(open) => {
+                            let pub_key = open.pub_key.clone();
+                            channels::finalize_psbt_open(open, &signed_psbt).map(|output| (pub_key, output))
+                        }
+                        None => Err(anyhow::anyhow!("No PSBT channel open is pending, start a new one")),
+                    };
+                    report_channel_open(&task_weak_ref, result);
+
+                    slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = task_weak_ref.upgrade() {
+                            ui.set_psbt_channel_details("".into());
+                        }
+                    }).ok();
+                });
+            });
+
+            let cancel_psbt_weak_ref = window_weak.clone();
+            let cancel_psbt_open = psbt_open.clone();
+            window.on_cancel_psbt_channel(move || {
+                // Dropping the pending open stops lncli, which aborts the funding flow
+                cancel_psbt_open.lock().unwrap().take();
+                if let Some(ui) = cancel_psbt_weak_ref.upgrade() {
+                    ui.set_psbt_channel_details("".into());
+                    ui.set_create_channel_status_message("PSBT channel open cancelled.".into());
+                }
+            });
+
+            let rebalance_window_weak = window_weak.clone();
+            let rebalance_db = db.clone();
+            window.on_show_rebalance(move || {
+                let ui_handle_weak = rebalance_window_weak.clone();
+                let rebalance_db_clone = rebalance_db.clone();
+
+                tokio::spawn(async move {
+                    let rebalance_network = litd_service::get_network(&rebalance_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let active_channels_result = channels::list_active_channels(&rebalance_network);
+                    let unit = amount::display_unit(&rebalance_db_clone);
+
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = ui_handle_weak.upgrade() {
+                            match active_channels_result {
+                                Ok(active_list) => {
+                                    let active_list: Vec<_> = active_list.into_iter().filter(|c| c.active).collect();
+                                    let labels: Vec<SharedString> = active_list.iter().map(|c| SharedString::from(format!(
+                                        "{} (local {} / remote {})", c.channel_id, c.local_balance.format(unit), c.remote_balance.format(unit)
+                                    ))).collect();
+                                    let slint_channels: Vec<Channel> = active_list.into_iter().map(|ac| channel_row(ac, unit)).collect();
+                                    window.set_rebalance_channel_labels(ModelRc::new(VecModel::from(labels)));
+                                    window.set_rebalance_channels(ModelRc::new(VecModel::from(slint_channels)));
+                                }
+                                Err(e) => {
+                                    println!("Error listing active channels: {}", e);
+                                    window.set_status_message(format!("Error loading active channels: {}", e).into());
+                                }
+                            }
+                            window.set_active_page(6i32);
+                        }
+                    });
+                });
+            });
+
+            let start_rebalance_window_weak = window_weak.clone();
+            let start_rebalance_db = db.clone();
+            window.on_start_rebalance(move |outgoing, incoming, amount, max_fee| {
+                let task_weak_ref = start_rebalance_window_weak.clone();
+                let start_rebalance_db_clone = start_rebalance_db.clone();
+
+                let unit = amount::display_unit(&start_rebalance_db_clone);
+                let parsed = Amount::parse_positive(&amount, unit)
+                    .and_then(|amount| Amount::parse(&max_fee, unit).map(|max_fee| (amount, max_fee)));
+                let (amount, max_fee) = match parsed {
+                    Ok(amounts) => amounts,
+                    Err(e) => {
+                        if let Some(window) = task_weak_ref.upgrade() {
+                            window.set_rebalance_succeeded(false);
+                            window.set_rebalance_progress_message(format!("Invalid amount: {}", e).into());
+                            window.set_rebalance_in_progress(false);
+                        }
+                        return;
+                    }
+                };
+
+                tokio::spawn(async move {
+                    let rebalance_network = litd_service::get_network(&start_rebalance_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let progress_weak_ref = task_weak_ref.clone();
+                    let (outgoing_chan, incoming_chan) = (outgoing.clone(), incoming.clone());
+                    // payinvoice can block for minutes while lnd tries routes
+                    let result = tokio::task::spawn_blocking(move || {
+                        rebalance::rebalance(&rebalance_network, &outgoing_chan, &incoming_chan, amount, max_fee, move |message| {
+                            println!("Rebalance: {}", message);
+                            let progress_weak_ref = progress_weak_ref.clone();
+                            slint::invoke_from_event_loop(move || {
+                                if let Some(ui) = progress_weak_ref.upgrade() {
+                                    ui.set_rebalance_progress_message(message.into());
+                                }
+                            }).ok();
+                        })
+                    })
+                    .await
+                    .unwrap_or_else(|e| Err(anyhow::anyhow!("Rebalance task failed: {}", e)));
+
+                    let (succeeded, message) = match result {
+                        Ok(outcome) => (true, format!(
+                            "Rebalanced {} from {} to {} for {} in fees (payment hash {}).",
+                            amount, outgoing, incoming, outcome.fee, outcome.payment_hash
+                        )),
+                        Err(e) => (false, format!("Rebalance failed: {}", e)),
+                    };
+                    slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = task_weak_ref.upgrade() {
+                            ui.set_rebalance_succeeded(succeeded);
+                            ui.set_rebalance_progress_message(message.clone().into());
+                            ui.set_rebalance_in_progress(false);
+                            ui.set_status_message(message.into());
+                        }
+                    }).ok();
+                });
+            });
+
+            let fee_window_weak = window_weak.clone();
+            let fee_db = db.clone();
+            window.on_show_fee_policies(move || {
+                let ui_handle_weak = fee_window_weak.clone();
+                let fee_db_clone = fee_db.clone();
+
+                tokio::spawn(async move {
+                    let fee_network = litd_service::get_network(&fee_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let active_channels_result = channels::list_active_channels(&fee_network);
+                    let profile_entries = fee_profile_entries(&fee_db_clone);
+                    let unit = amount::display_unit(&fee_db_clone);
+
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = ui_handle_weak.upgrade() {
+                            let mut labels = vec![SharedString::from("All channels")];
+                            let mut ids = vec![SharedString::from("")];
+                            match active_channels_result {
+                                Ok(active_list) => {
+                                    for c in active_list {
+                                        labels.push(format!("{} (local {} / remote {})", c.channel_id, c.local_balance.format(unit), c.remote_balance.format(unit)).into());
+                                        ids.push(c.channel_id.into());
+                                    }
+                                    window.set_fee_policy_status("Select a channel to load its current policy from the graph.".into());
+                                }
+                                Err(e) => {
+                                    println!("Error listing active channels: {}", e);
+                                    window.set_fee_policy_status(format!("Error loading active channels: {}", e).into());
+                                }
+                            }
+                            window.set_fee_channel_labels(ModelRc::new(VecModel::from(labels)));
+                            window.set_fee_channel_ids(ModelRc::new(VecModel::from(ids)));
+                            window.set_fee_profiles(ModelRc::new(VecModel::from(profile_entries)));
+                            window.set_active_page(7i32);
+                        }
+                    });
+                });
+            });
+
+            let load_fee_window_weak = window_weak.clone();
+            let load_fee_db = db.clone();
+            window.on_load_fee_policy(move |chan_id| {
+                let ui_handle_weak = load_fee_window_weak.clone();
+                let load_fee_db_clone = load_fee_db.clone();
+                if chan_id.is_empty() {
+                    if let Some(window) = ui_handle_weak.upgrade() {
+                        window.set_fee_policy_status("The policy will be applied to all channels.".into());
+                    }
+                    return;
+                }
+
+                tokio::spawn(async move {
+                    let fee_network = litd_service::get_network(&load_fee_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let own_pubkey = stored_identity_pubkey(&load_fee_db_clone, &fee_network);
+                    let result = fee_policy::current_policy(&fee_network, &chan_id, &own_pubkey);
+
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = ui_handle_weak.upgrade() {
+                            match result {
+                                Ok((chan_point, policy)) => {
+                                    window.set_fee_base_fee_msat(policy.base_fee_msat.to_string().into());
+                                    window.set_fee_rate_ppm(policy.fee_rate_ppm.to_string().into());
+                                    window.set_fee_time_lock_delta(policy.time_lock_delta.to_string().into());
+                                    window.set_fee_min_htlc_msat(policy.min_htlc_msat.to_string().into());
+                                    window.set_fee_max_htlc_msat(policy.max_htlc_msat.to_string().into());
+                                    window.set_fee_policy_status(format!("Current policy of {} ({}).", chan_id, chan_point).into());
+                                }
+                                Err(e) => {
+                                    window.set_fee_policy_status(format!("Error loading policy: {}", e).into());
+                                }
+                            }
+                        }
+                    });
+                });
+            });
+
+            let apply_fee_window_weak = window_weak.clone();
+            let apply_fee_db = db.clone();
+            window.on_apply_fee_policy(move |chan_id, base_fee, fee_rate, time_lock_delta, min_htlc, max_htlc| {
+                let ui_handle_weak = apply_fee_window_weak.clone();
+                let apply_fee_db_clone = apply_fee_db.clone();
+
+                let parsed: Result<Vec<u64>, _> = [&base_fee, &fee_rate, &time_lock_delta, &min_htlc, &max_htlc]
+                    .iter()
+                    .map(|v| v.trim().parse::<u64>())
+                    .collect();
+                let policy = match parsed.as_deref() {
+                    Ok([base_fee_msat, fee_rate_ppm, time_lock_delta, min_htlc_msat, max_htlc_msat]) => fee_policy::FeePolicy {
+                        base_fee_msat: *base_fee_msat,
+                        fee_rate_ppm: *fee_rate_ppm,
+                        time_lock_delta: *time_lock_delta,
+                        min_htlc_msat: *min_htlc_msat,
+                        max_htlc_msat: *max_htlc_msat,
+                    },
+                    _ => {
+                        if let Some(window) = ui_handle_weak.upgrade() {
+                            window.set_fee_policy_status("All policy fields must be whole numbers.".into());
+                        }
+                        return;
+                    }
+                };
+
+                tokio::spawn(async move {
+                    let fee_network = litd_service::get_network(&apply_fee_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let result = if chan_id.is_empty() {
+                        fee_policy::update_policy(&fee_network, None, &policy)
+                    } else {
+                        let own_pubkey = stored_identity_pubkey(&apply_fee_db_clone, &fee_network);
+                        fee_policy::current_policy(&fee_network, &chan_id, &own_pubkey)
+                            .and_then(|(chan_point, _)| fee_policy::update_policy(&fee_network, Some(&chan_point), &policy))
+                    };
+                    let message = match result {
+                        Ok(_) if chan_id.is_empty() => "Policy applied to all channels.".to_string(),
+                        Ok(_) => format!("Policy applied to channel {}.", chan_id),
+                        Err(e) => format!("Error applying policy: {}", e),
+                    };
+
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = ui_handle_weak.upgrade() {
+                            window.set_fee_policy_status(message.into());
+                        }
+                    });
+                });
+            });
+
+            let save_profile_window_weak = window_weak.clone();
+            let save_profile_db = db.clone();
+            window.on_save_fee_profile(move |name, chan_id, threshold, low_base, low_ppm, normal_base, normal_ppm| {
+                if let Some(window) = save_profile_window_weak.upgrade() {
+                    let parsed: Result<Vec<u64>, _> = [&threshold, &low_base, &low_ppm, &normal_base, &normal_ppm]
+                        .iter()
+                        .map(|v| v.trim().parse::<u64>())
+                        .collect();
+                    let result = match parsed.as_deref() {
+                        Ok([threshold_pct, low_base_fee_msat, low_fee_rate_ppm, normal_base_fee_msat, normal_fee_rate_ppm]) => {
+                            fee_policy::save_profile(&litd_service::current_network(&save_profile_db), &save_profile_db, &fee_policy::FeeProfile {
+                                name: name.to_string(),
+                                chan_id: chan_id.to_string(),
+                                threshold_pct: *threshold_pct,
+                                low_base_fee_msat: *low_base_fee_msat,
+                                low_fee_rate_ppm: *low_fee_rate_ppm,
+                                normal_base_fee_msat: *normal_base_fee_msat,
+                                normal_fee_rate_ppm: *normal_fee_rate_ppm,
+                            })
+                        }
+                        _ => Err(anyhow::anyhow!("Threshold and fees must be whole numbers")),
+                    };
+                    match result {
+                        Ok(_) => window.set_fee_policy_status(format!("Saved profile {}.", name).into()),
+                        Err(e) => window.set_fee_policy_status(format!("Error saving profile: {}", e).into()),
+                    }
+                    window.set_fee_profiles(ModelRc::new(VecModel::from(fee_profile_entries(&save_profile_db))));
+                }
+            });
+
+            let delete_profile_window_weak = window_weak.clone();
+            let delete_profile_db = db.clone();
+            window.on_delete_fee_profile(move |name| {
+                if let Some(window) = delete_profile_window_weak.upgrade() {
+                    match fee_policy::delete_profile(&litd_service::current_network(&delete_profile_db), &delete_profile_db, &name) {
+                        Ok(_) => window.set_fee_policy_status(format!("Deleted profile {}.", name).into()),
+                        Err(e) => window.set_fee_policy_status(format!("Error deleting profile: {}", e).into()),
+                    }
+                    window.set_fee_profiles(ModelRc::new(VecModel::from(fee_profile_entries(&delete_profile_db))));
+                }
+            });
+
+            let profile_window_weak = window_weak.clone();
+            let profile_db = db.clone();
+            // Re-evaluate liquidity profiles in intervals
+            tokio::spawn(async move {
+                let mut interval = interval(Duration::from_secs(FEE_PROFILE_INTERVAL_SECS));
+                loop {
+                    interval.tick().await;
+                    let profile_network = litd_service::get_network(&profile_db).await.unwrap_or_else(|_| "testnet".to_string());
+                    let own_pubkey = stored_identity_pubkey(&profile_db, &profile_network);
+                    match fee_policy::apply_profiles(&profile_network, &profile_db, &own_pubkey) {
+                        Ok(changes) if !changes.is_empty() => {
+                            let message = format!("Fee profiles updated {} channel(s): {}", changes.len(), changes.join("; "));
+                            println!("{}", message);
+                            let window_weak_clone = profile_window_weak.clone();
+                            let _ = slint::invoke_from_event_loop(move || {
+                                if let Some(window) = window_weak_clone.upgrade() {
+                                    window.set_status_message(message.into());
+                                }
+                            });
+                        }
+                        Ok(_) => {}
+                        Err(e) => println!("Error applying fee profiles: {}", e),
+                    }
+                }
+            });
+
+            let webhooks_window_weak = window_weak.clone();
+            let webhooks_db = db.clone();
+            window.on_show_webhooks(move || {
+                show_webhooks(&webhooks_window_weak, &webhooks_db, String::new());
+            });
+
+            let add_webhook_window_weak = window_weak.clone();
+            let add_webhook_db = db.clone();
+            window.on_add_webhook(move |url, secret| {
+                let status = match webhooks::add_webhook(&add_webhook_db, &url, &secret) {
+                    Ok(webhook) => format!("Added {}. Use Copy Secret to verify its signatures.", webhook.url),
+                    Err(e) => format!("Error adding webhook: {}", e),
+                };
+                show_webhooks(&add_webhook_window_weak, &add_webhook_db, status);
+            });
+
+            let remove_webhook_window_weak = window_weak.clone();
+            let remove_webhook_db = db.clone();
+            window.on_remove_webhook(move |url| {
+                let status = match webhooks::remove_webhook(&remove_webhook_db, &url) {
+                    Ok(_) => format!("Removed {}.", url),
+                    Err(e) => format!("Error removing webhook: {}", e),
+                };
+                show_webhooks(&remove_webhook_window_weak, &remove_webhook_db, status);
+            });
+
+            let webhook_poll_window_weak = window_weak.clone();
+            let webhook_poll_db = db.clone();
+            // Watch invoices and payments for state changes and deliver the resulting events
+            tokio::spawn(async move {
+                let mut interval = interval(Duration::from_secs(WEBHOOK_POLL_INTERVAL_SECS));
+                loop {
+                    interval.tick().await;
+                    let webhook_network = litd_service::get_network(&webhook_poll_db).await.unwrap_or_else(|_| "testnet".to_string());
+                    if let Err(e) = webhooks::poll(&webhook_network, &webhook_poll_db) {
+                        println!("Error polling for webhook events: {}", e);
+                    }
+                    if let Err(e) = webhooks::deliver_due(&webhook_poll_db).await {
+                        println!("Error delivering webhooks: {}", e);
+                    }
+                    let window_weak_clone = webhook_poll_window_weak.clone();
+                    let db_clone = webhook_poll_db.clone();
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if window_weak_clone.upgrade().is_some_and(|window| window.get_active_page() == 13) {
+                            show_webhooks(&window_weak_clone, &db_clone, String::new());
+                        }
+                    });
+                }
+            });
+
+            let show_export_window_weak = window_weak.clone();
+            window.on_show_export(move || {
+                if let Some(window) = show_export_window_weak.upgrade() {
+                    // Default to the last month, keeping whatever range was typed before
+                    if window.get_export_from_date().is_empty() {
+                        let today = chrono::Utc::now().date_naive();
+                        let from = today - chrono::Duration::days(EXPORT_DEFAULT_DAYS);
+                        window.set_export_from_date(from.to_string().into());
+                        window.set_export_to_date(today.to_string().into());
+                    }
+                    window.set_export_status("".into());
+                }
+            });
+
+            let export_window_weak = window_weak.clone();
+            let export_db = db.clone();
+            window.on_export_records(move |from, to, invoices, payments, forwards, onchain, format_index| {
+                let window_weak = export_window_weak.clone();
+                let db = export_db.clone();
+                tokio::spawn(async move {
+                    let network = litd_service::get_network(&db).await.unwrap_or_else(|_| "testnet".to_string());
+                    let types: Vec<export::RecordType> = [
+                        (invoices, export::RecordType::Invoice),
+                        (payments, export::RecordType::Payment),
+                        (forwards, export::RecordType::Forward),
+                        (onchain, export::RecordType::OnChain),
+                    ].into_iter().filter(|(selected, _)| *selected).map(|(_, record_type)| record_type).collect();
+                    let result = export::parse_date(&from).and_then(|from| Ok((from, export::parse_date(&to)?)))
+                        .and_then(|(from, to)| export::export(
+                            &network, &db, &types, from, to, export::ExportFormat::from_index(format_index), &utils::export_dir(),
+                        ));
+                    let status = match result {
+                        Ok((path, count)) => format!("Exported {} records to {}", count, path.display()),
+                        Err(e) => format!("Export failed: {}", e),
+                    };
+                    println!("{}", status);
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = window_weak.upgrade() {
+                            window.set_export_status(status.into());
+                            window.set_export_in_progress(false);
+                        }
+                    });
+                });
+            });
+
+            let dashboard_window_weak = window_weak.clone();
+            let dashboard_db = db.clone();
+            window.on_refresh_node_dashboard(move || {
+                let ui_handle_weak = dashboard_window_weak.clone();
+                let dashboard_db_clone = dashboard_db.clone();
+
+                tokio::spawn(async move {
+                    let network = litd_service::current_network(&dashboard_db_clone);
+                    let info = node_status(&network, &ui_handle_weak).await;
+                    update_ui_with_node_info(&ui_handle_weak, info, &dashboard_db_clone);
+                });
+            });
+
+            let forwarding_window_weak = window_weak.clone();
+            let forwarding_db = db.clone();
+            window.on_show_forwarding_history(move || {
+                show_forwarding_report(&forwarding_window_weak, &forwarding_db, String::new());
+                if let Some(window) = forwarding_window_weak.upgrade() {
+                    window.set_active_page(8i32);
+                }
+            });
+
+            let sync_forwarding_window_weak = window_weak.clone();
+            let sync_forwarding_db = db.clone();
+            window.on_sync_forwarding_history(move || {
+                let ui_handle_weak = sync_forwarding_window_weak.clone();
+                let sync_forwarding_db_clone = sync_forwarding_db.clone();
+
+                tokio::spawn(async move {
+                    let forwarding_network = litd_service::get_network(&sync_forwarding_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let status = match forwarding::sync(&forwarding_network, &sync_forwarding_db_clone) {
+                        Ok(added) => format!("Synced {} new forwarding events.", added),
+                        Err(e) => format!("Error syncing forwarding history: {}", e),
+                    };
+                    show_forwarding_report(&ui_handle_weak, &sync_forwarding_db_clone, status);
+                });
+            });
+
+            let export_forwarding_window_weak = window_weak.clone();
+            let export_forwarding_db = db.clone();
+            window.on_export_forwarding_history(move || {
+                let ui_handle_weak = export_forwarding_window_weak.clone();
+                let export_forwarding_db_clone = export_forwarding_db.clone();
+
+                tokio::spawn(async move {
+                    let forwarding_network = litd_service::get_network(&export_forwarding_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let status = match forwarding::list_events(&forwarding_network, &export_forwarding_db_clone)
+                        .and_then(|events| forwarding::export_csv(&events, &utils::export_dir(), &forwarding_network))
+                    {
+                        Ok(path) => format!("Exported forwarding history to {}", path.display()),
+                        Err(e) => format!("Error exporting forwarding history: {}", e),
+                    };
+                    show_forwarding_report(&ui_handle_weak, &export_forwarding_db_clone, status);
+                });
+            });
+
+            let onchain_window_weak = window_weak.clone();
+            let onchain_db = db.clone();
+            window.on_show_onchain_wallet(move || {
+                let ui_handle_weak = onchain_window_weak.clone();
+                let onchain_db_clone = onchain_db.clone();
+                if let Some(window) = ui_handle_weak.upgrade() {
+                    let mut fee_options: Vec<SharedString> = wallet::CONF_TARGETS.iter().map(|(label, _)| SharedString::from(*label)).collect();
+                    fee_options.push("Custom (sat/vB)".into());
+                    window.set_onchain_fee_options(ModelRc::new(VecModel::from(fee_options)));
+                    window.set_onchain_status("".into());
+                    window.set_active_page(9i32);
+                }
+
+                tokio::spawn(async move {
+                    let onchain_network = litd_service::get_network(&onchain_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    refresh_onchain_wallet(&ui_handle_weak, &onchain_network, None);
+                });
+            });
+
+            let refresh_onchain_window_weak = window_weak.clone();
+            let refresh_onchain_db = db.clone();
+            window.on_refresh_onchain_wallet(move || {
+                let ui_handle_weak = refresh_onchain_window_weak.clone();
+                let refresh_onchain_db_clone = refresh_onchain_db.clone();
+
+                tokio::spawn(async move {
+                    let onchain_network = litd_service::get_network(&refresh_onchain_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    refresh_onchain_wallet(&ui_handle_weak, &onchain_network, None);
+                });
+            });
+
+            let address_window_weak = window_weak.clone();
+            let address_db = db.clone();
+            window.on_new_onchain_address(move |type_index| {
+                let ui_handle_weak = address_window_weak.clone();
+                let address_db_clone = address_db.clone();
 
-                    let channel_network = litd_service::get_network(&channel_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
-                    
-                    // --- Stage 1: List Peers (Async/Blocking work) ---
-                    let list_peers_result = channels::list_peers(&channel_network);
-                    
-                    let channel_network_clone = channel_network.clone();
-                    match list_peers_result {
-                        Ok(peers) => {
-                            if let Some(first_peer_pubkey_str) = peers.first().map(|s| s.to_string()) { // Own the string
-                                
-                                // --- Stage 2: Update UI - Found Peer (on Slint thread) ---
-                                let status_msg_found_peer = format!("Found peer: {}. Attempting to open channel for {} sats...", first_peer_pubkey_str, DEFAULT_CHANNEL_AMOUNT);
-                                let weak_for_status_update = task_weak_ref.clone();
-                                slint::invoke_from_event_loop(move || {
-                                    if let Some(ui) = weak_for_status_update.upgrade() {
-                                        ui.set_create_channel_status_message(status_msg_found_peer.into());
-                                    }
-                                }).ok(); // .ok() to ignore error if UI is already closed
+                tokio::spawn(async move {
+                    let onchain_network = litd_service::get_network(&address_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let address_type = wallet::ADDRESS_TYPES.get(type_index as usize).copied().unwrap_or("p2wkh");
+                    let result = wallet::new_address(&onchain_network, address_type);
 
-                                // --- Stage 3: Open Channel (Async/Blocking work) ---
-                                let open_channel_result = channels::open_channel(&channel_network_clone, &first_peer_pubkey_str, DEFAULT_CHANNEL_AMOUNT);
-                                let weak_for_final_update = task_weak_ref.clone(); // Clone weak ref for the final update closure
-
-                                match open_channel_result {
-                                    Ok(result) => {
-                                        let funding_txid = utils::extract_funding_txid_from_string(&result).unwrap_or_else(|| "N/A".to_string());
-                                        let success_message = format!(
-                                            "Channel open success! Funding TXID: {}. You can now visit 'Manage Channels'.",
-                                            funding_txid
-                                        );
-                                        // --- Stage 4a: Update UI - Success (on Slint thread) ---
-                                        slint::invoke_from_event_loop(move || {
-                                            if let Some(ui) = weak_for_final_update.upgrade() {
-                                                ui.set_create_channel_funding_txid(funding_txid.into());
-                                                ui.set_create_channel_status_message(success_message.into());
-                                                ui.set_create_channel_in_progress(false);
-                                            }
-                                        }).ok();
-                                    }
-                                    Err(e) => {
-                                        let error_message = format!("Failed to open channel with {}: {}", first_peer_pubkey_str, e);
-                                        println!("{}", error_message);
-                                        // --- Stage 4b: Update UI - Error Open Channel (on Slint thread) ---
-                                        slint::invoke_from_event_loop(move || {
-                                            if let Some(ui) = weak_for_final_update.upgrade() {
-                                                ui.set_create_channel_status_message(error_message.into());
-                                                ui.set_create_channel_funding_txid("".into());
-                                                ui.set_create_channel_in_progress(false);
-                                            }
-                                        }).ok();
-                                    }
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = ui_handle_weak.upgrade() {
+                            match result {
+                                Ok(address) => {
+                                    window.set_onchain_receive_address(address.into());
+                                    window.set_onchain_status("".into());
                                 }
-                            } else { // No peers found
-                                let msg = "No peers found to auto-open a channel with.".to_string();
-                                println!("{}", msg);
-                                let weak_for_no_peers_update = task_weak_ref.clone();
-                                // --- Update UI - No Peers (on Slint thread) ---
-                                slint::invoke_from_event_loop(move || {
-                                    if let Some(ui) = weak_for_no_peers_update.upgrade() {
-                                        ui.set_create_channel_status_message(msg.into());
-                                        ui.set_create_channel_in_progress(false);
-                                    }
-                                }).ok();
+                                Err(e) => window.set_onchain_status(format!("Error generating address: {}", e).into()),
                             }
                         }
-                        Err(e) => { // Failed to list peers
-                            let error_message = format!("Failed to list peers: {}", e);
-                            println!("{}", error_message);
-                            let weak_for_list_error_update = task_weak_ref.clone();
-                            // --- Update UI - List Peers Error (on Slint thread) ---
-                            slint::invoke_from_event_loop(move || {
-                                if let Some(ui) = weak_for_list_error_update.upgrade() {
-                                    ui.set_create_channel_status_message(error_message.into());
-                                    ui.set_create_channel_in_progress(false);
-                                }
-                            }).ok();
-                        }
-                    }
+                    });
+                });
+            });
+
+            let send_coins_window_weak = window_weak.clone();
+            let send_coins_db = db.clone();
+            window.on_send_onchain_coins(move |address, amount, fee_index, custom_rate, label| {
+                let ui_handle_weak = send_coins_window_weak.clone();
+                let send_coins_db_clone = send_coins_db.clone();
+                let outpoints: Vec<String> = match ui_handle_weak.upgrade() {
+                    Some(window) => window.get_onchain_utxos().iter()
+                        .filter(|u| u.selected)
+                        .map(|u| u.outpoint.to_string())
+                        .collect(),
+                    None => return,
+                };
+
+                tokio::spawn(async move {
+                    let onchain_network = litd_service::get_network(&send_coins_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    // The on-chain form is always in sats, a suffix can still say otherwise
+                    let result = Amount::parse_positive(&amount, amount::Unit::Sat)
+                        .and_then(|amount| {
+                            let fee_rate = wallet::FeeRate::from_option(fee_index as usize, &custom_rate)?;
+                            wallet::send_coins(&onchain_network, &address, amount, &fee_rate, &outpoints, &label)
+                        });
+                    let status = match result {
+                        Ok(txid) => format!("Transaction broadcast: {}", txid),
+                        Err(e) => format!("Error sending coins: {}", e),
+                    };
+                    refresh_onchain_wallet(&ui_handle_weak, &onchain_network, Some(status));
+                });
+            });
+
+            let label_tx_window_weak = window_weak.clone();
+            let label_tx_db = db.clone();
+            window.on_label_onchain_transaction(move |txid, label| {
+                let ui_handle_weak = label_tx_window_weak.clone();
+                let label_tx_db_clone = label_tx_db.clone();
+
+                tokio::spawn(async move {
+                    let onchain_network = litd_service::get_network(&label_tx_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let status = match wallet::label_transaction(&onchain_network, &txid, &label) {
+                        Ok(_) => format!("Labelled {}", txid),
+                        Err(e) => format!("Error labelling transaction: {}", e),
+                    };
+                    refresh_onchain_wallet(&ui_handle_weak, &onchain_network, Some(status));
                 });
             });
 
@@ -397,20 +1360,26 @@ async fn main() -> Result<()> {
                 });
             });
 
+            // Filter, order and page of the invoice list, kept across refreshes
+            let invoice_query = Arc::new(Mutex::new(invoice::InvoiceQuery::new(INVOICE_PAGE_SIZE)));
+
             let window_weak_clone = window_weak.clone();
             let db_clone = db.clone();
 
+            let manage_invoice_query = invoice_query.clone();
             window.on_manage_invoices(move || {
                 println!("Listing invoices (UI callback invoked)...");
                 let ui_handle_weak = window_weak_clone.clone();
                 let db_clone_for_invoices = db_clone.clone();
+                let query_clone = manage_invoice_query.clone();
+                *manage_invoice_query.lock().unwrap() = invoice::InvoiceQuery::new(INVOICE_PAGE_SIZE);
 
                 tokio::spawn(async move {
-                    match invoice::list_invoices(&db_clone_for_invoices) {
-                        Ok(slint_invoices_vec) => {
+                    match invoice::sync_invoices(&litd_service::current_network(&db_clone_for_invoices), &db_clone_for_invoices) {
+                        Ok(_) => {
                             let _ = slint::invoke_from_event_loop(move || {
                                 if let Some(window) = ui_handle_weak.upgrade() {
-                                    window.set_all_invoices(ModelRc::new(VecModel::from(slint_invoices_vec)));
+                                    show_invoices(&window, &db_clone_for_invoices, &query_clone);
                                     window.set_status_message("Invoices loaded.".into());
                                     println!("Invoices successfully loaded and UI updated.");
                                     window.set_active_page(2i32);
@@ -430,6 +1399,133 @@ async fn main() -> Result<()> {
                 });
             });
 
+            let refresh_invoices_window_weak = window_weak.clone();
+            let refresh_invoices_db = db.clone();
+            let refresh_invoice_query = invoice_query.clone();
+            window.on_refresh_invoices(move || {
+                let ui_handle_weak = refresh_invoices_window_weak.clone();
+                let refresh_db_clone = refresh_invoices_db.clone();
+                let query_clone = refresh_invoice_query.clone();
+
+                tokio::spawn(async move {
+                    let result = invoice::sync_invoices(&litd_service::current_network(&refresh_db_clone), &refresh_db_clone);
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = ui_handle_weak.upgrade() {
+                            match result {
+                                Ok(0) => {}
+                                Ok(_) => show_invoices(&window, &refresh_db_clone, &query_clone),
+                                Err(e) => window.set_status_message(format!("Failed to refresh invoices: {}", e).into()),
+                            }
+                        }
+                    });
+                });
+            });
+
+            let invoice_refresh_window_weak = window_weak.clone();
+            // Pick up new and changed invoices while the list is open
+            tokio::spawn(async move {
+                let mut interval = interval(Duration::from_secs(INVOICE_REFRESH_INTERVAL_SECS));
+                loop {
+                    interval.tick().await;
+                    let window_weak_clone = invoice_refresh_window_weak.clone();
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = window_weak_clone.upgrade() {
+                            if window.get_active_page() == 2 {
+                                window.invoke_refresh_invoices();
+                            }
+                        }
+                    });
+                }
+            });
+
+            let query_window_weak = window_weak.clone();
+            let query_db = db.clone();
+            let filter_invoice_query = invoice_query.clone();
+            window.on_query_invoices(move |text, state, from, to, min_amount, max_amount, ownership, sort| {
+                if let Some(window) = query_window_weak.upgrade() {
+                    let unit = amount::display_unit(&query_db);
+                    let parsed = (|| -> anyhow::Result<invoice::InvoiceQuery> {
+                        let optional = |value: &str| (!value.trim().is_empty()).then(|| value.trim().to_string());
+                        let amount = |value: &str| optional(value).map(|v| Amount::parse(&v, unit)).transpose();
+                        Ok(invoice::InvoiceQuery {
+                            state: invoice::state_filter(state).to_string(),
+                            from: optional(&from).map(|d| export::parse_date(&d)).transpose()?,
+                            to: optional(&to).map(|d| export::parse_date(&d)).transpose()?,
+                            min_amount: amount(&min_amount)?,
+                            max_amount: amount(&max_amount)?,
+                            ownership: invoice::Ownership::from_index(ownership),
+                            text: text.to_string(),
+                            sort: invoice::InvoiceSort::from_index(sort),
+                            ..invoice::InvoiceQuery::new(INVOICE_PAGE_SIZE)
+                        })
+                    })();
+                    match parsed {
+                        Ok(query) => {
+                            *filter_invoice_query.lock().unwrap() = query;
+                            show_invoices(&window, &query_db, &filter_invoice_query);
+                        }
+                        Err(e) => window.set_status_message(format!("Invalid filter: {}", e).into()),
+                    }
+                }
+            });
+
+            let unit_window_weak = window_weak.clone();
+            let unit_db = db.clone();
+            let unit_invoice_query = invoice_query.clone();
+            window.on_display_unit_changed(move |index| {
+                let unit = amount::Unit::from_index(index);
+                if let Err(e) = amount::set_display_unit(&unit_db, unit) {
+                    println!("Failed to save display unit: {}", e);
+                }
+                if let Some(window) = unit_window_weak.upgrade() {
+                    // The wallet balance follows on the next node status check, other views when reopened
+                    window.set_amount_unit(unit.as_str().into());
+                    if window.get_active_page() == 2 {
+                        show_invoices(&window, &unit_db, &unit_invoice_query);
+                    }
+                }
+            });
+
+            let page_window_weak = window_weak.clone();
+            let page_db = db.clone();
+            let page_invoice_query = invoice_query.clone();
+            window.on_show_invoice_page(move |page| {
+                if let Some(window) = page_window_weak.upgrade() {
+                    page_invoice_query.lock().unwrap().page = page.max(0) as usize;
+                    show_invoices(&window, &page_db, &page_invoice_query);
+                }
+            });
+            let annotation_window_weak = window_weak.clone();
+            let annotation_db = db.clone();
+            window.on_save_invoice_annotation(move |r_hash, counterparty, order_ref, tags, notes| {
+                if let Some(window) = annotation_window_weak.upgrade() {
+                    let annotation = store::Annotation {
+                        notes: notes.trim().to_string(),
+                        tags: invoice::parse_tags(&tags),
+                        counterparty: counterparty.trim().to_string(),
+                        order_ref: order_ref.trim().to_string(),
+                    };
+                    let network = litd_service::current_network(&annotation_db);
+                    match invoice::save_annotation(&network, &annotation_db, &r_hash, &annotation) {
+                        Ok(_) => {
+                            // Update the row in place so the list keeps its search and scroll position
+                            let invoices = window.get_all_invoices();
+                            if let Some(row) = (0..invoices.row_count()).find(|&row| invoices.row_data(row).is_some_and(|i| i.r_hash == r_hash)) {
+                                if let Some(mut details) = invoices.row_data(row) {
+                                    details.notes = annotation.notes.into();
+                                    details.tags = annotation.tags.join(", ").into();
+                                    details.counterparty = annotation.counterparty.into();
+                                    details.order_ref = annotation.order_ref.into();
+                                    invoices.set_row_data(row, details);
+                                }
+                            }
+                            window.set_status_message("Notes saved.".into());
+                        }
+                        Err(e) => window.set_status_message(format!("Error saving notes: {}", e).into()),
+                    }
+                }
+            });
+
             let window_weak_clone = window_weak.clone();
             window.on_request_preimage_generation(move || {
                 let (preimage, hash) = generate_preimage();
@@ -450,13 +1546,22 @@ async fn main() -> Result<()> {
             window.on_create_custom_invoice(move |preimage_x, preimage_h, amount, memo| {
                 if let Some(window) = window_weak_clone.upgrade() {
                     println!("Creating custom invoice with preimage: {}, amount: {}, memo: {}", preimage_x, amount, memo);
-                    match invoice::create_invoice(preimage_x.to_string(), preimage_h.to_string(), amount.to_string(), memo.to_string(), &db_clone_for_create) {
+                    let unit = amount::display_unit(&db_clone_for_create);
+                    let amount = match Amount::parse_positive(&amount, unit) {
+                        Ok(amount) => amount,
+                        Err(e) => {
+                            window.set_status_message(SharedString::from(format!("Invalid amount: {}", e)));
+                            return;
+                        }
+                    };
+                    match invoice::create_invoice(&litd_service::current_network(&db_clone_for_create), preimage_x.to_string(), preimage_h.to_string(), amount, memo.to_string(), &db_clone_for_create) {
                         Ok(output) => {
                             window.set_status_message(SharedString::from(format!(
                                 "Created invoice with preimage: {}, amount: {}, memo: {}",
-                                preimage_x, amount, memo
+                                preimage_x, amount.format(unit), memo
                             )));
                             window.set_payment_address(SharedString::from(output.payment_addr));
+                            show_qr(&window, qr::bolt11_payload(&output.payment_request), "custom-invoice");
                             window.set_generated_preimage_h(SharedString::from(""));
                             window.set_generated_preimage_x(SharedString::from(""));
                         }
@@ -474,10 +1579,10 @@ async fn main() -> Result<()> {
             let confirm_preimage_window_weak_clone = window_weak.clone();
             window.on_confirm_preimage(move | pre_image_x: SharedString, pre_image_h: SharedString | {
                 if let Some(window) = confirm_preimage_window_weak_clone.upgrade() {
-                    let x_bytes = match hex::decode(pre_image_x.to_string()) {
+                    let x_bytes = match hex::decode(&pre_image_x) {
                         Ok(bytes) => bytes,
-                        Err(e) => {
-                            window.set_custom_invoice_status_message(SharedString::from(format!("Invalid preimage X")));
+                        Err(_) => {
+                            window.set_custom_invoice_status_message(SharedString::from("Invalid preimage X"));
                             window.set_confirmed_preimage(false);
                             return;
                         }
@@ -487,10 +1592,10 @@ async fn main() -> Result<()> {
                     hasher.update(x_bytes);
                     let result = hasher.finalize();
                     let result_hex = hex::encode(result);
-                    println!("pre_image_x: {}", pre_image_x.to_string());
-                    println!("pre_image_h: {}", pre_image_h.to_string());
+                    println!("pre_image_x: {}", pre_image_x);
+                    println!("pre_image_h: {}", pre_image_h);
                     println!("result_hex: {}", result_hex);
-                    if result_hex == pre_image_h.to_string() {
+                    if pre_image_h == result_hex {
                         window.set_custom_invoice_status_message(SharedString::from("Preimage confirmed."));
                         window.set_confirmed_preimage(true);
                     } else {
@@ -521,15 +1626,11 @@ async fn main() -> Result<()> {
                         preimage_x
                     )));
 
-                    let refresh_ui_handle_weak = settle_window_weak_clone.clone();
-                    let refresh_db_clone = db_clone_for_settle.clone();
-
-                    match invoice::settle_invoice(preimage_x.to_string()) {
+                    match invoice::settle_invoice(&litd_service::current_network(&db_clone_for_settle), preimage_x.to_string()) {
                         Ok(_) => {
                             let ms_db_clone = db_clone_for_settle.clone();
-                            match ms_db_clone.get(b"identity_pubkey") {
-                                Ok(Some(identity_pubkey)) => {
-                                    let identity_pubkey_str = String::from_utf8_lossy(&identity_pubkey).to_string();
+                            match store::node_identity(&ms_db_clone, &litd_service::current_network(&ms_db_clone)) {
+                                Ok(identity_pubkey_str) => {
 
                                     tokio::spawn(async move {
                                         match litd_service::send_custom_message(preimage_x.to_string(), identity_pubkey_str).await {
@@ -549,31 +1650,7 @@ async fn main() -> Result<()> {
 
                             window.set_status_message(SharedString::from("Invoice settled successfully. Refreshing list..."));
 
-                            // Spawn a task to refresh the invoices list
-                            tokio::spawn(async move {
-                                match invoice::list_invoices(&refresh_db_clone) {
-                                    Ok(slint_invoices_vec) => {
-                                        let _ = slint::invoke_from_event_loop(move || {
-                                            if let Some(window_for_refresh) = refresh_ui_handle_weak.upgrade() {
-                                                window_for_refresh.set_all_invoices(ModelRc::new(VecModel::from(slint_invoices_vec)));
-                                                let current_status = window_for_refresh.get_status_message();
-                                                window_for_refresh.set_status_message(format!("{} Invoices refreshed.", current_status).into());
-                                                println!("Invoices successfully refreshed and UI updated after settlement.");
-                                            }
-                                        });
-                                    }
-                                    Err(e) => {
-                                        let error_msg = format!("Error refreshing invoices after settlement: {}", e);
-                                        println!("{}", error_msg);
-                                        let _ = slint::invoke_from_event_loop(move || {
-                                            if let Some(window_for_refresh_err) = refresh_ui_handle_weak.upgrade() {
-                                                let current_status = window_for_refresh_err.get_status_message();
-                                                window_for_refresh_err.set_status_message(format!("{} Failed to refresh invoices: {}", current_status, e).into());
-                                            }
-                                        });
-                                    }
-                                }
-                            });
+                            window.invoke_refresh_invoices();
                         }
                         Err(e) => {
                             window.set_status_message(SharedString::from(format!(
@@ -590,9 +1667,7 @@ async fn main() -> Result<()> {
                 if let Some(window) = copy_window_weak_clone.upgrade() {
                     match invoice::copy_payment_request(payment_request.to_string()) {
                         Ok(_) => {
-                            window.set_status_message(SharedString::from(format!(
-                                "Copied to clipboard",
-                            )));
+                            window.set_status_message(SharedString::from("Copied to clipboard"));
                         }
                         Err(e) => {
                             window.set_status_message(SharedString::from(format!(
@@ -610,14 +1685,23 @@ async fn main() -> Result<()> {
             window.on_create_standard_invoice(move |amount, memo| {
                 if let Some(window) = standard_window_weak_clone.upgrade() {
                     println!("Creating standard invoice with amount: {}, memo: {}", amount, memo);
+                    let unit = amount::display_unit(&db_clone_for_create);
+                    let amount = match Amount::parse_positive(&amount, unit) {
+                        Ok(amount) => amount,
+                        Err(e) => {
+                            window.set_status_message(SharedString::from(format!("Invalid amount: {}", e)));
+                            return;
+                        }
+                    };
 
-                    match invoice::create_standard_invoice(amount.to_string(), memo.to_string(), &db_clone_for_create) {
+                    match invoice::create_standard_invoice(&litd_service::current_network(&db_clone_for_create), amount, memo.to_string(), &db_clone_for_create) {
                         Ok(output) => {
                             window.set_status_message(SharedString::from(format!(
                                 "Created standard invoice with memo: {}, amount: {}",
-                                memo, amount
+                                memo, amount.format(unit)
                             )));
-                            window.set_standard_payment_address(SharedString::from(output));
+                            window.set_standard_payment_address(SharedString::from(output.payment_addr));
+                            show_qr(&window, qr::bolt11_payload(&output.payment_request), "standard-invoice");
                         }
                         Err(e) => {
                             window.set_status_message(SharedString::from(format!(
@@ -629,7 +1713,146 @@ async fn main() -> Result<()> {
                 }
             });
 
+            let invoice_qr_window_weak = window_weak.clone();
+            window.on_show_invoice_qr(move |payment_request| {
+                if let Some(window) = invoice_qr_window_weak.upgrade() {
+                    show_qr(&window, qr::bolt11_payload(&payment_request), "invoice-list");
+                }
+            });
+
+            let address_qr_window_weak = window_weak.clone();
+            window.on_show_address_qr(move |address, amount, label| {
+                if let Some(window) = address_qr_window_weak.upgrade() {
+                    // On-chain amounts are whole sats, whatever unit the rest of the app shows
+                    let amount = if amount.trim().is_empty() {
+                        None
+                    } else {
+                        match Amount::parse_positive(&amount, amount::Unit::Sat).and_then(|a| a.to_sat_exact().map(|_| a)) {
+                            Ok(amount) => Some(amount),
+                            Err(e) => {
+                                window.set_onchain_status(format!("Invalid amount: {}", e).into());
+                                return;
+                            }
+                        }
+                    };
+                    window.set_onchain_status("".into());
+                    show_qr(&window, qr::bip21_uri(&address, amount, &label), "onchain");
+                }
+            });
+
+            let save_qr_window_weak = window_weak.clone();
+            window.on_save_qr(move |format| {
+                if let Some(window) = save_qr_window_weak.upgrade() {
+                    let name = format!("qr-{}", chrono::Utc::now().format("%Y%m%d-%H%M%S"));
+                    let status = match qr::export(&window.get_qr_payload(), &name, qr::ExportFormat::from_index(format), &utils::export_dir()) {
+                        Ok(path) => format!("Saved to {}", path.display()),
+                        Err(e) => format!("Error saving QR code: {}", e),
+                    };
+                    window.set_qr_status(status.into());
+                }
+            });
+
+            let scan_window_weak = window_weak.clone();
+            let scan_db = db.clone();
+            window.on_scan_payment_qr(move |path| {
+                let ui_handle_weak = scan_window_weak.clone();
+                let scan_db_clone = scan_db.clone();
+                if let Some(window) = ui_handle_weak.upgrade() {
+                    window.set_pay_in_progress(true);
+                    window.set_pay_summary("".into());
+                    window.set_pay_status("Reading QR code...".into());
+                }
+
+                tokio::spawn(async move {
+                    let pay_network = litd_service::get_network(&scan_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let path = PathBuf::from(path.trim());
+                    let result = qr::decode_file(&path)
+                        .and_then(|text| payable_invoice(&pay_network, &text, amount::display_unit(&scan_db_clone)));
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = ui_handle_weak.upgrade() {
+                            window.set_pay_in_progress(false);
+                            match result {
+                                Ok((payment_request, summary)) => {
+                                    window.set_pay_request(payment_request.into());
+                                    window.set_pay_summary(summary.into());
+                                    window.set_pay_status(format!("Read from {}, check the details before paying", path.display()).into());
+                                }
+                                Err(e) => window.set_pay_status(format!("Error reading QR code: {}", e).into()),
+                            }
+                        }
+                    });
+                });
+            });
+
+            let check_pay_window_weak = window_weak.clone();
+            let check_pay_db = db.clone();
+            window.on_check_payment_request(move |text| {
+                let ui_handle_weak = check_pay_window_weak.clone();
+                let check_pay_db_clone = check_pay_db.clone();
+
+                tokio::spawn(async move {
+                    let pay_network = litd_service::get_network(&check_pay_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let result = payable_invoice(&pay_network, &text, amount::display_unit(&check_pay_db_clone));
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = ui_handle_weak.upgrade() {
+                            match result {
+                                Ok((payment_request, summary)) => {
+                                    window.set_pay_request(payment_request.into());
+                                    window.set_pay_summary(summary.into());
+                                    window.set_pay_status("".into());
+                                }
+                                Err(e) => {
+                                    window.set_pay_summary("".into());
+                                    window.set_pay_status(format!("Error: {}", e).into());
+                                }
+                            }
+                        }
+                    });
+                });
+            });
+
+            let pay_window_weak = window_weak.clone();
+            let pay_db = db.clone();
+            window.on_pay_custom_invoice(move |payment_request| {
+                let ui_handle_weak = pay_window_weak.clone();
+                let pay_db_clone = pay_db.clone();
+                if let Some(window) = ui_handle_weak.upgrade() {
+                    window.set_pay_in_progress(true);
+                    window.set_pay_status("Paying, a hold invoice stays in flight until the payee settles it...".into());
+                }
+
+                tokio::spawn(async move {
+                    let pay_network = litd_service::get_network(&pay_db_clone).await.unwrap_or_else(|_| "testnet".to_string());
+                    let unit = amount::display_unit(&pay_db_clone);
+                    // pay_invoice waits for lncli until the payment resolves, which for a hold
+                    // invoice can take as long as the payee likes
+                    let result = tokio::task::spawn_blocking(move || invoice::pay_invoice(&pay_network, &payment_request, None))
+                        .await
+                        .unwrap_or_else(|e| Err(anyhow::anyhow!("Payment task failed: {}", e)));
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = ui_handle_weak.upgrade() {
+                            window.set_pay_in_progress(false);
+                            let status = match result {
+                                Ok(outcome) if outcome.status == "SUCCEEDED" => {
+                                    window.set_pay_summary("".into());
+                                    format!(
+                                        "Paid {} with {} fee\nPreimage: {}",
+                                        outcome.amount.format(unit), outcome.fee.format(unit), outcome.preimage
+                                    )
+                                }
+                                Ok(outcome) => format!("Payment {}: {}", outcome.status, outcome.failure_reason),
+                                Err(e) => format!("Error paying invoice: {}", e),
+                            };
+                            window.set_pay_status(status.into());
+                        }
+                    });
+                });
+            });
+
             window.run()?;
+            if let Some(supervisor) = &supervisor {
+                supervisor.stop();
+            }
             Ok(())
         }
         Err(e) => {
@@ -639,6 +1862,378 @@ async fn main() -> Result<()> {
     }
 }
 
+/// Reads an invoice, `lightning:` or `bitcoin:` URI and has lnd check the invoice in it.
+/// Returns the BOLT11 string and a summary to confirm before paying.
+fn payable_invoice(network: &str, text: &str, unit: amount::Unit) -> Result<(String, String)> {
+    let payment_request = match payment_uri::parse(text)? {
+        payment_uri::PaymentCode::Lightning(payment_request) => payment_request,
+        payment_uri::PaymentCode::OnChain { address, amount, .. } => {
+            let amount = amount.map(|a| format!(" for {}", a.format(unit))).unwrap_or_default();
+            return Err(anyhow::anyhow!(
+                "This is an on-chain request{} to {}, send it from the On-chain Wallet page",
+                amount, address
+            ));
+        }
+    };
+    let decoded = invoice::decode_invoice(network, &payment_request)?;
+    let expires = chrono::DateTime::from_timestamp(decoded.expires_at, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default();
+    let description = if decoded.description.is_empty() { "(none)" } else { decoded.description.as_str() };
+    let summary = format!(
+        "Amount: {}\nTo: {}\nDescription: {}\nPayment hash: {}\nExpires: {}",
+        decoded.amount.format(unit), decoded.destination, description, decoded.payment_hash, expires
+    );
+    Ok((payment_request, summary))
+}
+
+/// Encodes `payload` and shows it in the view named by `source`, the others keep theirs hidden.
+fn show_qr(window: &MainWindow, payload: String, source: &str) {
+    match qr::QrCode::encode(&payload) {
+        Ok(code) => {
+            window.set_qr_code(code.to_image());
+            window.set_qr_payload(payload.into());
+            window.set_qr_source(source.into());
+            window.set_qr_status("".into());
+        }
+        Err(e) => window.set_status_message(format!("Could not render QR code: {}", e).into()),
+    }
+}
+
+fn channel_row(channel: channels::ActiveChannelInfo, unit: amount::Unit) -> Channel {
+    Channel {
+        channel_id: channel.channel_id.into(),
+        remote_pubkey: channel.remote_pubkey.into(),
+        capacity: channel.capacity.format(unit).into(),
+        local_balance: channel.local_balance.format(unit).into(),
+        remote_balance: channel.remote_balance.format(unit).into(),
+        active: channel.active,
+    }
+}
+
+fn show_channel_candidates(window_weak: &Arc<slint::Weak<MainWindow>>, candidates: &[channel_scoring::ChannelCandidate]) {
+    let slint_candidates: Vec<ChannelCandidate> = candidates.iter().map(|c| ChannelCandidate {
+        pub_key: c.pub_key.clone().into(),
+        alias: c.alias.clone().into(),
+        score: format!("{:.1}", c.score).into(),
+        address: c.address.clone().unwrap_or_default().into(),
+        explanation: c.explanation.join("\n").into(),
+    }).collect();
+    let window_weak_clone = window_weak.clone();
+    slint::invoke_from_event_loop(move || {
+        if let Some(ui) = window_weak_clone.upgrade() {
+            ui.set_channel_candidates(ModelRc::new(VecModel::from(slint_candidates)));
+        }
+    }).ok();
+}
+
+fn report_channel_open(window_weak: &Arc<slint::Weak<MainWindow>>, result: Result<(String, String)>) {
+    let window_weak_clone = window_weak.clone();
+    match result {
+        Ok((pub_key, output)) => {
+            let funding_txid = utils::extract_funding_txid_from_string(&output).unwrap_or_else(|| "N/A".to_string());
+            let success_message = format!(
+                "Channel open with {} success! Funding TXID: {}. You can now visit 'Manage Channels'.",
+                pub_key, funding_txid
+            );
+            slint::invoke_from_event_loop(move || {
+                if let Some(ui) = window_weak_clone.upgrade() {
+                    ui.set_create_channel_funding_txid(funding_txid.into());
+                    ui.set_create_channel_status_message(success_message.into());
+                    ui.set_create_channel_in_progress(false);
+                }
+            }).ok();
+        }
+        Err(e) => {
+            let error_message = format!("Failed to open channel: {}", e);
+            println!("{}", error_message);
+            slint::invoke_from_event_loop(move || {
+                if let Some(ui) = window_weak_clone.upgrade() {
+                    ui.set_create_channel_status_message(error_message.into());
+                    ui.set_create_channel_funding_txid("".into());
+                    ui.set_create_channel_in_progress(false);
+                }
+            }).ok();
+        }
+    }
+}
+
+fn stored_identity_pubkey(db: &sled::Db, network: &str) -> String {
+    store::node_identity(db, network).unwrap_or_default()
+}
+
+fn fee_profile_entries(db: &sled::Db) -> Vec<FeeProfileEntry> {
+    fee_policy::list_profiles(&litd_service::current_network(db), db).unwrap_or_default().into_iter().map(|p| FeeProfileEntry {
+        channel: if p.chan_id.is_empty() { "all channels".into() } else { p.chan_id.clone().into() },
+        summary: format!(
+            "Below {}% local: {} msat + {} ppm, otherwise {} msat + {} ppm",
+            p.threshold_pct, p.low_base_fee_msat, p.low_fee_rate_ppm, p.normal_base_fee_msat, p.normal_fee_rate_ppm
+        ).into(),
+        name: p.name.into(),
+    }).collect()
+}
+
+fn show_forwarding_report(window_weak: &Arc<slint::Weak<MainWindow>>, db: &sled::Db, status: String) {
+    let events = match forwarding::list_events(&litd_service::current_network(db), db) {
+        Ok(events) => events,
+        Err(e) => {
+            println!("Error reading forwarding events: {}", e);
+            Vec::new()
+        }
+    };
+
+    let total_fee_msat: u64 = events.iter().map(|e| e.fee_msat).sum();
+    let totals = format!("{} forwards, {:.3} sats earned in fees", events.len(), total_fee_msat as f64 / 1000.0);
+
+    let per_day = forwarding::revenue_per_day(&events, FORWARDING_CHART_DAYS);
+    let max_day_fee = per_day.iter().map(|d| d.fee_msat).max().unwrap_or(0).max(1);
+    let daily_fees: Vec<DailyFees> = per_day.into_iter().map(|d| DailyFees {
+        summary: format!("{}: {} forwards, {:.3} sats", d.day, d.forwards, d.fee_msat as f64 / 1000.0).into(),
+        ratio: d.fee_msat as f32 / max_day_fee as f32,
+        day: d.day.into(),
+    }).collect();
+
+    let per_channel = forwarding::revenue_per_channel(&events);
+    let max_channel_fee = per_channel.iter().map(|c| c.fee_msat).max().unwrap_or(0).max(1);
+    let channel_fees: Vec<ChannelFees> = per_channel.into_iter().map(|c| ChannelFees {
+        summary: format!("{} forwards, {:.3} sats fees, {} sats volume", c.forwards, c.fee_msat as f64 / 1000.0, c.volume_msat / 1000).into(),
+        ratio: c.fee_msat as f32 / max_channel_fee as f32,
+        chan_id: c.chan_id.into(),
+    }).collect();
+
+    let window_weak_clone = window_weak.clone();
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(window) = window_weak_clone.upgrade() {
+            window.set_forwarding_totals(totals.into());
+            window.set_forwarding_daily_fees(ModelRc::new(VecModel::from(daily_fees)));
+            window.set_forwarding_channel_fees(ModelRc::new(VecModel::from(channel_fees)));
+            window.set_forwarding_status(status.into());
+            window.set_forwarding_in_progress(false);
+        }
+    });
+}
+
+fn refresh_onchain_wallet(window_weak: &Arc<slint::Weak<MainWindow>>, network: &str, status: Option<String>) {
+    let balance_summary = match wallet::balance(network) {
+        Ok(b) => format!("Confirmed: {}, unconfirmed: {}, locked: {}", b.confirmed, b.unconfirmed, b.locked),
+        Err(e) => format!("Error loading balance: {}", e),
+    };
+
+    let utxos: Vec<WalletUtxo> = match wallet::list_utxos(network) {
+        Ok(list) => list.into_iter().map(|u| WalletUtxo {
+            summary: format!("{}, {} confirmations, {} ({})", u.amount, u.confirmations, u.address, u.address_type).into(),
+            outpoint: u.outpoint.into(),
+            address: u.address.into(),
+            selected: false,
+        }).collect(),
+        Err(e) => {
+            println!("Error listing UTXOs: {}", e);
+            Vec::new()
+        }
+    };
+
+    let transactions: Vec<WalletTransaction> = match wallet::list_transactions(network) {
+        Ok(list) => list.into_iter().map(|t| {
+            let time = chrono::DateTime::from_timestamp(t.timestamp as i64, 0)
+                .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            WalletTransaction {
+                summary: format!("{:+} sats, fee {} sats, {} confirmations, {}", t.amount_sat, t.fee_sat, t.confirmations, time).into(),
+                txid: t.txid.into(),
+                label: t.label.into(),
+            }
+        }).collect(),
+        Err(e) => {
+            println!("Error listing wallet transactions: {}", e);
+            Vec::new()
+        }
+    };
+
+    let window_weak_clone = window_weak.clone();
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(window) = window_weak_clone.upgrade() {
+            window.set_onchain_balance_summary(balance_summary.into());
+            window.set_onchain_utxos(ModelRc::new(VecModel::from(utxos)));
+            window.set_onchain_transactions(ModelRc::new(VecModel::from(transactions)));
+            if let Some(status) = status {
+                window.set_onchain_status(status.into());
+                window.set_onchain_in_progress(false);
+            }
+        }
+    });
+}
+
+fn report_wallet_init(window_weak: &Arc<slint::Weak<MainWindow>>, result: Result<()>, success_message: &str) {
+    let window_weak_clone = window_weak.clone();
+    let success_message = success_message.to_string();
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(window) = window_weak_clone.upgrade() {
+            match result {
+                Ok(_) => {
+                    window.set_wallet_needs_init(false);
+                    window.set_wallet_seed_words(ModelRc::new(VecModel::from(Vec::<SharedString>::new())));
+                    window.set_wallet_setup_status("".into());
+                    window.set_wallet_setup_step(0);
+                    window.set_status_message(success_message.into());
+                    window.set_active_page(-1i32);
+                }
+                Err(e) => {
+                    println!("Failed to initialize wallet: {}", e);
+                    window.set_wallet_setup_status(format!("Failed to initialize wallet: {}", e).into());
+                }
+            }
+            window.set_wallet_setup_in_progress(false);
+        }
+    });
+}
+
+fn show_connection_report(window_weak: &Arc<slint::Weak<MainWindow>>, network: &str) {
+    let connection = discovery::discover(network);
+    let mut report = discovery::report(&connection);
+    match litd_service::litd_service_status(network) {
+        Ok((status, running)) => report.push(discovery::DiscoveredItem {
+            label: "litd service".to_string(),
+            value: status,
+            found: running,
+        }),
+        Err(e) => println!("Failed to check litd service status: {}", e),
+    }
+    let items: Vec<ConnectionItem> = report.into_iter().map(|item| {
+        println!("Discovery: {} = {} ({})", item.label, item.value, if item.found { "found" } else { "missing" });
+        ConnectionItem {
+            label: item.label.into(),
+            value: item.value.into(),
+            found: item.found,
+        }
+    }).collect();
+
+    let window_weak_clone = window_weak.clone();
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(window) = window_weak_clone.upgrade() {
+            window.set_connection_report(ModelRc::new(VecModel::from(items)));
+        }
+    });
+}
+
+fn show_webhooks(window_weak: &Arc<slint::Weak<MainWindow>>, db: &sled::Db, status: String) {
+    let entries: Vec<WebhookEntry> = webhooks::list_webhooks(db).unwrap_or_default().into_iter()
+        .map(|webhook| WebhookEntry {
+            url: webhook.url.into(),
+            secret: webhook.secret.into(),
+        })
+        .collect();
+    let deliveries: Vec<WebhookDelivery> = match webhooks::recent_deliveries(db, WEBHOOK_LOG_ROWS) {
+        Ok(deliveries) => deliveries.into_iter().map(|delivery| {
+            let created = chrono::DateTime::from_timestamp(delivery.created_at, 0)
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default();
+            WebhookDelivery {
+                summary: format!("{}  {}  {}  ({} attempt(s))", created, delivery.event_type, delivery.url, delivery.attempts).into(),
+                status: format!("{:?}", delivery.status).into(),
+                error: delivery.last_error.into(),
+            }
+        }).collect(),
+        Err(e) => {
+            println!("Error reading webhook deliveries: {}", e);
+            Vec::new()
+        }
+    };
+
+    let window_weak_clone = window_weak.clone();
+    let _ = slint::invoke_from_event_loop(move || {
+        if let Some(window) = window_weak_clone.upgrade() {
+            window.set_webhook_entries(ModelRc::new(VecModel::from(entries)));
+            window.set_webhook_deliveries(ModelRc::new(VecModel::from(deliveries)));
+            window.set_webhooks_status(status.into());
+        }
+    });
+}
+
+/// Runs the invoice query against the local index and shows the resulting page. Rows that
+/// are already shown are updated in place, so a refresh doesn't reset the list.
+/// Runs the invoice query off the event loop, a large index takes a while to filter and sort.
+fn show_invoices(window: &MainWindow, db: &sled::Db, query: &Arc<Mutex<invoice::InvoiceQuery>>) {
+    let window_weak = window.as_weak();
+    let db = db.clone();
+    let query = query.clone();
+    tokio::task::spawn_blocking(move || {
+        let requested = query.lock().unwrap().clone();
+        let result = invoice::query_invoices(&litd_service::current_network(&db), &db, &requested, amount::display_unit(&db));
+        let _ = slint::invoke_from_event_loop(move || {
+            let mut query = query.lock().unwrap();
+            // A newer filter or page is on its way, this result is already stale
+            if *query != requested {
+                return;
+            }
+            if let Some(window) = window_weak.upgrade() {
+                match result {
+                    Ok(page) => {
+                        query.page = page.page;
+                        show_invoice_page(&window, page);
+                    }
+                    Err(e) => window.set_status_message(format!("Error reading invoices: {}", e).into()),
+                }
+            }
+        });
+    });
+}
+
+fn show_invoice_page(window: &MainWindow, page: invoice::InvoicePage) {
+    window.set_invoice_page(page.page as i32);
+    window.set_invoice_pages(page.pages as i32);
+    window.set_invoice_total(page.total as i32);
+
+    let model = window.get_all_invoices();
+    match model.as_any().downcast_ref::<VecModel<InvoiceDetails>>() {
+        Some(rows) if rows.row_count() == page.invoices.len()
+            && page.invoices.iter().enumerate().all(|(row, i)| rows.row_data(row).is_some_and(|shown| shown.r_hash == i.r_hash)) =>
+        {
+            for (row, invoice) in page.invoices.into_iter().enumerate() {
+                if rows.row_data(row).as_ref() != Some(&invoice) {
+                    rows.set_row_data(row, invoice);
+                }
+            }
+        }
+        _ => window.set_all_invoices(ModelRc::new(VecModel::from(page.invoices))),
+    }
+}
+
+fn node_dashboard(node_info: &NodeInfo) -> NodeDashboard {
+    let info = &node_info.info;
+    // getinfo reports the color as "#rrggbb"
+    let color = u32::from_str_radix(info.color.trim_start_matches('#'), 16)
+        .map(|rgb| slint::Color::from_rgb_u8((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+        .unwrap_or_else(|_| slint::Color::from_rgb_u8(0x80, 0x80, 0x80));
+    let mut features: Vec<(u32, String)> = info.features.iter().map(|(bit, feature)| {
+        let name = if feature.is_known { feature.name.as_str() } else { "unknown" };
+        let kind = if feature.is_required { "required" } else { "optional" };
+        (bit.parse().unwrap_or(u32::MAX), format!("{}  {} ({})", bit, name, kind))
+    }).collect();
+    features.sort();
+    let chains = info.chains.iter().map(|c| format!("{} {}", c.chain, c.network)).collect::<Vec<_>>().join(", ");
+    let uris: Vec<SharedString> = info.uris.iter().map(SharedString::from).collect();
+    let features: Vec<SharedString> = features.into_iter().map(|(_, f)| f.into()).collect();
+
+    NodeDashboard {
+        running: node_info.running,
+        alias: info.alias.clone().into(),
+        color,
+        identity_pubkey: info.identity_pubkey.clone().into(),
+        version: info.version.clone().into(),
+        block_height: i32::try_from(info.block_height).unwrap_or(i32::MAX),
+        block_hash: info.block_hash.clone().into(),
+        synced_to_chain: info.synced_to_chain,
+        synced_to_graph: info.synced_to_graph,
+        num_peers: info.num_peers as i32,
+        num_active_channels: info.num_active_channels as i32,
+        num_pending_channels: info.num_pending_channels as i32,
+        num_inactive_channels: info.num_inactive_channels as i32,
+        chains: chains.into(),
+        uris: ModelRc::new(VecModel::from(uris)),
+        features: ModelRc::new(VecModel::from(features)),
+    }
+}
+
 fn update_ui_with_node_info(window_weak: &Arc<slint::Weak<MainWindow>>, node_info: NodeInfo, db: &sled::Db) {
     let window_weak_clone = window_weak.clone();
     let db_clone = db.clone();
@@ -648,6 +2243,7 @@ fn update_ui_with_node_info(window_weak: &Arc<slint::Weak<MainWindow>>, node_inf
             window.set_node_is_running(node_info.running);
             if node_info.running {
                 window.set_wallet_needs_unlock(false);
+                window.set_wallet_needs_init(false);
             }
             let sync_status = if node_info.synced {
                 format!("Synced: {} \n(h: {})", node_info.network, node_info.block_height)
@@ -664,8 +2260,10 @@ fn update_ui_with_node_info(window_weak: &Arc<slint::Weak<MainWindow>>, node_inf
                 }
             });
 
+            window.set_node_dashboard(node_dashboard(&node_info));
+
             if node_info.running {
-                db_clone.insert(b"identity_pubkey", node_info.identity_pubkey.as_bytes());
+                let _ = store::set_node_identity(&db_clone, &node_info.network, &node_info.identity_pubkey);
                 window.set_status_message(SharedString::from(
                     format!("Connected to LND {}", node_info.identity_pubkey),
                 ));
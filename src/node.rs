@@ -1,125 +1,128 @@
-use anyhow::{anyhow, Result};
-use std::env;
-use std::process::Command;
-use std::process::Stdio;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
-use crate::unlock_wallet::unlock_wallet_rpc;
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::amount::{Amount, Unit};
+use crate::discovery;
+use crate::store;
+use crate::unlock_wallet::{self, WalletState};
+use crate::utils::lncli;
+use crate::wallet;
 use crate::MainWindow;
 
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Chain {
+    pub chain: String,
+    pub network: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Feature {
+    pub name: String,
+    pub is_required: bool,
+    pub is_known: bool,
+}
+
+/// `lncli getinfo`, missing fields default so older and newer lnd versions both parse.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct GetInfo {
+    pub version: String,
+    pub commit_hash: String,
+    pub identity_pubkey: String,
+    pub alias: String,
+    pub color: String,
+    pub num_pending_channels: u32,
+    pub num_active_channels: u32,
+    pub num_inactive_channels: u32,
+    pub num_peers: u32,
+    pub block_height: u32,
+    pub block_hash: String,
+    pub best_header_timestamp: String,
+    pub synced_to_chain: bool,
+    pub synced_to_graph: bool,
+    pub chains: Vec<Chain>,
+    pub uris: Vec<String>,
+    /// Keyed by feature bit
+    pub features: BTreeMap<String, Feature>,
+}
+
+impl GetInfo {
+    /// Output without an identity pubkey was cut off or isn't from lnd, and is rejected.
+    pub fn parse(stdout: &[u8]) -> Result<Self> {
+        let info: GetInfo = serde_json::from_slice(stdout).map_err(|e| anyhow!("Unexpected getinfo output: {}", e))?;
+        if info.identity_pubkey.is_empty() {
+            return Err(anyhow!("getinfo did not report an identity pubkey"));
+        }
+        Ok(info)
+    }
+}
+
+/// Asks the node on `network` who it is and stores the answer, so its namespace in the
+/// database can be opened. Called on startup and whenever the network changes.
+pub fn resolve_identity(db: &sled::Db, network: &str) -> Result<String> {
+    let output = lncli(network).arg("getinfo").output()?;
+    if !output.status.success() {
+        return Err(anyhow!("getinfo failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let info = GetInfo::parse(&output.stdout)?;
+    store::set_node_identity(db, network, &info.identity_pubkey)?;
+    Ok(info.identity_pubkey)
+}
+
 #[derive(Clone)]
 pub struct NodeInfo {
     pub running: bool,
-    pub version: String,
     pub synced: bool,
     pub block_height: u64,
     pub network: String,
     pub identity_pubkey: String,
+    /// Everything getinfo reported, default while the node isn't reachable
+    pub info: GetInfo,
 }
 
 pub async fn node_status(network: &str, window_weak: &Arc<slint::Weak<MainWindow>>) -> NodeInfo {
-    // Run lncli command and log results before starting UI
-    let output = if network == "testnet" {
-        Command::new("/usr/local/bin/lncli")
-            .args(["--network", "testnet", "getinfo"])
-            .output()
-    } else {
-        Command::new("/usr/local/bin/lncli")
-            .args(["getinfo"])
-            .output()
-    };
-
-    // Default values for when command fails
-    let mut node_running = false;
-    let mut node_version = String::from("unknown");
-    let mut is_synced = false;
-    let mut block_height = 0;
-    let mut network = String::from("testnet");
-    let mut identity_pubkey = String::from("unknown");
+    let output = lncli(network).arg("getinfo").output();
+    let mut info = None;
 
     let window_weak_clone = window_weak.clone();
 
     match output {
         Ok(output) => {
             if output.status.success() {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                // println!("lncli --network testnet getinfo result:\n{}", stdout);
-
-                node_running = true;
-
-                // Simple parsing of relevant fields
-                if let Some(i) = stdout.find("\"version\":") {
-                    if let Some(j) = stdout[i..].find(",") {
-                        node_version = (&stdout[i + 11..i + j - 1]).trim_matches('"').to_string();
-                    }
-                }
-
-                if let Some(i) = stdout.find("\"block_height\":") {
-                    if let Some(j) = stdout[i..].find(",") {
-                        if let Ok(height) = stdout[i + 15..i + j].trim().parse::<u64>() {
-                            block_height = height;
-                        }
-                    }
-                }
-
-                if let Some(i) = stdout.find("\"synced_to_chain\":") {
-                    is_synced = stdout[i + 17..i + 25].trim().contains("true");
-                }
+                match GetInfo::parse(&output.stdout) {
+                    Ok(parsed) => {
+                        info = Some(parsed);
+
+                        let node_window_weak = window_weak_clone.clone();
+                        let _ = slint::invoke_from_event_loop(move || {
+                            if let Some(window) = node_window_weak.upgrade() {
+                                window.set_wallet_needs_unlock(false);
+                            }
+                        });
 
-                if let Some(i) = stdout.find("\"identity_pubkey\":") {
-                    identity_pubkey = stdout[i + 21..i + 87].to_string();
-                }
-                let node_window_weak = window_weak_clone.clone();
-                let _ = slint::invoke_from_event_loop(move || {
-                    if let Some(window) = node_window_weak.upgrade() {
-                        window.set_wallet_needs_unlock(false);
-                    }
-                });
-
-                let wallet_output = if network == "testnet" {
-                    Command::new("/usr/local/bin/lncli")
-                        .args(["--network", "testnet", "walletbalance"])
-                        .output()
-                } else {
-                    Command::new("/usr/local/bin/lncli")
-                        .args(["walletbalance"])
-                        .output()
-                };
-
-                let mut wallet_balance: i32 = 0;
-                match wallet_output {
-                    Ok(wallet_output) => {
-                        if wallet_output.status.success() {
-                            let stdout = String::from_utf8_lossy(&wallet_output.stdout);
-                            const CONFIRMED_BALANCE_KEY: &str = "\"confirmed_balance\":  \"";
-                            if let Some(key_start_index) = stdout.find(CONFIRMED_BALANCE_KEY) {
-                                let value_start_index = key_start_index + CONFIRMED_BALANCE_KEY.len();
-
-                                let possible_balance = stdout[value_start_index..].trim();
-                                let index_of_quote = possible_balance.find("\"");
-                                let balance_str = &possible_balance[..index_of_quote.unwrap()].trim();
-
-                                if let Ok(balance) = balance_str.parse::<i32>() {
-                                    wallet_balance = balance;
-                                } else {
-                                    println!("Failed to parse confirmed balance from string: '{}'", balance_str);
-                                }
-                            } else {
-                                println!("'confirmed_balance' key not found in walletbalance output.");
+                        let wallet_balance = match wallet::balance(network) {
+                            Ok(balance) => balance.confirmed,
+                            Err(e) => {
+                                println!("Failed to get wallet balance: {}", e);
+                                Amount::ZERO
                             }
-                        }
-                    }
-                    Err(e) => {
-                        println!("Failed to get wallet balance: {}", e);
+                        };
+                        let wallet_window_weak = window_weak_clone.clone();
+                        let _ = slint::invoke_from_event_loop(move || {
+                            if let Some(window) = wallet_window_weak.upgrade() {
+                                let unit = Unit::from_index(window.get_display_unit());
+                                window.set_wallet_balance(wallet_balance.format(unit).into());
+                            }
+                        });
                     }
+                    // lnd answered but we can't read it, so nothing is known about the wallet either
+                    Err(e) => eprintln!("Node status unknown: {}", e),
                 }
-                let wallet_window_weak = window_weak_clone.clone();
-                let _ = slint::invoke_from_event_loop(move || {
-                    if let Some(window) = wallet_window_weak.upgrade() {
-                        window.set_wallet_balance(wallet_balance);
-                    }
-                });
-                
             } else {
                 let stderr = String::from_utf8_lossy(&output.stderr);
                 println!("lncli command failed: {}", stderr);
@@ -130,18 +133,104 @@ pub async fn node_status(network: &str, window_weak: &Arc<slint::Weak<MainWindow
                             window.set_wallet_needs_unlock(true);
                         }
                     });
+                } else if let Ok(WalletState::NonExisting) = unlock_wallet::wallet_state(&discovery::discover(network)).await {
+                    println!("Wallet has not been created yet");
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(window) = window_weak_clone.upgrade() {
+                            // Take a fresh user straight to onboarding, but only once
+                            if !window.get_wallet_needs_init() && window.get_active_page() == -1 {
+                                window.set_active_page(10);
+                            }
+                            window.set_wallet_needs_init(true);
+                        }
+                    });
                 }
             }
         }
         Err(e) => println!("Failed to execute lncli: {}", e),
     }
 
-    NodeInfo {
-        running: node_running,
-        version: node_version,
-        synced: is_synced,
-        block_height,
-        network,
-        identity_pubkey,
+    NodeInfo::from_getinfo(network, info)
+}
+
+impl NodeInfo {
+    /// The status shown for a getinfo answer, or an unknown one when there was none we could read.
+    fn from_getinfo(network: &str, info: Option<GetInfo>) -> Self {
+        let running = info.is_some();
+        let info = info.unwrap_or_default();
+        NodeInfo {
+            running,
+            synced: info.synced_to_chain,
+            block_height: info.block_height.into(),
+            network: network.to_string(),
+            identity_pubkey: if running { info.identity_pubkey.clone() } else { String::from("unknown") },
+            info,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYNCED: &str = r##"{
+        "version": "0.18.3-beta commit=v0.18.3-beta",
+        "commit_hash": "cd8fda3e2f0b5d1b3a8e4bd3e63a4e2fd7e0b8d4",
+        "identity_pubkey": "02b1e5a1c4f3a2d6e8f9b0c7d5e3a1f2b4c6d8e0f1a3b5c7d9e1f3a5b7c9d1e3f5",
+        "alias": "htlc-node",
+        "color": "#3399ff",
+        "num_pending_channels": 1,
+        "num_active_channels": 4,
+        "num_inactive_channels": 0,
+        "num_peers": 6,
+        "block_height": 2873410,
+        "block_hash": "00000000000000129c0e6c1f2b54d0e6a2cd6c23f1c5d7e8a9b0c1d2e3f4a5b6",
+        "best_header_timestamp": "1729300000",
+        "synced_to_chain": true,
+        "synced_to_graph": true,
+        "testnet": false,
+        "chains": [{"chain": "bitcoin", "network": "testnet"}],
+        "uris": ["02b1e5a1c4f3a2d6e8f9b0c7d5e3a1f2b4c6d8e0f1a3b5c7d9e1f3a5b7c9d1e3f5@203.0.113.7:9735"],
+        "features": {
+            "0": {"name": "data-loss-protect", "is_required": true, "is_known": true},
+            "9": {"name": "tlv-onion", "is_required": false, "is_known": true}
+        },
+        "require_htlc_interceptor": false,
+        "store_final_htlc_resolutions": false
+    }"##;
+
+    fn status(stdout: &str) -> NodeInfo {
+        NodeInfo::from_getinfo("testnet", GetInfo::parse(stdout.as_bytes()).ok())
+    }
+
+    #[test]
+    fn reads_a_synced_node() {
+        let node = status(SYNCED);
+        assert!(node.running);
+        assert!(node.synced);
+        assert_eq!(node.block_height, 2873410);
+        assert_eq!(node.identity_pubkey, "02b1e5a1c4f3a2d6e8f9b0c7d5e3a1f2b4c6d8e0f1a3b5c7d9e1f3a5b7c9d1e3f5");
+        assert_eq!(node.info.alias, "htlc-node");
+        assert_eq!(node.info.num_active_channels, 4);
+        assert_eq!(node.info.chains[0].network, "testnet");
+        assert!(node.info.features["0"].is_required);
+    }
+
+    #[test]
+    fn reads_an_unsynced_node() {
+        let node = status(&SYNCED.replace(r#""synced_to_chain": true"#, r#""synced_to_chain": false"#));
+        assert!(node.running);
+        assert!(!node.synced);
+        assert_eq!(node.block_height, 2873410);
+    }
+
+    #[test]
+    fn unreadable_or_partial_output_is_unknown() {
+        for stdout in ["", "[lncli] rpc error: code = Unavailable", &SYNCED[..SYNCED.len() / 2], "{}", r#"{"synced_to_chain": true}"#] {
+            let node = status(stdout);
+            assert!(!node.running, "{:?} read as running", stdout);
+            assert!(!node.synced);
+            assert_eq!(node.identity_pubkey, "unknown");
+        }
     }
 }
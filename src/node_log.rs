@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use anyhow::{Context, Result};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+// Written by the launchd plist, the systemd unit and the built-in supervisor alike
+const LOG_FILES: [(&str, &str); 2] = [
+    ("stdout", "/tmp/com.btc.litd.stdout.log"),
+    ("stderr", "/tmp/com.btc.litd.stderr.log"),
+];
+const MAX_LINES: usize = 5000;
+// Only the tail of an existing file is loaded at startup
+const INITIAL_READ_BYTES: u64 = 512 * 1024;
+
+/// LND log levels from most to least verbose, as they appear in `[INF]`.
+pub const LEVELS: [&str; 6] = ["TRC", "DBG", "INF", "WRN", "ERR", "CRT"];
+
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub source: &'static str,
+    pub timestamp: String,
+    pub level: String,
+    pub subsystem: String,
+    pub message: String,
+}
+
+impl LogLine {
+    /// Splits an LND style line `2024-05-01 12:34:56.789 [INF] LTND: message`.
+    /// Lines that don't follow it (panics, litd's own output) are kept with empty fields.
+    pub fn parse(source: &'static str, raw: &str) -> Self {
+        let unparsed = || LogLine {
+            source,
+            timestamp: String::new(),
+            level: String::new(),
+            subsystem: String::new(),
+            message: raw.to_string(),
+        };
+        let Some(open) = raw.find(" [") else { return unparsed() };
+        let rest = &raw[open + 2..];
+        let Some((level, rest)) = rest.split_once("] ") else { return unparsed() };
+        if !LEVELS.contains(&level) {
+            return unparsed();
+        }
+        let (subsystem, message) = match rest.split_once(": ") {
+            Some((subsystem, message)) if !subsystem.contains(' ') => (subsystem, message),
+            _ => ("", rest),
+        };
+        LogLine {
+            source,
+            timestamp: raw[..open].to_string(),
+            level: level.to_string(),
+            subsystem: subsystem.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn level_index(&self) -> Option<usize> {
+        LEVELS.iter().position(|l| *l == self.level)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    // Index into LEVELS, lines below it are hidden. None shows everything.
+    pub min_level: Option<usize>,
+    pub subsystem: String,
+    pub text: String,
+}
+
+impl LogFilter {
+    pub fn matches(&self, line: &LogLine) -> bool {
+        if let Some(min_level) = self.min_level {
+            // Unparsed lines have no level and are usually stack traces worth seeing
+            if line.level_index().is_some_and(|level| level < min_level) {
+                return false;
+            }
+        }
+        if !self.subsystem.is_empty() && !line.subsystem.eq_ignore_ascii_case(&self.subsystem) {
+            return false;
+        }
+        self.text.is_empty() || line.message.to_lowercase().contains(&self.text.to_lowercase())
+    }
+}
+
+/// Keeps the last lines of the litd logs in memory and follows the files as they grow.
+pub struct LogTail {
+    lines: Arc<Mutex<VecDeque<LogLine>>>,
+    // Dropping the watcher stops the notifications
+    _watcher: RecommendedWatcher,
+}
+
+impl LogTail {
+    /// Loads what's already in the files and calls `on_update` whenever new lines arrive.
+    pub fn start(on_update: impl Fn() + Send + 'static) -> Result<Self> {
+        let lines = Arc::new(Mutex::new(VecDeque::new()));
+        let mut readers: Vec<FileReader> = LOG_FILES.iter().map(|(source, path)| FileReader::new(source, path)).collect();
+        for reader in &mut readers {
+            reader.read_new(&lines, true);
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).context("Failed to create log watcher")?;
+        // Watch the directory so files created or rotated later are still seen
+        let log_dir = Path::new(LOG_FILES[0].1).parent().unwrap_or(Path::new("/tmp"));
+        watcher.watch(log_dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {}", log_dir.display()))?;
+
+        let thread_lines = lines.clone();
+        thread::spawn(move || {
+            for event in receiver {
+                let Ok(event) = event else { continue };
+                let mut changed = false;
+                for reader in &mut readers {
+                    if event.paths.iter().any(|p| p == &reader.path) {
+                        changed |= reader.read_new(&thread_lines, false);
+                    }
+                }
+                if changed {
+                    on_update();
+                }
+            }
+        });
+
+        Ok(Self { lines, _watcher: watcher })
+    }
+
+    /// Up to `limit` of the newest lines matching `filter`, oldest first.
+    pub fn filtered(&self, filter: &LogFilter, limit: usize) -> Vec<LogLine> {
+        let lines = self.lines.lock().unwrap();
+        let mut matched: Vec<LogLine> = lines.iter().rev().filter(|l| filter.matches(l)).take(limit).cloned().collect();
+        matched.reverse();
+        matched
+    }
+
+    /// Lines mentioning `payment_hash`, each with `context` lines before and after it.
+    pub fn around_payment_hash(&self, payment_hash: &str, context: usize) -> Vec<LogLine> {
+        let lines = self.lines.lock().unwrap();
+        let hash = payment_hash.trim().to_lowercase();
+        let mut keep = vec![false; lines.len()];
+        for (index, line) in lines.iter().enumerate() {
+            if line.message.to_lowercase().contains(&hash) {
+                let end = (index + context + 1).min(lines.len());
+                keep[index.saturating_sub(context)..end].iter_mut().for_each(|k| *k = true);
+            }
+        }
+        lines.iter().zip(keep).filter(|(_, keep)| *keep).map(|(line, _)| line.clone()).collect()
+    }
+}
+
+struct FileReader {
+    source: &'static str,
+    path: PathBuf,
+    offset: u64,
+}
+
+impl FileReader {
+    fn new(source: &'static str, path: &str) -> Self {
+        Self { source, path: PathBuf::from(path), offset: 0 }
+    }
+
+    /// Appends lines written since the last read, returns whether there were any.
+    fn read_new(&mut self, lines: &Mutex<VecDeque<LogLine>>, initial: bool) -> bool {
+        let Ok(mut file) = File::open(&self.path) else { return false };
+        let Ok(len) = file.metadata().map(|m| m.len()) else { return false };
+        if len < self.offset {
+            // Truncated or rotated, start over from the top of the new file
+            self.offset = 0;
+        }
+        let skip_partial = initial && len > INITIAL_READ_BYTES;
+        if skip_partial {
+            self.offset = len - INITIAL_READ_BYTES;
+        }
+        if file.seek(SeekFrom::Start(self.offset)).is_err() {
+            return false;
+        }
+
+        let mut reader = BufReader::new(file);
+        let mut new_lines = Vec::new();
+        let mut buffer = String::new();
+        loop {
+            buffer.clear();
+            match reader.read_line(&mut buffer) {
+                // A line without its newline is still being written, read it next time
+                Ok(read) if read > 0 && buffer.ends_with('\n') => {
+                    self.offset += read as u64;
+                    new_lines.push(LogLine::parse(self.source, buffer.trim_end()));
+                }
+                _ => break,
+            }
+        }
+        if skip_partial && !new_lines.is_empty() {
+            new_lines.remove(0);
+        }
+
+        let changed = !new_lines.is_empty();
+        let mut lines = lines.lock().unwrap();
+        lines.extend(new_lines);
+        while lines.len() > MAX_LINES {
+            lines.pop_front();
+        }
+        changed
+    }
+}
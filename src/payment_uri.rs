@@ -0,0 +1,118 @@
+//! Payment codes as they arrive from a QR code or a paste: a bare BOLT11 invoice, a
+//! `lightning:` URI, or a BIP21 `bitcoin:` URI that may carry an invoice in its
+//! `lightning` parameter. Only the shape is checked here, lnd decodes the invoice itself.
+
+use anyhow::{anyhow, Result};
+
+use crate::amount::{Amount, Unit};
+
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaymentCode {
+    /// A BOLT11 invoice, lower case and without any URI prefix.
+    Lightning(String),
+    /// A BIP21 URI with no invoice in it.
+    OnChain { address: String, amount: Option<Amount>, label: String },
+}
+
+pub fn parse(text: &str) -> Result<PaymentCode> {
+    let text = text.trim();
+    if let Some(rest) = strip_scheme(text, "lightning:") {
+        return bolt11(rest.trim_start_matches("//")).map(PaymentCode::Lightning);
+    }
+    if let Some(rest) = strip_scheme(text, "bitcoin:") {
+        return parse_bip21(rest);
+    }
+    bolt11(text)
+        .map(PaymentCode::Lightning)
+        .map_err(|_| anyhow!("'{}' is not a Lightning invoice or a bitcoin: URI", shorten(text)))
+}
+
+/// URI schemes are case-insensitive, and QR codes often carry them in upper case.
+fn strip_scheme<'a>(text: &'a str, scheme: &str) -> Option<&'a str> {
+    text.get(..scheme.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(scheme))
+        .map(|_| &text[scheme.len()..])
+}
+
+fn parse_bip21(rest: &str) -> Result<PaymentCode> {
+    let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (mut amount, mut label, mut invoice) = (None, String::new(), None);
+    for param in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        let value = percent_decode(value);
+        match key.to_ascii_lowercase().as_str() {
+            "amount" => amount = Some(bip21_amount(&value).map_err(|e| anyhow!("Invalid amount in the bitcoin: URI: {}", e))?),
+            "label" => label = value,
+            "lightning" => invoice = Some(bolt11(&value)?),
+            // BIP21 says a wallet must refuse a URI with a req- parameter it doesn't understand
+            key if key.starts_with("req-") => return Err(anyhow!("The bitcoin: URI requires '{}', which we don't support", key)),
+            _ => {}
+        }
+    }
+    if let Some(invoice) = invoice {
+        return Ok(PaymentCode::Lightning(invoice));
+    }
+    if address.is_empty() {
+        return Err(anyhow!("The bitcoin: URI has neither an address nor an invoice"));
+    }
+    Ok(PaymentCode::OnChain { address: address.to_string(), amount, label })
+}
+
+/// BIP21 amounts are plain decimal BTC. Unlike amounts typed into the app there are no unit
+/// suffixes or digit separators, and nothing finer than a satoshi.
+fn bip21_amount(value: &str) -> Result<Amount> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let digits_only = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if !digits_only(whole) || !digits_only(fraction) || (whole.is_empty() && fraction.is_empty()) {
+        return Err(anyhow!("'{}' is not a decimal BTC amount", value));
+    }
+    let amount = Amount::parse(value, Unit::Btc)?;
+    amount.to_sat_exact()?;
+    Ok(amount)
+}
+
+/// Checks `text` has the form of a BOLT11 invoice: an `ln` prefix, the bech32 separator and
+/// a data part in the bech32 alphabet, all in one case.
+fn bolt11(text: &str) -> Result<String> {
+    let invalid = || anyhow!("'{}' is not a valid Lightning invoice", shorten(text));
+    if text != text.to_lowercase() && text != text.to_uppercase() {
+        return Err(invalid());
+    }
+    let invoice = text.to_lowercase();
+    let (prefix, data) = invoice.rsplit_once('1').ok_or_else(invalid)?;
+    if !prefix.starts_with("ln") || data.len() < 6 || !data.chars().all(|c| BECH32_CHARSET.contains(c)) {
+        return Err(invalid());
+    }
+    Ok(invoice)
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Long invoices are cut down for error messages.
+fn shorten(text: &str) -> String {
+    if text.chars().count() > 40 {
+        format!("{}…", text.chars().take(40).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
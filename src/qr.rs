@@ -0,0 +1,1015 @@
+//! QR codes for payment requests, addresses and BIP21 URIs, with PNG and SVG export.
+//!
+//! Codes are encoded by the `qrcode` crate at error correction level M and drawn here. The
+//! decoder reads codes of any level from screenshots and scans: it expects the code roughly
+//! upright or turned by a multiple of 90 degrees, not a photo taken at an angle.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use slint::{Image, Rgb8Pixel, SharedPixelBuffer};
+
+use crate::amount::{Amount, Unit};
+
+/// Light modules around the code, the minimum scanners expect.
+const QUIET_ZONE: usize = 4;
+/// Pixels per module in the views and in exported PNGs.
+const SCREEN_SCALE: usize = 4;
+const PNG_SCALE: usize = 10;
+
+const MAX_VERSION: usize = 40;
+/// Level M leaves room for a smudged print while keeping BOLT11 strings scannable.
+const ENCODE_LEVEL: qrcode::EcLevel = qrcode::EcLevel::M;
+// Indexed by error correction level and version, from table 9 of the standard
+const ECC_CODEWORDS_PER_BLOCK: [[usize; MAX_VERSION + 1]; 4] = [
+    [0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28,
+     28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26,
+     26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+    [0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30,
+     28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28,
+     30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+];
+const NUM_ECC_BLOCKS: [[usize; MAX_VERSION + 1]; 4] = [
+    [0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8,
+     8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25],
+    [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16,
+     17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49],
+    [0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20,
+     23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68],
+    [0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25,
+     25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81],
+];
+const ALPHANUMERIC: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// A square grid of modules, `true` is dark.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QrCode {
+    size: usize,
+    modules: Vec<bool>,
+}
+
+/// BOLT11 is bech32 and case-insensitive. Upper case fits the compact alphanumeric mode,
+/// which keeps the code a few versions smaller and easier to scan.
+pub fn bolt11_payload(payment_request: &str) -> String {
+    let request = payment_request.trim();
+    let request = request.strip_prefix("lightning:").or_else(|| request.strip_prefix("LIGHTNING:")).unwrap_or(request);
+    format!("lightning:{}", request).to_uppercase()
+}
+
+/// A BIP21 `bitcoin:` URI for `address`, with the amount in BTC and a label if given.
+pub fn bip21_uri(address: &str, amount: Option<Amount>, label: &str) -> String {
+    let mut params = Vec::new();
+    if let Some(amount) = amount.filter(|a| !a.is_zero()) {
+        let btc = amount.format(Unit::Btc);
+        params.push(format!("amount={}", btc.trim_end_matches(" BTC").replace(',', "")));
+    }
+    if !label.trim().is_empty() {
+        params.push(format!("label={}", percent_encode(label.trim())));
+    }
+    if params.is_empty() {
+        format!("bitcoin:{}", address.trim())
+    } else {
+        format!("bitcoin:{}?{}", address.trim(), params.join("&"))
+    }
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+        _ => format!("%{:02X}", b),
+    }).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Png,
+    Svg,
+}
+
+impl ExportFormat {
+    /// Index of the save buttons in the QR panel.
+    pub fn from_index(index: i32) -> Self {
+        if index == 1 { ExportFormat::Svg } else { ExportFormat::Png }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "png",
+            ExportFormat::Svg => "svg",
+        }
+    }
+}
+
+/// How much of the code is error correction, as read from a code's format information.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EcLevel {
+    Low,
+    Medium,
+    Quartile,
+    High,
+}
+
+impl EcLevel {
+    const ALL: [EcLevel; 4] = [EcLevel::Low, EcLevel::Medium, EcLevel::Quartile, EcLevel::High];
+
+    /// The two bits the level has in the format information.
+    fn format_bits(self) -> u32 {
+        match self {
+            EcLevel::Low => 1,
+            EcLevel::Medium => 0,
+            EcLevel::Quartile => 3,
+            EcLevel::High => 2,
+        }
+    }
+
+    fn ecc_codewords_per_block(self, version: usize) -> usize {
+        ECC_CODEWORDS_PER_BLOCK[self as usize][version]
+    }
+
+    fn blocks(self, version: usize) -> usize {
+        NUM_ECC_BLOCKS[self as usize][version]
+    }
+}
+
+/// The 15 format bits for `level` and `mask`, BCH protected and masked.
+fn format_word(level: EcLevel, mask: u32) -> u32 {
+    let data = level.format_bits() << 3 | mask;
+    let mut remainder = data;
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    (data << 10 | remainder) ^ 0x5412
+}
+
+/// Segment modes we read, payment codes never use kanji.
+enum Mode {
+    Numeric,
+    Alphanumeric,
+    Byte,
+}
+
+impl Mode {
+    fn indicator(&self) -> u32 {
+        match self {
+            Mode::Numeric => 0b0001,
+            Mode::Alphanumeric => 0b0010,
+            Mode::Byte => 0b0100,
+        }
+    }
+
+    fn from_indicator(indicator: u32) -> Option<Self> {
+        [Mode::Numeric, Mode::Alphanumeric, Mode::Byte].into_iter().find(|mode| mode.indicator() == indicator)
+    }
+
+    fn count_bits(&self, version: usize) -> usize {
+        match (self, version) {
+            (Mode::Numeric, 1..=9) => 10,
+            (Mode::Numeric, 10..=26) => 12,
+            (Mode::Numeric, _) => 14,
+            (Mode::Alphanumeric, 1..=9) => 9,
+            (Mode::Alphanumeric, 10..=26) => 11,
+            (Mode::Alphanumeric, _) => 13,
+            (Mode::Byte, 1..=9) => 8,
+            (Mode::Byte, _) => 16,
+        }
+    }
+
+}
+
+fn raw_data_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let alignments = version / 7 + 2;
+        modules -= (25 * alignments - 10) * alignments - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut product: u16 = 0;
+    for i in (0..8).rev() {
+        product = (product << 1) ^ ((product >> 7) * 0x11D);
+        product ^= ((y >> i) & 1) as u16 * x as u16;
+    }
+    product as u8
+}
+
+/// Data codewords in each block, the last blocks carry one more than the first.
+fn block_data_lengths(version: usize, level: EcLevel) -> Vec<usize> {
+    let blocks = level.blocks(version);
+    let raw_codewords = raw_data_modules(version) / 8;
+    let short_blocks = blocks - raw_codewords % blocks;
+    let short_data_len = raw_codewords / blocks - level.ecc_codewords_per_block(version);
+    (0..blocks).map(|i| short_data_len + usize::from(i >= short_blocks)).collect()
+}
+
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let size = version * 4 + 17;
+    let step = if version == 32 { 26 } else { (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2 };
+    let mut positions: Vec<usize> = (0..count - 1).map(|i| size - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+/// Where the 15 format bits go, least significant first: once around the top left finder,
+/// and split between the other two.
+fn format_positions(size: usize) -> [[(usize, usize); 15]; 2] {
+    let mut first = [(0, 0); 15];
+    let mut second = [(0, 0); 15];
+    for i in 0..15 {
+        first[i] = match i {
+            0..=5 => (8, i),
+            6 => (8, 7),
+            7 => (8, 8),
+            8 => (7, 8),
+            _ => (14 - i, 8),
+        };
+        second[i] = if i < 8 { (size - 1 - i, 8) } else { (8, size - 15 + i) };
+    }
+    [first, second]
+}
+
+fn mask_applies(mask: u32, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+/// Which modules of a `version` code are finder, timing, alignment, format or version
+/// patterns rather than data.
+fn function_modules(version: usize) -> Vec<bool> {
+    let size = version * 4 + 17;
+    let mut function = vec![false; size * size];
+    let mut mark = |x: usize, y: usize| function[y * size + x] = true;
+    for i in 0..size {
+        mark(6, i);
+        mark(i, 6);
+    }
+    // Finders with their separators and the format bits next to them, and the dark module
+    for y in 0..9 {
+        for x in 0..9 {
+            mark(x, y);
+        }
+        for x in size - 8..size {
+            mark(x, y);
+        }
+    }
+    for y in size - 8..size {
+        for x in 0..9 {
+            mark(x, y);
+        }
+    }
+    let positions = alignment_positions(version);
+    let last = positions.len().saturating_sub(1);
+    for (i, &cx) in positions.iter().enumerate() {
+        for (j, &cy) in positions.iter().enumerate() {
+            // Skip the three corners taken by finder patterns
+            let corner = (i == 0 && (j == 0 || j == last)) || (i == last && j == 0);
+            if !corner {
+                for y in cy - 2..=cy + 2 {
+                    for x in cx - 2..=cx + 2 {
+                        mark(x, y);
+                    }
+                }
+            }
+        }
+    }
+    if version >= 7 {
+        for a in size - 11..size - 8 {
+            for b in 0..6 {
+                mark(a, b);
+                mark(b, a);
+            }
+        }
+    }
+    function
+}
+
+/// Modules that carry codeword bits, in the two-column zigzag from the bottom right corner.
+fn data_positions(version: usize) -> Vec<usize> {
+    let size = version * 4 + 17;
+    let function = function_modules(version);
+    let mut positions = Vec::new();
+    let mut right = size as i32 - 1;
+    while right >= 1 {
+        if right == 6 {
+            right = 5;
+        }
+        for vert in 0..size {
+            for j in 0..2 {
+                let x = right as usize - j;
+                let upward = (right + 1) & 2 == 0;
+                let y = if upward { size - 1 - vert } else { vert };
+                if !function[y * size + x] {
+                    positions.push(y * size + x);
+                }
+            }
+        }
+        right -= 2;
+    }
+    positions
+}
+
+impl QrCode {
+    /// Encodes `text` in the smallest version that fits, in the most compact modes its
+    /// characters allow.
+    pub fn encode(text: &str) -> Result<Self> {
+        if text.is_empty() {
+            return Err(anyhow!("Nothing to encode"));
+        }
+        let code = qrcode::QrCode::with_error_correction_level(text, ENCODE_LEVEL)
+            .map_err(|e| anyhow!("Could not encode {} characters as a QR code: {}", text.len(), e))?;
+        Ok(QrCode::from_modules(&code))
+    }
+
+    fn from_modules(code: &qrcode::QrCode) -> Self {
+        let modules = code.to_colors().into_iter().map(|color| color == qrcode::Color::Dark).collect();
+        QrCode { size: code.width(), modules }
+    }
+
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        x < self.size && y < self.size && self.modules[y * self.size + x]
+    }
+
+    /// Grey levels, one byte per pixel, with `scale` pixels per module and the quiet zone.
+    fn pixels(&self, scale: usize) -> (usize, Vec<u8>) {
+        let side = (self.size + 2 * QUIET_ZONE) * scale;
+        let mut pixels = vec![0xFF; side * side];
+        for y in 0..side {
+            for x in 0..side {
+                let (mx, my) = ((x / scale).wrapping_sub(QUIET_ZONE), (y / scale).wrapping_sub(QUIET_ZONE));
+                if self.is_dark(mx, my) {
+                    pixels[y * side + x] = 0;
+                }
+            }
+        }
+        (side, pixels)
+    }
+
+    /// The code as an image for the views.
+    pub fn to_image(&self) -> Image {
+        let (side, grey) = self.pixels(SCREEN_SCALE);
+        let rgb: Vec<u8> = grey.into_iter().flat_map(|v| [v, v, v]).collect();
+        Image::from_rgb8(SharedPixelBuffer::<Rgb8Pixel>::clone_from_slice(&rgb, side as u32, side as u32))
+    }
+
+    pub fn to_svg(&self) -> String {
+        let side = self.size + 2 * QUIET_ZONE;
+        let mut path = String::new();
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if self.is_dark(x, y) {
+                    path.push_str(&format!("M{},{}h1v1h-1z", x + QUIET_ZONE, y + QUIET_ZONE));
+                }
+            }
+        }
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {side} {side}\" shape-rendering=\"crispEdges\">\n\
+             <rect width=\"{side}\" height=\"{side}\" fill=\"#ffffff\"/>\n\
+             <path d=\"{path}\" fill=\"#000000\"/>\n\
+             </svg>\n"
+        )
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let (side, grey) = self.pixels(PNG_SCALE);
+        let image = image::GrayImage::from_raw(side as u32, side as u32, grey)
+            .ok_or_else(|| anyhow!("QR image buffer has the wrong size"))?;
+        let mut png = Vec::new();
+        image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+        Ok(png)
+    }
+}
+
+/// Writes the QR code for `payload` to `dir` as `<name>.png` or `.svg` and returns the path.
+pub fn export(payload: &str, name: &str, format: ExportFormat, dir: &Path) -> Result<PathBuf> {
+    let code = QrCode::encode(payload)?;
+    let contents = match format {
+        ExportFormat::Png => code.to_png()?,
+        ExportFormat::Svg => code.to_svg().into_bytes(),
+    };
+    std::fs::create_dir_all(dir).map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}.{}", name, format.extension()));
+    std::fs::write(&path, contents).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Reads the QR code in a PNG or JPEG file and returns the text it carries.
+pub fn decode_file(path: &Path) -> Result<String> {
+    let image = image::open(path)
+        .map_err(|e| anyhow!("Could not open {} as an image: {}", path.display(), e))?
+        .to_luma8();
+    decode_image(&image)
+}
+
+/// Finds and reads a QR code in a greyscale image. Codes drawn light on dark are only
+/// tried once the normal reading fails.
+fn decode_image(image: &image::GrayImage) -> Result<String> {
+    let bitmap = Bitmap::binarize(image);
+    let mut error = anyhow!("No QR code found in the image");
+    for bitmap in [bitmap.inverted(), bitmap].into_iter().rev() {
+        for [top_left, top_right, bottom_left] in finder_triples(&bitmap.finder_patterns()) {
+            let module = (top_left.module + top_right.module + bottom_left.module) / 3.0;
+            let across = (top_left.distance(&top_right) + top_left.distance(&bottom_left)) / 2.0 / module + 7.0;
+            let estimate = ((across - 17.0) / 4.0).round() as i64;
+            for version in [estimate, estimate - 1, estimate + 1] {
+                if !(1..=MAX_VERSION as i64).contains(&version) {
+                    continue;
+                }
+                let version = version as usize;
+                match bitmap.sample(&top_left, &top_right, &bottom_left, version).read_text(version) {
+                    Ok(text) => return Ok(text),
+                    Err(e) => error = e,
+                }
+            }
+        }
+    }
+    Err(error)
+}
+
+/// Black and white pixels, `true` is dark.
+#[derive(Clone)]
+struct Bitmap {
+    width: usize,
+    height: usize,
+    dark: Vec<bool>,
+}
+
+/// Centre of a finder pattern in pixels, the size of one module and how many scan lines hit it.
+#[derive(Debug, Clone, Copy)]
+struct Finder {
+    x: f64,
+    y: f64,
+    module: f64,
+    hits: usize,
+}
+
+impl Finder {
+    fn distance(&self, other: &Finder) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+}
+
+impl Bitmap {
+    fn binarize(image: &image::GrayImage) -> Self {
+        let threshold = otsu_threshold(image);
+        Bitmap {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            dark: image.pixels().map(|p| p.0[0] <= threshold).collect(),
+        }
+    }
+
+    fn inverted(&self) -> Self {
+        Bitmap { dark: self.dark.iter().map(|d| !d).collect(), ..*self }
+    }
+
+    fn at(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.dark[y * self.width + x]
+    }
+
+    /// Runs of dark, light, dark, light, dark in the 1:1:3:1:1 ratio of a finder pattern, found
+    /// along every row and confirmed down the column and back across the row through its centre.
+    fn finder_patterns(&self) -> Vec<Finder> {
+        let mut finders: Vec<Finder> = Vec::new();
+        for y in 0..self.height {
+            let mut runs: Vec<(usize, usize, bool)> = Vec::new();
+            for x in 0..self.width {
+                match runs.last_mut() {
+                    Some((_, len, dark)) if *dark == self.at(x, y) => *len += 1,
+                    _ => runs.push((x, 1, self.at(x, y))),
+                }
+            }
+            for window in runs.windows(5).filter(|w| w[0].2) {
+                let lengths = [window[0].1, window[1].1, window[2].1, window[3].1, window[4].1];
+                if !finder_ratio(&lengths) {
+                    continue;
+                }
+                let column = window[2].0 + window[2].1 / 2;
+                let Some((cy, vertical)) = cross_check(self.height, y, |i| self.at(column, i)) else { continue };
+                let Some((cx, horizontal)) = cross_check(self.width, column, |i| self.at(i, cy as usize)) else { continue };
+                let module = (vertical + horizontal) / 2.0;
+                let same = |f: &&mut Finder| {
+                    (f.x - cx).abs() <= f.module && (f.y - cy).abs() <= f.module && (f.module - module).abs() <= f.module / 2.0
+                };
+                if let Some(finder) = finders.iter_mut().find(same) {
+                    let hits = finder.hits as f64;
+                    finder.x = (finder.x * hits + cx) / (hits + 1.0);
+                    finder.y = (finder.y * hits + cy) / (hits + 1.0);
+                    finder.module = (finder.module * hits + module) / (hits + 1.0);
+                    finder.hits += 1;
+                } else {
+                    finders.push(Finder { x: cx, y: cy, module, hits: 1 });
+                }
+            }
+        }
+        finders.sort_by_key(|f| std::cmp::Reverse(f.hits));
+        finders
+    }
+
+    /// Reads the module grid of `version` by mapping module centres between the finder centres.
+    fn sample(&self, top_left: &Finder, top_right: &Finder, bottom_left: &Finder, version: usize) -> QrCode {
+        let size = version * 4 + 17;
+        let span = (size - 7) as f64;
+        let modules = (0..size * size).map(|i| {
+            // Finder centres are the middle of module 3 from each edge
+            let u = ((i % size) as f64 - 3.0) / span;
+            let v = ((i / size) as f64 - 3.0) / span;
+            let x = top_left.x + u * (top_right.x - top_left.x) + v * (bottom_left.x - top_left.x);
+            let y = top_left.y + u * (top_right.y - top_left.y) + v * (bottom_left.y - top_left.y);
+            x >= 0.0 && y >= 0.0 && self.at(x as usize, y as usize)
+        }).collect();
+        QrCode { size, modules }
+    }
+}
+
+/// Threshold that best splits the histogram into dark and light, by Otsu's method.
+fn otsu_threshold(image: &image::GrayImage) -> u8 {
+    let mut histogram = [0usize; 256];
+    for pixel in image.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let total = (image.width() * image.height()) as f64;
+    let sum: f64 = histogram.iter().enumerate().map(|(value, &count)| (value * count) as f64).sum();
+    let (mut dark_count, mut dark_sum, mut best, mut threshold) = (0.0, 0.0, 0.0, 0);
+    for (value, &count) in histogram.iter().enumerate() {
+        dark_count += count as f64;
+        dark_sum += (value * count) as f64;
+        let light_count = total - dark_count;
+        if dark_count == 0.0 || light_count == 0.0 {
+            continue;
+        }
+        let difference = dark_sum / dark_count - (sum - dark_sum) / light_count;
+        let between = dark_count * light_count * difference * difference;
+        if between > best {
+            best = between;
+            threshold = value as u8;
+        }
+    }
+    threshold
+}
+
+fn finder_ratio(runs: &[usize; 5]) -> bool {
+    let total: usize = runs.iter().sum();
+    if total < 7 {
+        return false;
+    }
+    let module = total as f64 / 7.0;
+    runs.iter().zip([1.0, 1.0, 3.0, 1.0, 1.0]).all(|(&run, ratio)| (run as f64 - module * ratio).abs() < module * ratio / 2.0)
+}
+
+/// Looks for a finder pattern through `at` on a line of `len` pixels and returns the centre
+/// of its middle run and the module size.
+fn cross_check(len: usize, at: usize, dark: impl Fn(usize) -> bool) -> Option<(f64, f64)> {
+    if at >= len || !dark(at) {
+        return None;
+    }
+    let mut runs = [0; 5];
+    let (mut start, mut end) = (at, at + 1);
+    while start > 0 && dark(start - 1) {
+        start -= 1;
+    }
+    while end < len && dark(end) {
+        end += 1;
+    }
+    runs[2] = end - start;
+    let mut before = start;
+    while before > 0 && !dark(before - 1) {
+        before -= 1;
+        runs[1] += 1;
+    }
+    while before > 0 && dark(before - 1) {
+        before -= 1;
+        runs[0] += 1;
+    }
+    let mut after = end;
+    while after < len && !dark(after) {
+        after += 1;
+        runs[3] += 1;
+    }
+    while after < len && dark(after) {
+        after += 1;
+        runs[4] += 1;
+    }
+    finder_ratio(&runs).then(|| (start as f64 + runs[2] as f64 / 2.0, runs.iter().sum::<usize>() as f64 / 7.0))
+}
+
+/// Groups of three finders ordered top left, top right, bottom left, most plausible first:
+/// the top left finder sits at a right angle between two arms of about equal length.
+fn finder_triples(finders: &[Finder]) -> Vec<[Finder; 3]> {
+    let candidates = &finders[..finders.len().min(8)];
+    let mut triples = Vec::new();
+    for i in 0..candidates.len() {
+        for j in i + 1..candidates.len() {
+            for k in j + 1..candidates.len() {
+                let group = [candidates[i], candidates[j], candidates[k]];
+                let smallest = group.iter().map(|f| f.module).fold(f64::MAX, f64::min);
+                let largest = group.iter().map(|f| f.module).fold(0.0, f64::max);
+                if largest > smallest * 1.5 {
+                    continue;
+                }
+                // The corner is opposite the longest side
+                let corner = (0..3)
+                    .max_by(|&a, &b| {
+                        let opposite = |c: usize| group[(c + 1) % 3].distance(&group[(c + 2) % 3]);
+                        opposite(a).total_cmp(&opposite(b))
+                    })
+                    .unwrap_or(0);
+                let (top_left, mut first, mut second) = (group[corner], group[(corner + 1) % 3], group[(corner + 2) % 3]);
+                let (first_x, first_y) = (first.x - top_left.x, first.y - top_left.y);
+                let (second_x, second_y) = (second.x - top_left.x, second.y - top_left.y);
+                let (first_len, second_len) = (first_x.hypot(first_y), second_x.hypot(second_y));
+                if first_len < 7.0 * smallest || second_len < 7.0 * smallest {
+                    continue;
+                }
+                let cosine = (first_x * second_x + first_y * second_y) / (first_len * second_len);
+                let ratio = first_len / second_len;
+                if cosine.abs() > 0.25 || !(0.75..=1.33).contains(&ratio) {
+                    continue;
+                }
+                // With y pointing down, top right comes before bottom left turning clockwise
+                if first_x * second_y - first_y * second_x < 0.0 {
+                    std::mem::swap(&mut first, &mut second);
+                }
+                triples.push((cosine.abs() + (1.0 - ratio).abs(), [top_left, first, second]));
+            }
+        }
+    }
+    triples.sort_by(|a, b| a.0.total_cmp(&b.0));
+    triples.into_iter().map(|(_, triple)| triple).collect()
+}
+
+impl QrCode {
+    /// Error correction level and mask from whichever copy of the format bits reads closest
+    /// to a valid word, allowing three wrong bits.
+    fn read_format(&self) -> Result<(EcLevel, u32)> {
+        let copies = format_positions(self.size).map(|positions| {
+            positions.iter().enumerate().fold(0u32, |word, (i, &(x, y))| word | u32::from(self.is_dark(x, y)) << i)
+        });
+        let mut best: Option<(u32, EcLevel, u32)> = None;
+        for level in EcLevel::ALL {
+            for mask in 0..8 {
+                let word = format_word(level, mask);
+                for copy in copies {
+                    let distance = (word ^ copy).count_ones();
+                    if best.is_none_or(|(closest, _, _)| distance < closest) {
+                        best = Some((distance, level, mask));
+                    }
+                }
+            }
+        }
+        match best {
+            Some((distance, level, mask)) if distance <= 3 => Ok((level, mask)),
+            _ => Err(anyhow!("Could not read the QR code's format information")),
+        }
+    }
+
+    /// Unmasks the grid as `version`, corrects it and parses the data segments.
+    fn read_text(&self, version: usize) -> Result<String> {
+        let (level, mask) = self.read_format()?;
+        let mut codewords = vec![0u8; raw_data_modules(version) / 8];
+        let positions = data_positions(version);
+        for (i, &index) in positions.iter().take(codewords.len() * 8).enumerate() {
+            if self.modules[index] != mask_applies(mask, index % self.size, index / self.size) {
+                codewords[i >> 3] |= 0x80 >> (i & 7);
+            }
+        }
+        let data = deinterleave_and_correct(&codewords, version, level)?;
+        parse_segments(&data, version)
+    }
+}
+
+/// Splits the interleaved codewords back into blocks, corrects each and returns the data codewords.
+fn deinterleave_and_correct(codewords: &[u8], version: usize, level: EcLevel) -> Result<Vec<u8>> {
+    let ecc_len = level.ecc_codewords_per_block(version);
+    let data_lengths = block_data_lengths(version, level);
+    let mut blocks: Vec<Vec<u8>> = data_lengths.iter().map(|len| Vec::with_capacity(len + ecc_len)).collect();
+    let mut next = codewords.iter().copied();
+    for i in 0..data_lengths.last().copied().unwrap_or(0) {
+        for (block, &len) in blocks.iter_mut().zip(&data_lengths) {
+            if i < len {
+                block.extend(next.next());
+            }
+        }
+    }
+    for _ in 0..ecc_len {
+        for block in blocks.iter_mut() {
+            block.extend(next.next());
+        }
+    }
+    let mut data = Vec::new();
+    for (mut block, len) in blocks.into_iter().zip(data_lengths) {
+        rs_correct(&mut block, ecc_len)?;
+        data.extend_from_slice(&block[..len]);
+    }
+    Ok(data)
+}
+
+/// Powers of 2 in GF(256) and their logarithms.
+fn gf_tables() -> ([u8; 255], [usize; 256]) {
+    let (mut exp, mut log) = ([0u8; 255], [0usize; 256]);
+    let mut value = 1u8;
+    for (power, entry) in exp.iter_mut().enumerate() {
+        *entry = value;
+        log[value as usize] = power;
+        value = gf_multiply(value, 2);
+    }
+    (exp, log)
+}
+
+/// Corrects up to `ecc_len / 2` wrong codewords of a block in place: Berlekamp-Massey finds
+/// the error locator, its roots the positions and Forney's formula the values.
+fn rs_correct(block: &mut [u8], ecc_len: usize) -> Result<()> {
+    let too_damaged = || anyhow!("The QR code is too damaged to read");
+    let (exp, log) = gf_tables();
+    let inverse = |value: u8| exp[(255 - log[value as usize]) % 255];
+    // Polynomials are lowest degree first, the block is highest degree first
+    let evaluate = |poly: &[u8], x: u8| poly.iter().rev().fold(0u8, |acc, &c| gf_multiply(acc, x) ^ c);
+    let syndromes = |block: &[u8]| -> Vec<u8> {
+        (0..ecc_len).map(|j| block.iter().fold(0u8, |acc, &b| gf_multiply(acc, exp[j % 255]) ^ b)).collect()
+    };
+    let syndrome = syndromes(block);
+    if syndrome.iter().all(|&s| s == 0) {
+        return Ok(());
+    }
+
+    let (mut locator, mut previous) = (vec![1u8], vec![1u8]);
+    let (mut errors, mut shift, mut previous_discrepancy) = (0, 1, 1u8);
+    for r in 0..ecc_len {
+        let discrepancy = (1..=errors)
+            .fold(syndrome[r], |acc, i| acc ^ gf_multiply(locator.get(i).copied().unwrap_or(0), syndrome[r - i]));
+        if discrepancy == 0 {
+            shift += 1;
+            continue;
+        }
+        let factor = gf_multiply(discrepancy, inverse(previous_discrepancy));
+        let mut updated = locator.clone();
+        updated.resize(updated.len().max(previous.len() + shift), 0);
+        for (i, &coefficient) in previous.iter().enumerate() {
+            updated[i + shift] ^= gf_multiply(factor, coefficient);
+        }
+        if 2 * errors <= r {
+            previous = std::mem::replace(&mut locator, updated);
+            errors = r + 1 - errors;
+            previous_discrepancy = discrepancy;
+            shift = 1;
+        } else {
+            locator = updated;
+            shift += 1;
+        }
+    }
+    if 2 * errors > ecc_len {
+        return Err(too_damaged());
+    }
+
+    // Byte i is the coefficient of x^(n - 1 - i), it is wrong if the locator has a root at the inverse of that power of 2
+    let n = block.len();
+    let power = |i: usize| (n - 1 - i) % 255;
+    let wrong: Vec<usize> = (0..n).filter(|&i| evaluate(&locator, exp[(255 - power(i)) % 255]) == 0).collect();
+    if wrong.len() != errors {
+        return Err(too_damaged());
+    }
+    let mut evaluator = vec![0u8; ecc_len];
+    for (i, &s) in syndrome.iter().enumerate() {
+        for (j, &l) in locator.iter().enumerate().take(ecc_len - i) {
+            evaluator[i + j] ^= gf_multiply(s, l);
+        }
+    }
+    // The formal derivative keeps the odd powers
+    let derivative: Vec<u8> = locator.iter().enumerate().skip(1).map(|(i, &c)| if i % 2 == 1 { c } else { 0 }).collect();
+    for i in wrong {
+        let x_inverse = exp[(255 - power(i)) % 255];
+        let denominator = evaluate(&derivative, x_inverse);
+        if denominator == 0 {
+            return Err(too_damaged());
+        }
+        block[i] ^= gf_multiply(gf_multiply(exp[power(i)], evaluate(&evaluator, x_inverse)), inverse(denominator));
+    }
+    if syndromes(block).iter().any(|&s| s != 0) {
+        return Err(too_damaged());
+    }
+    Ok(())
+}
+
+/// Bits read most significant first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.position
+    }
+
+    fn read(&mut self, len: usize) -> Result<u32> {
+        if len > self.remaining() {
+            return Err(anyhow!("The QR code's data ends early"));
+        }
+        let mut value = 0;
+        for _ in 0..len {
+            value = value << 1 | u32::from((self.data[self.position >> 3] >> (7 - (self.position & 7))) & 1);
+            self.position += 1;
+        }
+        Ok(value)
+    }
+}
+
+/// Concatenates the numeric, alphanumeric and byte segments up to the terminator. ECI
+/// designators are skipped, the text is taken to be UTF-8 whatever they say.
+fn parse_segments(data: &[u8], version: usize) -> Result<String> {
+    let mut reader = BitReader { data, position: 0 };
+    let mut text = Vec::new();
+    let alphanumeric = |value: u32| ALPHANUMERIC.get(value as usize).copied().ok_or_else(|| anyhow!("Invalid alphanumeric data in the QR code"));
+    while reader.remaining() >= 4 {
+        let indicator = reader.read(4)?;
+        if indicator == 0 {
+            break;
+        }
+        if indicator == 0b0111 {
+            let first = reader.read(8)?;
+            if first & 0x80 != 0 {
+                reader.read(if first & 0x40 == 0 { 8 } else { 16 })?;
+            }
+            continue;
+        }
+        let mode = Mode::from_indicator(indicator).ok_or_else(|| anyhow!("The QR code uses a data mode we can't read"))?;
+        let mut count = reader.read(mode.count_bits(version))? as usize;
+        match mode {
+            Mode::Numeric => while count > 0 {
+                let digits = count.min(3);
+                let value = reader.read([0, 4, 7, 10][digits])?;
+                text.extend(format!("{:0width$}", value, width = digits).bytes());
+                count -= digits;
+            },
+            Mode::Alphanumeric => while count > 0 {
+                if count >= 2 {
+                    let value = reader.read(11)?;
+                    text.extend([alphanumeric(value / 45)?, alphanumeric(value % 45)?]);
+                    count -= 2;
+                } else {
+                    text.push(alphanumeric(reader.read(6)?)?);
+                    count -= 1;
+                }
+            },
+            Mode::Byte => for _ in 0..count {
+                text.push(reader.read(8)? as u8);
+            },
+        }
+    }
+    String::from_utf8(text).map_err(|_| anyhow!("The QR code does not contain text"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_at(text: &str, level: EcLevel) -> QrCode {
+        let level = match level {
+            EcLevel::Low => qrcode::EcLevel::L,
+            EcLevel::Medium => qrcode::EcLevel::M,
+            EcLevel::Quartile => qrcode::EcLevel::Q,
+            EcLevel::High => qrcode::EcLevel::H,
+        };
+        QrCode::from_modules(&qrcode::QrCode::with_error_correction_level(text, level).unwrap())
+    }
+
+    #[test]
+    fn format_words() {
+        assert_eq!(format_word(EcLevel::Medium, 0), 0b101010000010010);
+        assert_eq!(format_word(EcLevel::Low, 4), 0b110011000101111);
+        assert_eq!(format_word(EcLevel::Quartile, 0), 0b011010101011111);
+        assert_eq!(format_word(EcLevel::High, 7), 0b000100000111011);
+    }
+
+    #[test]
+    fn data_capacity() {
+        let capacities = [
+            (1, [19, 16, 13, 9]),
+            (10, [274, 216, 154, 122]),
+            (20, [861, 669, 485, 385]),
+            (30, [1735, 1373, 985, 745]),
+            (40, [2956, 2334, 1666, 1276]),
+        ];
+        for (version, expected) in capacities {
+            for (level, bytes) in EcLevel::ALL.into_iter().zip(expected) {
+                assert_eq!(block_data_lengths(version, level).iter().sum::<usize>(), bytes, "version {} {:?}", version, level);
+            }
+            // Every module is either a function pattern or carries a bit, the rest are remainder bits
+            assert_eq!(data_positions(version).len(), raw_data_modules(version), "version {}", version);
+        }
+    }
+
+    #[test]
+    fn encodes_at_level_m_up_to_version_40() {
+        assert_eq!(QrCode::encode("HELLO WORLD").unwrap().size, 21);
+        assert_eq!(QrCode::encode(&"a".repeat(2331)).unwrap().size, 177);
+        assert!(QrCode::encode(&"a".repeat(2332)).is_err());
+        assert!(QrCode::encode("").is_err());
+    }
+
+    #[test]
+    fn bolt11_is_written_in_upper_case() {
+        assert_eq!(bolt11_payload("lightning:lntb1abc"), "LIGHTNING:LNTB1ABC");
+    }
+
+    fn render(code: &QrCode, scale: usize) -> image::GrayImage {
+        let (side, pixels) = code.pixels(scale);
+        image::GrayImage::from_raw(side as u32, side as u32, pixels).unwrap()
+    }
+
+    /// Lower case text, so it is encoded in byte mode.
+    fn sample_text(len: usize) -> String {
+        "lightning:lnbc2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypq".chars().cycle().take(len).collect()
+    }
+
+    #[test]
+    fn round_trip_across_versions_and_levels() {
+        for level in EcLevel::ALL {
+            for len in [1, 17, 60, 150, 400, 900] {
+                let text = sample_text(len);
+                let code = encode_at(&text, level);
+                assert_eq!(decode_image(&render(&code, 3)).unwrap(), text, "{} bytes at {:?}", len, level);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_numeric_and_alphanumeric() {
+        for text in ["0123456789".repeat(12), bolt11_payload(&sample_text(200)), format!("ABC{}xyz", "0123456789".repeat(4))] {
+            let code = QrCode::encode(&text).unwrap();
+            assert_eq!(decode_image(&render(&code, 2)).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn corrects_damaged_modules() {
+        for level in EcLevel::ALL {
+            let text = sample_text(80);
+            let mut code = encode_at(&text, level);
+            // A 3x3 smudge in the data region touches at most three codewords
+            for y in code.size - 3..code.size {
+                for x in code.size - 3..code.size {
+                    code.modules[y * code.size + x] ^= true;
+                }
+            }
+            assert_eq!(decode_image(&render(&code, 3)).unwrap(), text, "{:?}", level);
+        }
+    }
+
+    #[test]
+    fn reads_rotated_inverted_and_resized_codes() {
+        let text = sample_text(120);
+        let image = render(&QrCode::encode(&text).unwrap(), 4);
+        assert_eq!(decode_image(&image::imageops::rotate90(&image)).unwrap(), text);
+        assert_eq!(decode_image(&image::imageops::rotate180(&image)).unwrap(), text);
+
+        let mut inverted = image.clone();
+        image::imageops::invert(&mut inverted);
+        assert_eq!(decode_image(&inverted).unwrap(), text);
+
+        // Not a whole number of pixels per module, and off centre on a bigger canvas
+        let side = (image.width() as f64 * 1.37) as u32;
+        let resized = image::imageops::resize(&image, side, side, image::imageops::FilterType::Triangle);
+        let mut canvas = image::GrayImage::from_pixel(side + 90, side + 40, image::Luma([255]));
+        image::imageops::overlay(&mut canvas, &resized, 60, 15);
+        assert_eq!(decode_image(&canvas).unwrap(), text);
+    }
+
+    #[test]
+    fn reads_jpeg_and_exported_png_files() {
+        let text = sample_text(100);
+        let dir = tempfile::tempdir().unwrap();
+
+        let jpeg_path = dir.path().join("code.jpg");
+        render(&QrCode::encode(&text).unwrap(), 5).save(&jpeg_path).unwrap();
+        assert_eq!(decode_file(&jpeg_path).unwrap(), text);
+
+        let png_path = export(&text, "code", ExportFormat::Png, dir.path()).unwrap();
+        assert_eq!(decode_file(&png_path).unwrap(), text);
+    }
+
+    #[test]
+    fn rejects_images_without_a_code() {
+        let blank = image::GrayImage::from_pixel(200, 200, image::Luma([255]));
+        assert!(decode_image(&blank).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use crate::amount::Amount;
+use crate::channels::{self, ActiveChannelInfo};
+use crate::utils::lncli;
+
+pub struct RebalanceOutcome {
+    pub payment_hash: String,
+    pub fee: Amount,
+}
+
+/// Moves `amount` out through `outgoing_chan_id` and back in through `incoming_chan_id`
+/// by paying an invoice to ourselves along a circular route.
+/// `progress` is called with a human readable message at every step.
+pub fn rebalance<F>(
+    network: &str,
+    outgoing_chan_id: &str,
+    incoming_chan_id: &str,
+    amount: Amount,
+    max_fee: Amount,
+    progress: F,
+) -> Result<RebalanceOutcome>
+where
+    F: Fn(String),
+{
+    if outgoing_chan_id == incoming_chan_id {
+        return Err(anyhow!("Outgoing and incoming channel must be different"));
+    }
+    if amount.is_zero() {
+        return Err(anyhow!("Amount must be greater than zero"));
+    }
+
+    progress("Checking channel balances...".to_string());
+    let active_channels = channels::list_active_channels(network)?;
+    let outgoing = find_channel(&active_channels, outgoing_chan_id)?;
+    let incoming = find_channel(&active_channels, incoming_chan_id)?;
+
+    let needed = amount.checked_add(max_fee).ok_or_else(|| anyhow!("Amount plus max fee is too large"))?;
+    if outgoing.local_balance < needed {
+        return Err(anyhow!(
+            "Outgoing channel {} only has {} local balance, {} needed including max fee",
+            outgoing_chan_id, outgoing.local_balance, needed
+        ));
+    }
+    if incoming.remote_balance < amount {
+        return Err(anyhow!(
+            "Incoming channel {} only has {} remote balance, {} needed",
+            incoming_chan_id, incoming.remote_balance, amount
+        ));
+    }
+
+    progress(format!("Creating invoice to ourselves for {}...", amount));
+    let memo = format!("Rebalance {} -> {}", outgoing_chan_id, incoming_chan_id);
+    let invoice_output = lncli(network)
+        .args(["addinvoice", "--amt_msat", &amount.as_msat().to_string(), "--memo", &memo, "--expiry", "600"])
+        .output()?;
+    if !invoice_output.status.success() {
+        let stderr = String::from_utf8_lossy(&invoice_output.stderr);
+        return Err(anyhow!("Failed to create rebalance invoice: {}", stderr));
+    }
+    let invoice: Value = serde_json::from_slice(&invoice_output.stdout)
+        .map_err(|e| anyhow!("Failed to parse addinvoice JSON: {}", e))?;
+    let payment_request = invoice["payment_request"].as_str()
+        .ok_or_else(|| anyhow!("No payment_request found in response"))?;
+    let payment_hash = invoice["r_hash"].as_str().unwrap_or_default().to_string();
+
+    progress(format!(
+        "Paying out via {} and back in via {} (max fee {})...",
+        outgoing_chan_id, incoming_chan_id, max_fee
+    ));
+    let pay_output = lncli(network)
+        .args([
+            "payinvoice",
+            "--pay_req", payment_request,
+            "--outgoing_chan_id", outgoing_chan_id,
+            "--last_hop", &incoming.remote_pubkey,
+            // Whole sats only, rounded down so the limit is never exceeded
+            "--fee_limit", &max_fee.to_sat_floor().to_string(),
+            "--allow_self_payment",
+            "--force",
+            "--json",
+        ])
+        .output()?;
+
+    // With --json lncli prints one JSON object per payment update, the last one is final
+    let final_update = serde_json::Deserializer::from_slice(&pay_output.stdout)
+        .into_iter::<Value>()
+        .filter_map(|update| update.ok())
+        .last();
+
+    match final_update {
+        Some(update) if update["status"].as_str() == Some("SUCCEEDED") => {
+            let fee = Amount::from_json_msat(&update["fee_msat"]);
+            progress(format!("Rebalance succeeded, paid {} in fees.", fee));
+            Ok(RebalanceOutcome { payment_hash, fee })
+        }
+        other => {
+            let reason = other
+                .and_then(|update| update["failure_reason"].as_str().map(|r| r.to_string()))
+                .unwrap_or_else(|| String::from_utf8_lossy(&pay_output.stderr).trim().to_string());
+            progress(format!("Payment failed: {}. Canceling rebalance invoice...", reason));
+            cancel_invoice(network, &payment_hash);
+            Err(anyhow!("Rebalance payment failed: {}", reason))
+        }
+    }
+}
+
+fn find_channel<'a>(channels: &'a [ActiveChannelInfo], chan_id: &str) -> Result<&'a ActiveChannelInfo> {
+    let channel = channels.iter()
+        .find(|c| c.channel_id == chan_id)
+        .ok_or_else(|| anyhow!("Channel {} not found among active channels", chan_id))?;
+    if !channel.active {
+        return Err(anyhow!("Channel {} is not active", chan_id));
+    }
+    Ok(channel)
+}
+
+fn cancel_invoice(network: &str, payment_hash: &str) {
+    match lncli(network).args(["cancelinvoice", payment_hash]).output() {
+        Ok(output) if output.status.success() => println!("Canceled rebalance invoice {}", payment_hash),
+        Ok(output) => println!("Failed to cancel rebalance invoice {}: {}", payment_hash, String::from_utf8_lossy(&output.stderr)),
+        Err(e) => println!("Failed to cancel rebalance invoice {}: {}", payment_hash, e),
+    }
+}
@@ -0,0 +1,305 @@
+//! Versioned layout of the sled database.
+//!
+//! Every entity lives in its own tree and `meta/schema_version` records which layout the
+//! database is in. Records are bincode, which can't skip unknown or missing fields, so a
+//! change to a stored struct needs a new entry in `MIGRATIONS` that rewrites the old records:
+//! keep the previous struct in `legacy`, decode with it and insert the new one.
+//! `migrate` runs the outstanding steps on startup before anything else touches the database.
+//!
+//! Data that belongs to a node lives in trees named `<network>/<identity pubkey>/<entity>`,
+//! opened with `scoped_tree`, so switching networks or pointing the app at another node
+//! never mixes preimages or history. Settings, the API token and the webhook delivery queue
+//! are shared by every namespace.
+
+use std::marker::PhantomData;
+
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::invoice_index;
+
+const META_TREE: &str = "meta";
+const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+const INVOICES_TREE: &str = "invoices";
+const ANNOTATIONS_TREE: &str = "annotations";
+const SETTINGS_TREE: &str = "settings";
+// Trees that moved into the node namespace in version 2
+const NODE_TREES: [&str; 5] = ["invoices", "fee_profiles", "forwarding_events", "forwarding_meta", "webhook_state"];
+
+pub const NETWORK_KEY: &str = "network";
+pub const API_TOKEN_KEY: &str = "api_token";
+pub const DISPLAY_UNIT_KEY: &str = "display_unit";
+// Version 1 kept a single identity for every network, from version 2 it is "identity_pubkey/<network>"
+const IDENTITY_PUBKEY_KEY: &str = "identity_pubkey";
+
+/// Layout this build reads and writes.
+pub const SCHEMA_VERSION: u32 = 3;
+
+type Migration = fn(&sled::Db) -> Result<()>;
+
+/// `MIGRATIONS[n]` upgrades a database from version `n` to `n + 1`.
+const MIGRATIONS: [(&str, Migration); SCHEMA_VERSION as usize] = [
+    ("move invoices and settings out of the root tree", split_root_tree),
+    ("scope node data by network and identity", scope_node_trees),
+    ("rebuild the invoice index with msat amounts", drop_invoice_index),
+];
+
+/// What we keep for invoices created in the app, keyed by payment hash.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InvoiceData {
+    pub preimage_x: String,
+    pub preimage_h: String,
+    pub payment_address: String,
+    pub r_hash: String,
+    pub is_own_invoice: bool,
+}
+
+/// Our own notes on an invoice or payment, keyed by payment hash. Unlike the memo they
+/// never leave this machine and can be changed at any time.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Annotation {
+    pub notes: String,
+    pub tags: Vec<String>,
+    pub counterparty: String,
+    pub order_ref: String,
+}
+
+/// A sled tree holding one bincode encoded type, keyed by string.
+pub struct TypedTree<T> {
+    tree: sled::Tree,
+    name: &'static str,
+    _record: PhantomData<T>,
+}
+
+impl<T: Serialize + DeserializeOwned> TypedTree<T> {
+    fn open(db: &sled::Db, name: &'static str) -> Result<Self> {
+        Ok(TypedTree { tree: db.open_tree(name)?, name, _record: PhantomData })
+    }
+
+    /// An unreadable record is an error rather than `None`, so a layout mismatch never
+    /// looks like a missing entry.
+    pub fn get(&self, key: &str) -> Result<Option<T>> {
+        match self.tree.get(key.as_bytes())? {
+            Some(bytes) => bincode::deserialize(&bytes)
+                .map(Some)
+                .with_context(|| format!("Unreadable record '{}' in the {} tree", key, self.name)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn insert(&self, key: &str, value: &T) -> Result<()> {
+        self.tree.insert(key.as_bytes(), bincode::serialize(value)?)?;
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &str) -> Result<()> {
+        self.tree.remove(key.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn scoped_name(network: &str, identity: &str, name: &str) -> String {
+    format!("{}/{}/{}", network, identity, name)
+}
+
+/// Opens `name` in the namespace of the node we last saw on `network`.
+pub fn scoped_tree(db: &sled::Db, network: &str, name: &str) -> Result<sled::Tree> {
+    Ok(db.open_tree(scoped_name(network, &node_identity(db, network)?, name))?)
+}
+
+fn scoped_typed_tree<T>(db: &sled::Db, network: &str, name: &'static str) -> Result<TypedTree<T>> {
+    Ok(TypedTree { tree: scoped_tree(db, network, name)?, name, _record: PhantomData })
+}
+
+pub fn invoices(db: &sled::Db, network: &str) -> Result<TypedTree<InvoiceData>> {
+    scoped_typed_tree(db, network, INVOICES_TREE)
+}
+
+pub fn annotations(db: &sled::Db, network: &str) -> Result<TypedTree<Annotation>> {
+    scoped_typed_tree(db, network, ANNOTATIONS_TREE)
+}
+
+fn identity_key(network: &str) -> String {
+    format!("{}/{}", IDENTITY_PUBKEY_KEY, network)
+}
+
+/// Remembers which node we are connected to on `network`, called whenever getinfo succeeds.
+/// A migration that was waiting for the identity runs now.
+pub fn set_node_identity(db: &sled::Db, network: &str, identity_pubkey: &str) -> Result<()> {
+    set_setting(db, &identity_key(network), identity_pubkey)?;
+    if schema_version(db)? < SCHEMA_VERSION {
+        migrate(db)?;
+    }
+    Ok(())
+}
+
+/// Identity pubkey of the node on `network`, as last stored by `set_node_identity`.
+/// There is no namespace to read or write until we know it.
+pub fn node_identity(db: &sled::Db, network: &str) -> Result<String> {
+    get_setting(db, &identity_key(network))?
+        .ok_or_else(|| anyhow!("The {} node's identity is not known yet, connect to the node first", network))
+}
+
+pub fn get_setting(db: &sled::Db, key: &str) -> Result<Option<String>> {
+    let settings = db.open_tree(SETTINGS_TREE)?;
+    Ok(settings.get(key.as_bytes())?.map(|value| String::from_utf8_lossy(&value).to_string()))
+}
+
+pub fn set_setting(db: &sled::Db, key: &str, value: &str) -> Result<()> {
+    db.open_tree(SETTINGS_TREE)?.insert(key.as_bytes(), value.as_bytes())?;
+    Ok(())
+}
+
+fn schema_version(db: &sled::Db) -> Result<u32> {
+    let meta = db.open_tree(META_TREE)?;
+    Ok(meta.get(SCHEMA_VERSION_KEY)?
+        .and_then(|v| v.as_ref().try_into().ok().map(u32::from_be_bytes))
+        .unwrap_or(0))
+}
+
+/// Brings the database up to `SCHEMA_VERSION`, one step at a time. Each step is recorded
+/// once it completes, so an interrupted upgrade resumes where it stopped.
+pub fn migrate(db: &sled::Db) -> Result<()> {
+    let mut version = schema_version(db)?;
+    if version > SCHEMA_VERSION {
+        return Err(anyhow!(
+            "The database is at schema version {} but this build only knows up to {}. Please update the app.",
+            version, SCHEMA_VERSION
+        ));
+    }
+    let meta = db.open_tree(META_TREE)?;
+    while version < SCHEMA_VERSION {
+        let (description, migration) = MIGRATIONS[version as usize];
+        eprintln!("Migrating database to schema version {}: {}", version + 1, description);
+        match migration(db) {
+            Err(e) if e.is::<NeedsNodeIdentity>() => {
+                // The database stays at this version until set_node_identity retries
+                eprintln!("Deferring the migration to version {}: {}", version + 1, e);
+                return Ok(());
+            }
+            result => result.with_context(|| format!("Database migration to version {} failed", version + 1))?,
+        }
+        version += 1;
+        meta.insert(SCHEMA_VERSION_KEY, &version.to_be_bytes())?;
+        db.flush()?;
+    }
+    Ok(())
+}
+
+/// A migration that can't run until the node has told us its identity.
+#[derive(Debug)]
+struct NeedsNodeIdentity(usize);
+
+impl std::fmt::Display for NeedsNodeIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} records wait for the node's identity", self.0)
+    }
+}
+
+impl std::error::Error for NeedsNodeIdentity {}
+
+/// Record layouts as they were before a migration, for decoding old data.
+mod legacy {
+    use serde::Deserialize;
+
+    /// Invoice record of version 0, stored in the root tree keyed by r_hash.
+    #[derive(Deserialize)]
+    pub struct InvoiceDataV0 {
+        pub preimage_x: String,
+        pub preimage_h: String,
+        pub payment_address: String,
+        pub r_hash: String,
+        pub is_own_invoice: bool,
+    }
+}
+
+/// Version 0 kept everything in the root tree: invoices keyed by r_hash next to the
+/// `network`, `identity_pubkey` and `api_token` strings.
+fn split_root_tree(db: &sled::Db) -> Result<()> {
+    let invoices: TypedTree<InvoiceData> = TypedTree::open(db, INVOICES_TREE)?;
+    let settings = db.open_tree(SETTINGS_TREE)?;
+    let (mut moved_invoices, mut moved_settings) = (0, 0);
+
+    for entry in db.iter() {
+        let (key, value) = entry?;
+        let key_str = String::from_utf8_lossy(&key).to_string();
+        if [NETWORK_KEY, IDENTITY_PUBKEY_KEY, API_TOKEN_KEY].contains(&key_str.as_str()) {
+            settings.insert(&key, value)?;
+            moved_settings += 1;
+        } else {
+            match bincode::deserialize::<legacy::InvoiceDataV0>(&value) {
+                Ok(old) => {
+                    invoices.insert(&key_str, &InvoiceData {
+                        preimage_x: old.preimage_x,
+                        preimage_h: old.preimage_h,
+                        payment_address: old.payment_address,
+                        r_hash: old.r_hash,
+                        is_own_invoice: old.is_own_invoice,
+                    })?;
+                    moved_invoices += 1;
+                }
+                Err(e) => {
+                    // Left in place so nothing is lost, nothing reads the root tree any more
+                    eprintln!("Leaving unrecognised root record '{}' in place: {}", key_str, e);
+                    continue;
+                }
+            }
+        }
+        db.remove(&key)?;
+    }
+    eprintln!("Moved {} invoices and {} settings into their own trees", moved_invoices, moved_settings);
+    Ok(())
+}
+
+/// Version 1 shared every tree between networks and nodes. The data can only have come
+/// from the node last seen, so it moves into that node's namespace on the selected network.
+/// Without a stored identity the step waits for the first getinfo rather than leave the
+/// records behind.
+fn scope_node_trees(db: &sled::Db) -> Result<()> {
+    let settings = db.open_tree(SETTINGS_TREE)?;
+    let network = get_setting(db, NETWORK_KEY)?.unwrap_or_else(|| "testnet".to_string());
+    let identity = match settings.get(IDENTITY_PUBKEY_KEY)? {
+        Some(identity) => String::from_utf8_lossy(&identity).to_string(),
+        None => match get_setting(db, &identity_key(&network))? {
+            Some(identity) => identity,
+            None => {
+                let unattributed: usize = NODE_TREES.iter()
+                    .map(|name| db.open_tree(name).map(|tree| tree.len()).unwrap_or(0))
+                    .sum();
+                if unattributed > 0 {
+                    return Err(NeedsNodeIdentity(unattributed).into());
+                }
+                return Ok(());
+            }
+        },
+    };
+
+    for name in NODE_TREES {
+        let old = db.open_tree(name)?;
+        let new = db.open_tree(scoped_name(&network, &identity, name))?;
+        for entry in old.iter() {
+            let (key, value) = entry?;
+            new.insert(key, value)?;
+        }
+        eprintln!("Moved {} records from {} into {}/{}", old.len(), name, network, identity);
+        db.drop_tree(name)?;
+    }
+    settings.insert(identity_key(&network).as_bytes(), identity.as_bytes())?;
+    settings.remove(IDENTITY_PUBKEY_KEY)?;
+    Ok(())
+}
+
+/// The invoice index of version 2 stored whole sats. It is only a copy of what lnd has, so
+/// rather than converting it is dropped and the next sync pages everything in again.
+fn drop_invoice_index(db: &sled::Db) -> Result<()> {
+    for name in db.tree_names() {
+        let name_str = String::from_utf8_lossy(&name).to_string();
+        let suffixes = [invoice_index::INDEX_TREE, invoice_index::PENDING_TREE, invoice_index::SYNC_META_TREE].map(|tree| format!("/{}", tree));
+        if suffixes.iter().any(|suffix| name_str.ends_with(suffix.as_str())) {
+            eprintln!("Dropping {}", name_str);
+            db.drop_tree(&name)?;
+        }
+    }
+    Ok(())
+}
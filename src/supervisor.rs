@@ -0,0 +1,257 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use crate::litd_config;
+
+// Same files the launchd and systemd services write to, so the logs look the same in every mode
+const STDOUT_LOG: &str = "/tmp/com.btc.litd.stdout.log";
+const STDERR_LOG: &str = "/tmp/com.btc.litd.stderr.log";
+const LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+const LOG_KEEP_FILES: u32 = 3;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// A run this long counts as healthy and resets the backoff
+const STABLE_RUN: Duration = Duration::from_secs(60);
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Runs litd as a child of the app for hosts without launchd or systemd,
+/// restarting it with backoff when it exits on its own.
+#[derive(Clone)]
+pub struct LitdSupervisor {
+    // The running litd, None between exit and respawn
+    pid: Arc<Mutex<Option<u32>>>,
+    network: Arc<Mutex<String>>,
+    extra_args: Vec<String>,
+    stopping: Arc<AtomicBool>,
+    // Set by restart() so the next respawn happens without waiting out the backoff
+    restarting: Arc<AtomicBool>,
+}
+
+impl LitdSupervisor {
+    pub fn new(extra_args: Vec<String>) -> Self {
+        Self {
+            pid: Arc::new(Mutex::new(None)),
+            network: Arc::new(Mutex::new(String::new())),
+            extra_args,
+            stopping: Arc::new(AtomicBool::new(false)),
+            restarting: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn start(&self, network: &str) -> Result<()> {
+        *self.network.lock().unwrap() = network.to_string();
+        self.stopping.store(false, Ordering::SeqCst);
+
+        // Spawn the first process here so a missing binary is reported to the caller
+        let pid = self.spawn_litd()?;
+        let supervisor = self.clone();
+        thread::spawn(move || supervisor.monitor(pid));
+        Ok(())
+    }
+
+    /// Switches to `network` by stopping the current process, the monitor respawns it right away.
+    pub fn restart(&self, network: &str) -> Result<()> {
+        *self.network.lock().unwrap() = network.to_string();
+        self.restarting.store(true, Ordering::SeqCst);
+        match self.pid() {
+            Some(pid) => terminate(pid),
+            None => Ok(()),
+        }
+    }
+
+    /// Asks litd to shut down cleanly, killing it if it hasn't exited after a timeout.
+    pub fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        let Some(pid) = self.pid() else { return };
+        println!("Stopping supervised litd (pid {})", pid);
+        if let Err(e) = terminate(pid) {
+            println!("Failed to signal litd: {}", e);
+        }
+
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while self.pid().is_some() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(200));
+        }
+        if let Some(pid) = self.pid() {
+            println!("litd did not exit within {:?}, killing it", SHUTDOWN_TIMEOUT);
+            kill(pid);
+        }
+    }
+
+    fn pid(&self) -> Option<u32> {
+        *self.pid.lock().unwrap()
+    }
+
+    fn spawn_litd(&self) -> Result<u32> {
+        let network = self.network.lock().unwrap().clone();
+        let mut command = Command::new("/usr/local/bin/litd");
+        command.args(litd_config::litd_args(&network));
+        command.args(&self.extra_args);
+
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to start /usr/local/bin/litd")?;
+        let pid = child.id();
+        println!("Started supervised litd on {} (pid {})", network, pid);
+
+        if let Some(stdout) = child.stdout.take() {
+            thread::spawn(move || copy_to_log(stdout, Path::new(STDOUT_LOG)));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            thread::spawn(move || copy_to_log(stderr, Path::new(STDERR_LOG)));
+        }
+
+        // The monitor thread only knows the pid, so it waits on the child from here
+        let running = self.pid.clone();
+        *running.lock().unwrap() = Some(pid);
+        thread::spawn(move || {
+            let status = child.wait();
+            println!("Supervised litd (pid {}) exited: {:?}", pid, status);
+            let mut running = running.lock().unwrap();
+            if *running == Some(pid) {
+                *running = None;
+            }
+        });
+        Ok(pid)
+    }
+
+    fn monitor(&self, first_pid: u32) {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut started_at = Instant::now();
+        let mut pid = first_pid;
+
+        loop {
+            while self.pid() == Some(pid) {
+                thread::sleep(Duration::from_millis(500));
+            }
+            if self.stopping.load(Ordering::SeqCst) {
+                println!("litd supervisor stopped");
+                return;
+            }
+
+            if self.restarting.swap(false, Ordering::SeqCst) {
+                backoff = INITIAL_BACKOFF;
+            } else {
+                if started_at.elapsed() >= STABLE_RUN {
+                    backoff = INITIAL_BACKOFF;
+                }
+                println!("litd exited unexpectedly, restarting in {:?}", backoff);
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                if self.stopping.load(Ordering::SeqCst) {
+                    return;
+                }
+            }
+
+            match self.spawn_litd() {
+                Ok(new_pid) => {
+                    pid = new_pid;
+                    started_at = Instant::now();
+                }
+                Err(e) => println!("Failed to restart litd: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> Result<()> {
+    let output = Command::new("kill")
+        .args(["-TERM", &pid.to_string()])
+        .output()
+        .context("Failed to execute kill")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("kill -TERM {} failed: {}", pid, String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(())
+}
+
+// Windows has no SIGTERM, taskkill without /F asks the process to close
+#[cfg(windows)]
+fn terminate(pid: u32) -> Result<()> {
+    Command::new("taskkill")
+        .args(["/PID", &pid.to_string()])
+        .output()
+        .context("Failed to execute taskkill")?;
+    Ok(())
+}
+
+fn kill(pid: u32) {
+    let result = if cfg!(windows) {
+        Command::new("taskkill").args(["/F", "/PID", &pid.to_string()]).output()
+    } else {
+        Command::new("kill").args(["-KILL", &pid.to_string()]).output()
+    };
+    if let Err(e) = result {
+        println!("Failed to kill litd (pid {}): {}", pid, e);
+    }
+}
+
+/// Appends each line from `stream` to `path`, rotating to `path.1`, `path.2`, ... past the size limit.
+fn copy_to_log(stream: impl Read, path: &Path) {
+    let mut log = match RotatingLog::open(path) {
+        Ok(log) => log,
+        Err(e) => {
+            println!("Failed to open litd log {}: {}", path.display(), e);
+            return;
+        }
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { break };
+        if let Err(e) = log.write_line(&line) {
+            println!("Failed to write litd log {}: {}", path.display(), e);
+        }
+    }
+}
+
+struct RotatingLog {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingLog {
+    fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path: path.to_path_buf(), file, size })
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        if self.size >= LOG_MAX_BYTES {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        for index in (1..LOG_KEEP_FILES).rev() {
+            let from = rotated_path(&self.path, index);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.path, index + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, index: u32) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path.display(), index))
+}
@@ -1,21 +1,52 @@
-use slint::SharedString;
+use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 pub struct AppState {
     // pub invoices: Vec<Invoice>,
     pub status_message: String,
     pub node_is_running: bool,
     pub node_sync_status: String,
     pub wallet_needs_unlock: bool,
-    pub litd_pid: Option<u32>, // Store PID of litd process we started
 }
 
+/// How lnd is being run, which decides where its files and ports are.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeMode {
+    Lnd,
+    LitdIntegrated,
+    LitdRemote,
+}
+
+impl std::fmt::Display for NodeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeMode::Lnd => write!(f, "LND"),
+            NodeMode::LitdIntegrated => write!(f, "litd (integrated lnd)"),
+            NodeMode::LitdRemote => write!(f, "litd (remote lnd)"),
+        }
+    }
+}
+
+/// Where to reach lnd, resolved by `discovery::discover`.
 #[derive(Debug, Clone)]
 pub struct LndConnection {
+    pub mode: NodeMode,
     pub host: String,
-    pub port: u16,
-    pub cert_path: String,
-    pub macaroon_path: String,
+    pub port: u16, // gRPC
+    pub rest_port: u16,
+    pub cert_path: PathBuf,
+    pub macaroon_path: PathBuf,
+    pub lit_macaroon_path: Option<PathBuf>,
+    pub config_path: Option<PathBuf>,
+    /// lnd's data directory, `None` when litd talks to a remote lnd
+    pub lnd_dir: Option<PathBuf>,
+}
+
+impl LndConnection {
+    pub fn rest_url(&self) -> String {
+        format!("https://{}:{}", self.host, self.rest_port)
+    }
 }
 
 impl Default for AppState {
@@ -26,24 +57,6 @@ impl Default for AppState {
             node_is_running: false,
             node_sync_status: "Unknown".to_string(),
             wallet_needs_unlock: false,
-            litd_pid: None,
-        }
-    }
-}
-
-impl Default for LndConnection {
-    fn default() -> Self {
-        // For Windows, the default paths use AppData/Local/Lit
-        let home_dir = dirs::home_dir().unwrap_or_default();
-        let lit_dir = home_dir.join("AppData").join("Local").join("Lit");
-        let cert_path = lit_dir.join("tls.cert").to_string_lossy().to_string();
-        let macaroon_path = lit_dir.join("testnet").join("lit.macaroon").to_string_lossy().to_string();
-        
-        Self {
-            host: "127.0.0.1".to_string(),
-            port: 10009,
-            cert_path,
-            macaroon_path,
         }
     }
 }
@@ -1,34 +1,157 @@
 use anyhow::{anyhow, Context, Result};
+use base64::Engine;
 use reqwest::Certificate;
-use serde_json::json;
-use std::{env, fs, time::Duration};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
 
-pub async fn unlock_wallet_rpc(
-    password: &str,
-) -> Result<()> {
-    let lnd_rest_address = "https://localhost:8080".to_string();
-    let home_dir = env::var("HOME").unwrap();
-    let cert_file_path = format!("{}/Library/Application Support/Lnd/tls.cert", home_dir);
+use crate::types::LndConnection;
+
+const SEED_WORD_COUNT: usize = 24;
+const MIN_PASSWORD_LENGTH: usize = 8;
+// Addresses scanned for funds when restoring, the same default lncli create uses
+const RESTORE_RECOVERY_WINDOW: i32 = 2500;
+
+/// The wallet unlocker's view of the node, as reported by /v1/state.
+#[derive(Debug, PartialEq)]
+pub enum WalletState {
+    NonExisting,
+    Locked,
+    Unlocked,
+    RpcActive,
+    ServerActive,
+    WaitingToStart,
+}
 
-    let cert_pem = fs::read(&cert_file_path)
+fn rest_client(connection: &LndConnection) -> Result<reqwest::Client> {
+    let cert_file_path = connection.cert_path.display();
+
+    let cert_pem = fs::read(&connection.cert_path)
         .with_context(|| format!("Failed to read LND TLS cert from {}", cert_file_path))?;
     let lnd_cert = Certificate::from_pem(&cert_pem)
         .with_context(|| format!("Failed to parse LND TLS cert from PEM in {}", cert_file_path))?;
 
+    reqwest::Client::builder()
+        .add_root_certificate(lnd_cert)
+        .build()
+        .context("Failed to build reqwest client")
+}
+
+async fn read_response(response: reqwest::Response, what: &str) -> Result<Value> {
+    let status = response.status();
+    let response_text = response.text().await.unwrap_or_else(|e| e.to_string());
+    if !status.is_success() {
+        return Err(anyhow!("{} failed. Status: {}. Response: {}", what, status, response_text));
+    }
+    serde_json::from_str(&response_text)
+        .with_context(|| format!("Failed to parse {} response: {}", what, response_text))
+}
+
+pub async fn wallet_state(connection: &LndConnection) -> Result<WalletState> {
+    let client = rest_client(connection)?;
+    let response = client.get(format!("{}/v1/state", connection.rest_url())).send().await?;
+    let json = read_response(response, "State request").await?;
+    match json["state"].as_str().unwrap_or("NON_EXISTING") {
+        "NON_EXISTING" => Ok(WalletState::NonExisting),
+        "LOCKED" => Ok(WalletState::Locked),
+        "UNLOCKED" => Ok(WalletState::Unlocked),
+        "RPC_ACTIVE" => Ok(WalletState::RpcActive),
+        "SERVER_ACTIVE" => Ok(WalletState::ServerActive),
+        "WAITING_TO_START" => Ok(WalletState::WaitingToStart),
+        other => Err(anyhow!("Unknown wallet state: {}", other)),
+    }
+}
+
+/// Asks lnd for a fresh aezeed mnemonic, optionally protected by a seed passphrase.
+pub async fn generate_seed(connection: &LndConnection, seed_passphrase: &str) -> Result<Vec<String>> {
+    let client = rest_client(connection)?;
+    let mut request = client.get(format!("{}/v1/genseed", connection.rest_url()));
+    if !seed_passphrase.is_empty() {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(seed_passphrase.as_bytes());
+        request = request.query(&[("aezeed_passphrase", encoded)]);
+    }
+    let json = read_response(request.send().await?, "Seed generation").await?;
+    let words: Vec<String> = json["cipher_seed_mnemonic"].as_array()
+        .map(|words| words.iter().filter_map(|w| w.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+    if words.len() != SEED_WORD_COUNT {
+        return Err(anyhow!("lnd returned a {} word seed, expected {}", words.len(), SEED_WORD_COUNT));
+    }
+    Ok(words)
+}
+
+/// Whether `init_wallet` sets up a new wallet or brings back an existing one.
+pub enum WalletInit<'a> {
+    /// A seed we just generated, there is nothing on chain to look for.
+    Create,
+    /// A seed that may have funds, lnd rescans for them. The static channel backup file, if
+    /// given, lets lnd ask the peers of the old node to close its channels.
+    Restore { channel_backup_path: Option<&'a Path> },
+}
+
+/// Creates the wallet from `mnemonic`.
+pub async fn init_wallet(
+    connection: &LndConnection,
+    password: &str,
+    mnemonic: &[String],
+    seed_passphrase: &str,
+    init: WalletInit<'_>,
+) -> Result<()> {
+    if password.len() < MIN_PASSWORD_LENGTH {
+        return Err(anyhow!("The wallet password must be at least {} characters", MIN_PASSWORD_LENGTH));
+    }
+    if mnemonic.len() != SEED_WORD_COUNT {
+        return Err(anyhow!("The seed must have {} words, got {}", SEED_WORD_COUNT, mnemonic.len()));
+    }
+
+    let engine = base64::engine::general_purpose::STANDARD;
+    let mut request_body = json!({
+        "wallet_password": engine.encode(password.as_bytes()),
+        "cipher_seed_mnemonic": mnemonic,
+    });
+    if !seed_passphrase.is_empty() {
+        request_body["aezeed_passphrase"] = json!(engine.encode(seed_passphrase.as_bytes()));
+    }
+    if let WalletInit::Restore { channel_backup_path } = init {
+        request_body["recovery_window"] = json!(RESTORE_RECOVERY_WINDOW);
+        if let Some(path) = channel_backup_path {
+            let backup = fs::read(path)
+                .with_context(|| format!("Failed to read channel backup from {}", path.display()))?;
+            request_body["channel_backups"] = json!({
+                "multi_chan_backup": { "multi_chan_backup": engine.encode(backup) },
+            });
+        }
+    }
+
+    println!("Initializing LND wallet via REST: {}", connection.rest_url());
+    let client = rest_client(connection)?;
+    let response = client
+        .post(format!("{}/v1/initwallet", connection.rest_url()))
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .send()
+        .await?;
+    read_response(response, "Wallet init").await?;
+    println!("Wallet initialized via REST.");
+    Ok(())
+}
+
+pub async fn unlock_wallet_rpc(
+    connection: &LndConnection,
+    password: &str,
+) -> Result<()> {
+    let lnd_rest_address = connection.rest_url();
+    let client = rest_client(connection)?;
+
     println!(
         "Attempting LND wallet unlock via REST: {}",
         lnd_rest_address
     );
 
     let request_body = json!({
-        "wallet_password": base64::encode(password.as_bytes()),
+        "wallet_password": base64::engine::general_purpose::STANDARD.encode(password.as_bytes()),
     });
 
-    let client = reqwest::Client::builder()
-        .add_root_certificate(lnd_cert)
-        .build()
-        .context("Failed to build reqwest client")?;
-
     let unlock_url = format!("{}/v1/unlockwallet", lnd_rest_address);
 
     match client
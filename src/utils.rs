@@ -1,12 +1,11 @@
-use crate::MainWindow;
-use anyhow::{anyhow, Result};
 use rand::RngCore;
 use sha2::{Digest, Sha256};
-use slint::{Timer, TimerMode};
-use std::process::Command;
-use std::time::Duration;
-use std::path::Path;
 use serde_json::Value;
+use directories::UserDirs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::discovery;
 
 pub fn generate_preimage() -> (String, String) {
     let mut rng = rand::thread_rng();
@@ -20,16 +19,6 @@ pub fn generate_preimage() -> (String, String) {
     (preimage_hex, hash_hex)
 }
 
-pub fn spawn_ui_timer<F>(window: &MainWindow, interval: Duration, callback: F)
-where
-    F: Fn() + 'static,
-{
-    let timer = Timer::default();
-    timer.start(TimerMode::Repeated, interval, move || {
-        callback();
-    });
-}
-
 pub fn extract_funding_txid_from_string(json_string: &str) -> Option<String> {
     if let Ok(parsed_json) = serde_json::from_str::<Value>(json_string) {
         if let Some(txid) = parsed_json.get("funding_txid").and_then(|v| v.as_str()) {
@@ -42,4 +31,39 @@ pub fn extract_funding_txid_from_string(json_string: &str) -> Option<String> {
         }
     }
     None
-} 
\ No newline at end of file
+}
+
+const LNCLI_PATH: &str = "/usr/local/bin/lncli";
+
+/// lncli pointed at the node discovery found for `network`, so a custom lnddir, RPC address,
+/// certificate or macaroon from lnd.conf or lit.conf is used rather than lncli's defaults.
+pub fn lncli(network: &str) -> Command {
+    let program = if Path::new(LNCLI_PATH).exists() { LNCLI_PATH } else { "lncli" };
+    let mut command = Command::new(program);
+    if network != "mainnet" {
+        command.args(["--network", network]);
+    }
+    let connection = discovery::discover(network);
+    if let Some(lnd_dir) = &connection.lnd_dir {
+        command.arg(format!("--lnddir={}", lnd_dir.display()));
+    }
+    command.arg(format!("--rpcserver={}:{}", connection.host, connection.port));
+    command.arg(format!("--tlscertpath={}", connection.cert_path.display()));
+    command.arg(format!("--macaroonpath={}", connection.macaroon_path.display()));
+    command
+}
+
+/// lncli prints most 64-bit integers as JSON strings, this accepts either form.
+pub fn json_u64(value: &Value) -> u64 {
+    value.as_u64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+        .unwrap_or(0)
+}
+
+/// Where exported files are written: the user's downloads folder, falling back to home.
+pub fn export_dir() -> PathBuf {
+    UserDirs::new()
+        .and_then(|user_dirs| user_dirs.download_dir().map(|d| d.to_path_buf()))
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
@@ -0,0 +1,188 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use crate::amount::Amount;
+use crate::utils::{json_u64, lncli};
+
+/// Address types offered when generating a receive address, in the order shown in the UI.
+pub const ADDRESS_TYPES: [&str; 3] = ["p2wkh", "np2wkh", "p2tr"];
+
+/// Fee rate for an on-chain send, either a confirmation target for lnd's estimator or a fixed rate.
+pub enum FeeRate {
+    ConfTarget(u32),
+    SatPerVbyte(u64),
+}
+
+/// Confirmation targets offered for sends; the UI adds a final "custom sat/vB" option after these.
+pub const CONF_TARGETS: [(&str, u32); 3] = [
+    ("Next block", 1),
+    ("Within an hour (6 blocks)", 6),
+    ("Within a day (144 blocks)", 144),
+];
+
+impl FeeRate {
+    /// Fee rate for the option picked in the UI, `custom` is only read for the custom option.
+    pub fn from_option(index: usize, custom: &str) -> Result<FeeRate> {
+        match CONF_TARGETS.get(index) {
+            Some((_, blocks)) => Ok(FeeRate::ConfTarget(*blocks)),
+            None => {
+                let rate: u64 = custom.trim().parse()
+                    .map_err(|_| anyhow!("Invalid fee rate: {}", custom))?;
+                if rate == 0 {
+                    return Err(anyhow!("Fee rate must be at least 1 sat/vB"));
+                }
+                Ok(FeeRate::SatPerVbyte(rate))
+            }
+        }
+    }
+}
+
+pub struct WalletBalance {
+    pub confirmed: Amount,
+    pub unconfirmed: Amount,
+    pub locked: Amount,
+}
+
+pub struct Utxo {
+    pub outpoint: String, // txid:index
+    pub address: String,
+    pub address_type: String,
+    pub amount: Amount,
+    pub confirmations: u64,
+}
+
+pub struct WalletTransaction {
+    pub txid: String,
+    pub amount_sat: i64,
+    pub fee_sat: u64,
+    pub confirmations: u64,
+    pub timestamp: u64,
+    pub label: String,
+}
+
+fn run_json(network: &str, args: &[&str]) -> Result<Value> {
+    let output = lncli(network).args(args).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("lncli {} failed: {}", args[0], stderr));
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow!("Failed to parse {} JSON: {}", args[0], e))
+}
+
+pub fn balance(network: &str) -> Result<WalletBalance> {
+    let json = run_json(network, &["walletbalance"])?;
+    Ok(WalletBalance {
+        confirmed: Amount::from_json_sat(&json["confirmed_balance"]),
+        unconfirmed: Amount::from_json_sat(&json["unconfirmed_balance"]),
+        locked: Amount::from_json_sat(&json["locked_balance"]),
+    })
+}
+
+pub fn new_address(network: &str, address_type: &str) -> Result<String> {
+    if !ADDRESS_TYPES.contains(&address_type) {
+        return Err(anyhow!("Unsupported address type: {}", address_type));
+    }
+    let json = run_json(network, &["newaddress", address_type])?;
+    json["address"].as_str()
+        .map(|a| a.to_string())
+        .ok_or_else(|| anyhow!("No address in newaddress output"))
+}
+
+/// Unspent outputs including unconfirmed ones, most confirmations first.
+pub fn list_utxos(network: &str) -> Result<Vec<Utxo>> {
+    let json = run_json(network, &["listunspent", "--min_confs", "0"])?;
+    let mut utxos: Vec<Utxo> = json["utxos"].as_array().cloned().unwrap_or_default()
+        .iter()
+        .map(|u| Utxo {
+            outpoint: format!(
+                "{}:{}",
+                u["outpoint"]["txid_str"].as_str().unwrap_or_default(),
+                json_u64(&u["outpoint"]["output_index"])
+            ),
+            address: u["address"].as_str().unwrap_or_default().to_string(),
+            address_type: u["address_type"].as_str().unwrap_or_default().to_string(),
+            amount: Amount::from_json_sat(&u["amount_sat"]),
+            confirmations: json_u64(&u["confirmations"]),
+        })
+        .collect();
+    utxos.sort_by_key(|u| std::cmp::Reverse(u.confirmations));
+    Ok(utxos)
+}
+
+/// Sends `amount` to `address`. When `outpoints` is not empty only those UTXOs are spent.
+/// On-chain amounts are whole sats, anything with msats is rejected. Returns the txid.
+pub fn send_coins(
+    network: &str,
+    address: &str,
+    amount: Amount,
+    fee_rate: &FeeRate,
+    outpoints: &[String],
+    label: &str,
+) -> Result<String> {
+    if address.trim().is_empty() {
+        return Err(anyhow!("Destination address must not be empty"));
+    }
+    if amount.is_zero() {
+        return Err(anyhow!("Amount must be greater than zero"));
+    }
+    let amount_sat = amount.to_sat_exact()?;
+
+    let mut command = lncli(network);
+    command.args(["sendcoins", "--addr", address.trim(), "--amt", &amount_sat.to_string(), "--force"]);
+    match fee_rate {
+        FeeRate::ConfTarget(blocks) => command.args(["--conf_target", &blocks.to_string()]),
+        FeeRate::SatPerVbyte(rate) => command.args(["--sat_per_vbyte", &rate.to_string()]),
+    };
+    for outpoint in outpoints {
+        command.args(["--utxo", outpoint]);
+    }
+    if !label.trim().is_empty() {
+        command.args(["--label", label.trim()]);
+    }
+
+    let output = command.output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("Failed to send coins: {}", stderr));
+    }
+    let json: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow!("Failed to parse sendcoins JSON: {}", e))?;
+    let txid = json["txid"].as_str().unwrap_or_default().to_string();
+    eprintln!("Sent {} to {} in {}", amount, address, txid);
+    Ok(txid)
+}
+
+/// Wallet transactions, newest first.
+pub fn list_transactions(network: &str) -> Result<Vec<WalletTransaction>> {
+    let json = run_json(network, &["listchaintxns"])?;
+    let mut transactions: Vec<WalletTransaction> = json["transactions"].as_array().cloned().unwrap_or_default()
+        .iter()
+        .map(|t| WalletTransaction {
+            txid: t["tx_hash"].as_str().unwrap_or_default().to_string(),
+            amount_sat: t["amount"].as_i64()
+                .or_else(|| t["amount"].as_str().and_then(|s| s.parse().ok()))
+                .unwrap_or(0),
+            fee_sat: json_u64(&t["total_fees"]),
+            confirmations: json_u64(&t["num_confirmations"]),
+            timestamp: json_u64(&t["time_stamp"]),
+            label: t["label"].as_str().unwrap_or_default().to_string(),
+        })
+        .collect();
+    transactions.sort_by_key(|t| std::cmp::Reverse(t.timestamp));
+    Ok(transactions)
+}
+
+pub fn label_transaction(network: &str, txid: &str, label: &str) -> Result<()> {
+    if label.trim().is_empty() {
+        return Err(anyhow!("Label must not be empty"));
+    }
+    let output = lncli(network)
+        .args(["wallet", "labeltx", "--overwrite", txid, label.trim()])
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("Failed to label transaction {}: {}", txid, stderr));
+    }
+    Ok(())
+}
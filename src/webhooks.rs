@@ -0,0 +1,356 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sled::transaction::{ConflictableTransactionError, Transactional};
+
+use crate::{invoice, store};
+
+const WEBHOOKS_TREE: &str = "webhooks";
+const DELIVERIES_TREE: &str = "webhook_deliveries";
+// Last state seen per invoice and payment, keyed "invoice:<hash>" / "payment:<hash>"
+const STATE_TREE: &str = "webhook_state";
+const SEEDED_KEY: &[u8] = b"seeded";
+
+const INVOICE_EVENT_STATES: [&str; 3] = ["ACCEPTED", "SETTLED", "CANCELED"];
+const PAYMENT_EVENT_STATES: [&str; 2] = ["SUCCEEDED", "FAILED"];
+
+const MAX_ATTEMPTS: u32 = 8;
+const INITIAL_RETRY_SECS: i64 = 10;
+const MAX_RETRY_SECS: i64 = 3600;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+// Finished deliveries beyond this many are dropped from the log
+const DELIVERY_LOG_LIMIT: usize = 500;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Webhook {
+    pub url: String,
+    pub secret: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Delivery {
+    pub event_id: String,
+    pub event_type: String,
+    pub url: String,
+    pub payload: String,
+    pub attempts: u32,
+    pub status: DeliveryStatus,
+    pub last_error: String,
+    pub created_at: i64,
+    pub next_attempt_at: i64,
+}
+
+pub fn list_webhooks(db: &sled::Db) -> Result<Vec<Webhook>> {
+    let tree = db.open_tree(WEBHOOKS_TREE)?;
+    let mut webhooks = Vec::new();
+    for entry in tree.iter() {
+        let (_, value) = entry?;
+        match bincode::deserialize::<Webhook>(&value) {
+            Ok(webhook) => webhooks.push(webhook),
+            Err(e) => println!("Skipping unreadable webhook: {}", e),
+        }
+    }
+    Ok(webhooks)
+}
+
+/// Registers `url`, generating a signing secret when none is given.
+pub fn add_webhook(db: &sled::Db, url: &str, secret: &str) -> Result<Webhook> {
+    let url = url.trim();
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(anyhow!("Webhook URL must start with http:// or https://"));
+    }
+    let secret = match secret.trim() {
+        "" => random_hex(32),
+        secret => secret.to_string(),
+    };
+    let webhook = Webhook { url: url.to_string(), secret };
+    db.open_tree(WEBHOOKS_TREE)?.insert(url.as_bytes(), bincode::serialize(&webhook)?)?;
+    Ok(webhook)
+}
+
+pub fn remove_webhook(db: &sled::Db, url: &str) -> Result<()> {
+    db.open_tree(WEBHOOKS_TREE)?.remove(url.as_bytes())?;
+    Ok(())
+}
+
+/// Delivery log, newest first.
+pub fn recent_deliveries(db: &sled::Db, limit: usize) -> Result<Vec<Delivery>> {
+    let tree = db.open_tree(DELIVERIES_TREE)?;
+    let mut deliveries = Vec::new();
+    for entry in tree.iter().rev().take(limit) {
+        let (_, value) = entry?;
+        deliveries.push(bincode::deserialize::<Delivery>(&value)?);
+    }
+    Ok(deliveries)
+}
+
+fn random_hex(bytes: usize) -> String {
+    let mut buffer = vec![0u8; bytes];
+    rand::thread_rng().fill_bytes(&mut buffer);
+    hex::encode(buffer)
+}
+
+/// Compares invoice and payment states with the last poll and queues an event for each
+/// transition we report. The first poll only records the current states.
+pub fn poll(network: &str, db: &sled::Db) -> Result<usize> {
+    let webhooks = list_webhooks(db)?;
+    let state_tree = store::scoped_tree(db, network, STATE_TREE)?;
+    if webhooks.is_empty() {
+        // Start from a fresh snapshot once a webhook is added, not from whatever was seen last
+        state_tree.clear()?;
+        return Ok(0);
+    }
+    let seeded = state_tree.contains_key(SEEDED_KEY)?;
+    // Both lists are fetched before anything is recorded, so a failed lncli call leaves
+    // the states as they were and the transitions are picked up by the next poll
+    let invoices = invoice::list_invoices(network, db)?;
+    let payments = invoice::list_payments(network)?;
+
+    let mut states = Vec::new();
+    let mut events = Vec::new();
+    for record in &invoices {
+        let i = &record.invoice;
+        let key = format!("invoice:{}", i.r_hash);
+        let changed = state_tree.get(key.as_bytes())?.as_deref() != Some(i.state.as_bytes());
+        if seeded && changed && INVOICE_EVENT_STATES.contains(&i.state.as_str()) {
+            events.push((
+                format!("invoice.{}", i.state.to_lowercase()),
+                json!({
+                    "payment_hash": i.r_hash.as_str(),
+                    "state": i.state.as_str(),
+                    "value_sat": i.value.to_sat_floor(),
+                    "value_msat": i.value.as_msat(),
+                    "memo": i.memo.as_str(),
+                    "hold_invoice": invoice::stored_preimage(network, db, &i.r_hash).is_ok(),
+                }),
+            ));
+        }
+        if changed {
+            states.push((key, i.state.clone()));
+        }
+    }
+    for payment in &payments {
+        let key = format!("payment:{}", payment.payment_hash);
+        let changed = state_tree.get(key.as_bytes())?.as_deref() != Some(payment.status.as_bytes());
+        if seeded && changed && PAYMENT_EVENT_STATES.contains(&payment.status.as_str()) {
+            events.push((
+                format!("payment.{}", payment.status.to_lowercase()),
+                json!({
+                    "payment_hash": payment.payment_hash,
+                    "status": payment.status,
+                    "value_sat": payment.amount.to_sat_floor(),
+                    "value_msat": payment.amount.as_msat(),
+                    "fee_sat": payment.fee.to_sat_floor(),
+                    "fee_msat": payment.fee.as_msat(),
+                    "failure_reason": payment.failure_reason,
+                }),
+            ));
+        }
+        if changed {
+            states.push((key, payment.status.clone()));
+        }
+    }
+
+    let mut deliveries = Vec::new();
+    for (event_type, data) in &events {
+        deliveries.extend(deliveries_for(&webhooks, network, event_type, data)?);
+    }
+
+    // States and deliveries are written together, so a crash can neither lose an event
+    // nor queue it twice
+    let deliveries_tree = db.open_tree(DELIVERIES_TREE)?;
+    (&state_tree, &deliveries_tree)
+        .transaction(|(state_tx, deliveries_tx)| {
+            for (key, state) in &states {
+                state_tx.insert(key.as_bytes(), state.as_bytes())?;
+            }
+            state_tx.insert(SEEDED_KEY, b"1")?;
+            for (key, delivery) in &deliveries {
+                deliveries_tx.insert(key.as_bytes(), delivery.as_slice())?;
+            }
+            Ok::<_, ConflictableTransactionError>(())
+        })
+        .map_err(|e| anyhow!("Failed to record webhook events: {:?}", e))?;
+    Ok(events.len())
+}
+
+/// One pending delivery per webhook for an event, as delivery tree keys and records.
+fn deliveries_for(webhooks: &[Webhook], network: &str, event_type: &str, data: &Value) -> Result<Vec<(String, Vec<u8>)>> {
+    let now = Utc::now();
+    let event_id = random_hex(16);
+    let payload = json!({
+        "id": event_id,
+        "type": event_type,
+        "created_at": now.to_rfc3339(),
+        "network": network,
+        "data": data,
+    }).to_string();
+    println!("Queueing webhook event {} ({})", event_type, event_id);
+
+    let mut deliveries = Vec::new();
+    for webhook in webhooks {
+        let delivery = Delivery {
+            event_id: event_id.clone(),
+            event_type: event_type.to_string(),
+            url: webhook.url.clone(),
+            payload: payload.clone(),
+            attempts: 0,
+            status: DeliveryStatus::Pending,
+            last_error: String::new(),
+            created_at: now.timestamp(),
+            next_attempt_at: now.timestamp(),
+        };
+        // Keys sort by creation time so the log reads in order
+        let key = format!("{:020}:{}:{}", now.timestamp_millis(), event_id, webhook.url);
+        deliveries.push((key, bincode::serialize(&delivery)?));
+    }
+    Ok(deliveries)
+}
+
+/// HMAC-SHA256 as in RFC 2104.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut key_block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        key_block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        key_block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(key_block.map(|b| b ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(key_block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+/// The `X-Webhook-Signature` value: `sha256=` followed by the hex HMAC-SHA256 of
+/// `<X-Webhook-Timestamp>.<body>` keyed with the secret.
+fn signature(secret: &str, timestamp: &str, body: &str) -> String {
+    format!("sha256={}", hex::encode(hmac_sha256(secret.as_bytes(), format!("{}.{}", timestamp, body).as_bytes())))
+}
+
+/// Sends every pending delivery that is due, signed as `signature` describes.
+pub async fn deliver_due(db: &sled::Db) -> Result<()> {
+    let webhooks = list_webhooks(db)?;
+    let tree = db.open_tree(DELIVERIES_TREE)?;
+    let client = reqwest::Client::builder().timeout(DELIVERY_TIMEOUT).build()?;
+    let now = Utc::now().timestamp();
+
+    for entry in tree.iter() {
+        let (key, value) = entry?;
+        let mut delivery: Delivery = bincode::deserialize(&value)?;
+        if delivery.status != DeliveryStatus::Pending || delivery.next_attempt_at > now {
+            continue;
+        }
+        let Some(webhook) = webhooks.iter().find(|w| w.url == delivery.url) else {
+            delivery.status = DeliveryStatus::Failed;
+            delivery.last_error = "Webhook was removed".to_string();
+            tree.insert(key, bincode::serialize(&delivery)?)?;
+            continue;
+        };
+
+        let timestamp = Utc::now().timestamp().to_string();
+        let result = client.post(&delivery.url)
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Id", &delivery.event_id)
+            .header("X-Webhook-Event", &delivery.event_type)
+            .header("X-Webhook-Timestamp", &timestamp)
+            .header("X-Webhook-Signature", signature(&webhook.secret, &timestamp, &delivery.payload))
+            .body(delivery.payload.clone())
+            .send()
+            .await;
+
+        delivery.attempts += 1;
+        match result {
+            Ok(response) if response.status().is_success() => {
+                println!("Delivered webhook {} to {}", delivery.event_id, delivery.url);
+                delivery.status = DeliveryStatus::Delivered;
+                delivery.last_error.clear();
+            }
+            other => {
+                delivery.last_error = match other {
+                    Ok(response) => format!("HTTP {}", response.status()),
+                    Err(e) => e.to_string(),
+                };
+                if delivery.attempts >= MAX_ATTEMPTS {
+                    println!("Giving up on webhook {} to {}: {}", delivery.event_id, delivery.url, delivery.last_error);
+                    delivery.status = DeliveryStatus::Failed;
+                } else {
+                    let backoff = (INITIAL_RETRY_SECS << (delivery.attempts - 1)).min(MAX_RETRY_SECS);
+                    println!("Webhook {} to {} failed ({}), retrying in {}s", delivery.event_id, delivery.url, delivery.last_error, backoff);
+                    delivery.next_attempt_at = Utc::now().timestamp() + backoff;
+                }
+            }
+        }
+        tree.insert(key, bincode::serialize(&delivery)?)?;
+    }
+    prune(&tree)
+}
+
+fn prune(tree: &sled::Tree) -> Result<()> {
+    let excess = tree.len().saturating_sub(DELIVERY_LOG_LIMIT);
+    let mut removed = 0;
+    for entry in tree.iter() {
+        if removed >= excess {
+            break;
+        }
+        let (key, value) = entry?;
+        // Never drop a delivery that is still being retried
+        if bincode::deserialize::<Delivery>(&value).is_ok_and(|d| d.status != DeliveryStatus::Pending) {
+            tree.remove(key)?;
+            removed += 1;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hmac_sha256_rfc_4231_vectors() {
+        let long_key = [0xaa; 131];
+        let cases: [(&[u8], &[u8], &str); 6] = [
+            (&[0x0b; 20], b"Hi There", "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"),
+            (b"Jefe", b"what do ya want for nothing?", "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"),
+            (&[0xaa; 20], &[0xdd; 50], "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe"),
+            (
+                &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25],
+                &[0xcd; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            ),
+            (&long_key, b"Test Using Larger Than Block-Size Key - Hash Key First", "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"),
+            (
+                &long_key,
+                b"This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.",
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ];
+        for (key, data, expected) in cases {
+            assert_eq!(hex::encode(hmac_sha256(key, data)), expected);
+        }
+    }
+
+    #[test]
+    fn signs_timestamp_dot_body() {
+        assert_eq!(
+            signature("whsec_test", "1700000000", r#"{"event":"invoice.settled"}"#),
+            "sha256=39876fb1cc0a71c73ebc9f6b654b10b3c49e2b44cd32e238f38d9109dc213b74"
+        );
+    }
+}
@@ -1,8 +1,8 @@
-use anyhow::{Context, Result};
-use std::process::Command;
+use anyhow::Result;
 
 const SERVICE_NAME: &str = "com.btc.litd";
 
 pub fn start_windows_service(network: &str) -> Result<()> {
+    println!("Service {} is not managed by the app on Windows yet ({})", SERVICE_NAME, network);
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,40 @@
+use std::process::Command;
+
+/// Runs the app headless with its data directory in a fresh temporary home.
+fn headless(args: &[&str]) -> std::process::Output {
+    let home = tempfile::tempdir().unwrap();
+    Command::new(env!("CARGO_BIN_EXE_lnd-htlc-ui"))
+        .env("HOME", home.path())
+        .env("XDG_DATA_HOME", home.path().join("data"))
+        .arg("--headless")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn json_documents(stdout: &[u8]) -> Vec<serde_json::Value> {
+    serde_json::Deserializer::from_slice(stdout)
+        .into_iter::<serde_json::Value>()
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| panic!("stdout is not JSON ({}): {}", e, String::from_utf8_lossy(stdout)))
+}
+
+#[test]
+fn json_stdout_is_a_single_document() {
+    // A fresh database runs every migration first, their messages must not reach stdout
+    let output = headless(&["--json", "preimage"]);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let documents = json_documents(&output.stdout);
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0]["preimage"].as_str().map(str::len), Some(64));
+    assert_eq!(documents[0]["payment_hash"].as_str().map(str::len), Some(64));
+}
+
+#[test]
+fn json_errors_are_a_single_document() {
+    let output = headless(&["--json", "no-such-command"]);
+    assert!(!output.status.success());
+    let documents = json_documents(&output.stdout);
+    assert_eq!(documents.len(), 1);
+    assert!(documents[0]["error"].as_str().unwrap().contains("Unknown command"));
+}